@@ -0,0 +1,66 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+//! Loads the [`AccessPolicy`] of the streamer from a policy file and reloads it when the file
+//! changes
+
+use crate::config::AccessPolicyFile;
+use log::{info, warn};
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use up_rust::{UCode, UStatus};
+use up_streamer::{AccessPolicy, UStreamer};
+
+pub(crate) fn load(file_path: &str) -> Result<AccessPolicy, UStatus> {
+    let contents = fs::read_to_string(file_path).map_err(|e| {
+        UStatus::fail_with_code(
+            UCode::NOT_FOUND,
+            format!("Unable to read access policy file: {e:?}"),
+        )
+    })?;
+    let policy_file: AccessPolicyFile = json5::from_str(&contents).map_err(|e| {
+        UStatus::fail_with_code(
+            UCode::INVALID_ARGUMENT,
+            format!("Unable to parse access policy file: {e:?}"),
+        )
+    })?;
+    Ok(policy_file.access_policy())
+}
+
+fn modified(file_path: &str) -> Option<SystemTime> {
+    fs::metadata(file_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// checks the policy file for changes every `interval`, keeping the policy in effect if the
+// changed file can't be loaded
+pub(crate) async fn watch(file_path: String, interval: Duration, streamer: Arc<UStreamer>) {
+    let mut loaded = modified(&file_path);
+    loop {
+        tokio::time::sleep(interval).await;
+        let current = modified(&file_path);
+        if current == loaded {
+            continue;
+        }
+        loaded = current;
+        match load(&file_path) {
+            Ok(access_policy) => {
+                streamer.set_access_policy(access_policy);
+                info!("Reloaded access policy from {file_path}");
+            }
+            Err(err) => warn!("Keeping the access policy in effect, {}", err.get_message()),
+        }
+    }
+}
@@ -12,6 +12,13 @@
  ********************************************************************************/
 
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use up_rust::{UMessageType, UPayloadFormat, UPriority};
+use up_streamer::{
+    AccessPolicy, AccessRule, Deduplication, ForwardingRuleOptions, LoopDetection, MessageFilter,
+    OverflowPolicy, PriorityScheduling, QueueConfig, RateLimit, RateLimitAction, RequestTimeouts,
+    RetryPolicy,
+};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
@@ -20,12 +27,48 @@ pub struct Config {
     pub(crate) streamer_uuri: StreamerUuri,
     pub(crate) usubscription_config: USubscriptionConfig,
     pub(crate) transports: Transports,
+    #[serde(default)]
+    pub(crate) metrics: Option<MetricsConfig>,
+    #[serde(default)]
+    pub(crate) dead_letters: Option<DeadLettersConfig>,
+    #[serde(default)]
+    pub(crate) tracing: Option<TracingConfig>,
+    #[serde(default)]
+    pub(crate) access_policy: Option<AccessPolicyConfig>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct UpStreamerConfig {
     pub(crate) message_queue_size: u16,
+    // runs the forwarder of each out transport on its own thread instead of as a task
+    #[serde(default)]
+    pub(crate) dedicated_forwarder_threads: bool,
+    #[serde(default)]
+    pub(crate) loop_detection: Option<LoopDetectionConfig>,
+    #[serde(default)]
+    pub(crate) request_timeouts: Option<RequestTimeoutsConfig>,
+    // answers requests which can't be forwarded with an error response
+    #[serde(default)]
+    pub(crate) error_responses: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct LoopDetectionConfig {
+    #[serde(default)]
+    pub(crate) capacity: Option<usize>,
+    #[serde(default)]
+    pub(crate) log_loops: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct RequestTimeoutsConfig {
+    #[serde(default)]
+    pub(crate) max_in_flight: Option<usize>,
+    #[serde(default)]
+    pub(crate) late_response_window_ms: Option<u64>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -42,6 +85,59 @@ pub struct USubscriptionConfig {
     pub(crate) file_path: String,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub(crate) address: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DeadLettersConfig {
+    pub(crate) file_path: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TracingConfig {
+    // the OTLP/HTTP traces endpoint of the collector, e.g. http://localhost:4318/v1/traces
+    pub(crate) otlp_endpoint: String,
+    #[serde(default)]
+    pub(crate) service_name: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AccessPolicyConfig {
+    pub(crate) file_path: String,
+    // how often the policy file is checked for changes, it is only loaded at startup if unset
+    #[serde(default)]
+    pub(crate) reload_interval_ms: Option<u64>,
+}
+
+// the contents of the file an AccessPolicyConfig points to
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AccessPolicyFile {
+    #[serde(default)]
+    pub(crate) allow: Vec<AccessRuleConfig>,
+    #[serde(default)]
+    pub(crate) deny: Vec<AccessRuleConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AccessRuleConfig {
+    #[serde(default)]
+    pub(crate) source_authority: Option<String>,
+    #[serde(default)]
+    pub(crate) sink_authority: Option<String>,
+    #[serde(default)]
+    pub(crate) out_endpoint: Option<String>,
+    #[serde(default)]
+    pub(crate) filter: Option<FilterConfig>,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct Transports {
@@ -70,7 +166,168 @@ pub struct MqttTransport {
 pub struct EndpointConfig {
     pub(crate) authority: String,
     pub(crate) endpoint: String,
-    pub(crate) forwarding: Vec<String>,
+    pub(crate) forwarding: Vec<ForwardingConfig>,
+    #[serde(default)]
+    pub(crate) queue: Option<EndpointQueueConfig>,
+    #[serde(default)]
+    pub(crate) retry: Option<EndpointRetryConfig>,
+    #[serde(default)]
+    pub(crate) rate_limit: Option<RateLimitConfig>,
+    #[serde(default)]
+    pub(crate) deduplication: Option<DeduplicationConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointQueueConfig {
+    #[serde(default)]
+    pub(crate) max_messages: Option<usize>,
+    #[serde(default)]
+    pub(crate) max_bytes: Option<usize>,
+    #[serde(default)]
+    pub(crate) overflow_policy: OverflowPolicyConfig,
+    #[serde(default)]
+    pub(crate) scheduling: SchedulingConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointRetryConfig {
+    pub(crate) max_attempts: u32,
+    #[serde(default)]
+    pub(crate) initial_backoff_ms: Option<u64>,
+    #[serde(default)]
+    pub(crate) max_backoff_ms: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DeduplicationConfig {
+    #[serde(default)]
+    pub(crate) window_ms: Option<u64>,
+    #[serde(default)]
+    pub(crate) max_entries: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum OverflowPolicyConfig {
+    #[default]
+    DropOldest,
+    DropNewest,
+    Block {
+        timeout_ms: u64,
+    },
+}
+
+/// Either just the name of the endpoint to forward to, or that name along with the options of
+/// the forwarding rule
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ForwardingConfig {
+    Endpoint(String),
+    Rule(ForwardingRuleConfig),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ForwardingRuleConfig {
+    pub(crate) endpoint: String,
+    #[serde(default)]
+    pub(crate) filter: Option<FilterConfig>,
+    #[serde(default)]
+    pub(crate) rate_limit: Option<RateLimitConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub(crate) messages_per_second: Option<u32>,
+    #[serde(default)]
+    pub(crate) message_burst: Option<u32>,
+    #[serde(default)]
+    pub(crate) bytes_per_second: Option<u64>,
+    #[serde(default)]
+    pub(crate) byte_burst: Option<u64>,
+    #[serde(default)]
+    pub(crate) action: RateLimitActionConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitActionConfig {
+    #[default]
+    Delay,
+    Drop,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FilterConfig {
+    #[serde(default)]
+    pub(crate) message_types: Vec<MessageTypeConfig>,
+    #[serde(default)]
+    pub(crate) ue_ids: Vec<IdRangeConfig>,
+    #[serde(default)]
+    pub(crate) resource_ids: Vec<IdRangeConfig>,
+    #[serde(default)]
+    pub(crate) priorities: Vec<PriorityConfig>,
+    #[serde(default)]
+    pub(crate) payload_formats: Vec<PayloadFormatConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct IdRangeConfig {
+    pub(crate) min: u32,
+    pub(crate) max: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageTypeConfig {
+    Publish,
+    Notification,
+    Request,
+    Response,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum PriorityConfig {
+    Cs0,
+    Cs1,
+    Cs2,
+    Cs3,
+    Cs4,
+    Cs5,
+    Cs6,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadFormatConfig {
+    Unspecified,
+    ProtobufWrappedInAny,
+    Protobuf,
+    Json,
+    Someip,
+    SomeipTlv,
+    Raw,
+    Text,
+    Shm,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum SchedulingConfig {
+    #[default]
+    Fifo,
+    Strict,
+    Weighted {
+        weights: [u32; 7],
+    },
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -84,6 +341,208 @@ pub struct MqttConfigDetails {
     pub(crate) username: String,
 }
 
+impl EndpointConfig {
+    pub fn queue_config(&self) -> QueueConfig {
+        let Some(queue) = &self.queue else {
+            return QueueConfig::default();
+        };
+        QueueConfig {
+            max_messages: queue.max_messages,
+            max_bytes: queue.max_bytes,
+            overflow_policy: match queue.overflow_policy {
+                OverflowPolicyConfig::DropOldest => OverflowPolicy::DropOldest,
+                OverflowPolicyConfig::DropNewest => OverflowPolicy::DropNewest,
+                OverflowPolicyConfig::Block { timeout_ms } => {
+                    OverflowPolicy::Block(Duration::from_millis(timeout_ms))
+                }
+            },
+            scheduling: match queue.scheduling {
+                SchedulingConfig::Fifo => PriorityScheduling::Fifo,
+                SchedulingConfig::Strict => PriorityScheduling::Strict,
+                SchedulingConfig::Weighted { weights } => PriorityScheduling::Weighted(weights),
+            },
+        }
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        let Some(retry) = &self.retry else {
+            return default;
+        };
+        RetryPolicy {
+            max_attempts: retry.max_attempts,
+            initial_backoff: retry
+                .initial_backoff_ms
+                .map_or(default.initial_backoff, Duration::from_millis),
+            max_backoff: retry
+                .max_backoff_ms
+                .map_or(default.max_backoff, Duration::from_millis),
+            ..default
+        }
+    }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit.map(RateLimitConfig::rate_limit)
+    }
+
+    pub fn deduplication(&self) -> Option<Deduplication> {
+        let deduplication = self.deduplication?;
+        let default = Deduplication::default();
+        Some(Deduplication {
+            window: deduplication
+                .window_ms
+                .map_or(default.window, Duration::from_millis),
+            max_entries: deduplication.max_entries.unwrap_or(default.max_entries),
+        })
+    }
+}
+
+impl RateLimitConfig {
+    pub fn rate_limit(self) -> RateLimit {
+        RateLimit {
+            messages_per_second: self.messages_per_second,
+            message_burst: self.message_burst,
+            bytes_per_second: self.bytes_per_second,
+            byte_burst: self.byte_burst,
+            action: match self.action {
+                RateLimitActionConfig::Delay => RateLimitAction::Delay,
+                RateLimitActionConfig::Drop => RateLimitAction::Drop,
+            },
+        }
+    }
+}
+
+impl LoopDetectionConfig {
+    pub fn loop_detection(self) -> LoopDetection {
+        let defaults = LoopDetection::default();
+        LoopDetection {
+            capacity: self.capacity.unwrap_or(defaults.capacity),
+            log_loops: self.log_loops,
+        }
+    }
+}
+
+impl RequestTimeoutsConfig {
+    pub fn request_timeouts(self) -> RequestTimeouts {
+        let defaults = RequestTimeouts::default();
+        RequestTimeouts {
+            max_in_flight: self.max_in_flight.unwrap_or(defaults.max_in_flight),
+            late_response_window: self
+                .late_response_window_ms
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.late_response_window),
+        }
+    }
+}
+
+impl ForwardingConfig {
+    pub fn endpoint(&self) -> &str {
+        match self {
+            ForwardingConfig::Endpoint(endpoint) => endpoint,
+            ForwardingConfig::Rule(rule) => &rule.endpoint,
+        }
+    }
+
+    pub fn rule_options(&self) -> ForwardingRuleOptions {
+        let mut rule_options = ForwardingRuleOptions::default();
+        let ForwardingConfig::Rule(rule) = self else {
+            return rule_options;
+        };
+        if let Some(filter) = &rule.filter {
+            rule_options = rule_options.with_filter(filter.message_filter());
+        }
+        if let Some(rate_limit) = rule.rate_limit {
+            rule_options = rule_options.with_rate_limit(rate_limit.rate_limit());
+        }
+        rule_options
+    }
+}
+
+impl FilterConfig {
+    pub fn message_filter(&self) -> MessageFilter {
+        MessageFilter {
+            message_types: self
+                .message_types
+                .iter()
+                .map(|message_type| match message_type {
+                    MessageTypeConfig::Publish => UMessageType::UMESSAGE_TYPE_PUBLISH,
+                    MessageTypeConfig::Notification => UMessageType::UMESSAGE_TYPE_NOTIFICATION,
+                    MessageTypeConfig::Request => UMessageType::UMESSAGE_TYPE_REQUEST,
+                    MessageTypeConfig::Response => UMessageType::UMESSAGE_TYPE_RESPONSE,
+                })
+                .collect(),
+            ue_ids: self.ue_ids.iter().map(|ids| ids.min..=ids.max).collect(),
+            resource_ids: self
+                .resource_ids
+                .iter()
+                .map(|ids| ids.min..=ids.max)
+                .collect(),
+            priorities: self
+                .priorities
+                .iter()
+                .map(|priority| match priority {
+                    PriorityConfig::Cs0 => UPriority::UPRIORITY_CS0,
+                    PriorityConfig::Cs1 => UPriority::UPRIORITY_CS1,
+                    PriorityConfig::Cs2 => UPriority::UPRIORITY_CS2,
+                    PriorityConfig::Cs3 => UPriority::UPRIORITY_CS3,
+                    PriorityConfig::Cs4 => UPriority::UPRIORITY_CS4,
+                    PriorityConfig::Cs5 => UPriority::UPRIORITY_CS5,
+                    PriorityConfig::Cs6 => UPriority::UPRIORITY_CS6,
+                })
+                .collect(),
+            payload_formats: self
+                .payload_formats
+                .iter()
+                .map(|payload_format| match payload_format {
+                    PayloadFormatConfig::Unspecified => UPayloadFormat::UPAYLOAD_FORMAT_UNSPECIFIED,
+                    PayloadFormatConfig::ProtobufWrappedInAny => {
+                        UPayloadFormat::UPAYLOAD_FORMAT_PROTOBUF_WRAPPED_IN_ANY
+                    }
+                    PayloadFormatConfig::Protobuf => UPayloadFormat::UPAYLOAD_FORMAT_PROTOBUF,
+                    PayloadFormatConfig::Json => UPayloadFormat::UPAYLOAD_FORMAT_JSON,
+                    PayloadFormatConfig::Someip => UPayloadFormat::UPAYLOAD_FORMAT_SOMEIP,
+                    PayloadFormatConfig::SomeipTlv => UPayloadFormat::UPAYLOAD_FORMAT_SOMEIP_TLV,
+                    PayloadFormatConfig::Raw => UPayloadFormat::UPAYLOAD_FORMAT_RAW,
+                    PayloadFormatConfig::Text => UPayloadFormat::UPAYLOAD_FORMAT_TEXT,
+                    PayloadFormatConfig::Shm => UPayloadFormat::UPAYLOAD_FORMAT_SHM,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl AccessPolicyFile {
+    pub fn access_policy(&self) -> AccessPolicy {
+        AccessPolicy {
+            allow: self
+                .allow
+                .iter()
+                .map(AccessRuleConfig::access_rule)
+                .collect(),
+            deny: self
+                .deny
+                .iter()
+                .map(AccessRuleConfig::access_rule)
+                .collect(),
+        }
+    }
+}
+
+impl AccessRuleConfig {
+    pub fn access_rule(&self) -> AccessRule {
+        AccessRule {
+            source_authority: self.source_authority.clone(),
+            sink_authority: self.sink_authority.clone(),
+            out_endpoint: self.out_endpoint.clone(),
+            filter: self
+                .filter
+                .as_ref()
+                .map(FilterConfig::message_filter)
+                .unwrap_or_default(),
+        }
+    }
+}
+
 impl MqttTransport {
     pub fn load_mqtt_details(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let config_contents = std::fs::read_to_string(&self.config_file)?;
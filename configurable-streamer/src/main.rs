@@ -11,21 +11,30 @@
  * SPDX-License-Identifier: Apache-2.0
  ********************************************************************************/
 
+mod access_policy;
 mod config;
+mod metrics;
+mod telemetry;
 
-use crate::config::Config;
+use crate::config::{AccessPolicyConfig, Config};
 use clap::Parser;
-use log::info;
+use log::{info, warn};
 use std::io::Read;
 use std::sync::Arc;
-use std::thread;
+use std::time::Duration;
 use std::{collections::HashMap, fs::File};
-use up_rust::{UCode, UStatus, UTransport};
-use up_streamer::{Endpoint, UStreamer};
+use up_rust::core::usubscription::{
+    RESOURCE_ID_SUBSCRIPTION_CHANGE, USUBSCRIPTION_TYPE_ID, USUBSCRIPTION_VERSION_MAJOR,
+};
+use up_rust::{UCode, UStatus, UTransport, UUri};
+use up_streamer::{Endpoint, FileDeadLetterSink, ForwarderRuntime, RuleChange, UStreamer};
 use up_transport_mqtt5::{Mqtt5Transport, Mqtt5TransportOptions, MqttClientOptions};
 use up_transport_zenoh::{zenoh_config::Config as ZenohConfig, UPTransportZenoh};
 use usubscription_static_file::USubscriptionStaticFile;
 
+// how long the messages still queued on ctrl-c are given to be sent
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Parser)]
 #[command()]
 struct StreamerArgs {
@@ -59,16 +68,45 @@ async fn main() -> Result<(), UStatus> {
     })?;
     config.transports.mqtt.load_mqtt_details().unwrap();
 
+    // kept until the streamer exits, dropping it would stop the export
+    let _tracer_provider = config.tracing.take().map(telemetry::init).transpose()?;
+
     let subscription_path = config.usubscription_config.file_path;
     let usubscription = Arc::new(USubscriptionStaticFile::new(subscription_path));
 
     // Start the streamer instance.
-    let mut streamer = UStreamer::new(
+    let mut streamer = UStreamer::new_async(
         "up-streamer",
         config.up_streamer_config.message_queue_size,
         usubscription,
     )
-    .expect("Failed to create uStreamer");
+    .await?;
+    if config.up_streamer_config.dedicated_forwarder_threads {
+        streamer = streamer.with_forwarder_runtime(ForwarderRuntime::DedicatedThreads);
+    }
+    if let Some(loop_detection) = config.up_streamer_config.loop_detection {
+        streamer = streamer.with_loop_detection(loop_detection.loop_detection());
+    }
+    if let Some(request_timeouts) = config.up_streamer_config.request_timeouts {
+        streamer = streamer.with_request_timeouts(request_timeouts.request_timeouts());
+    }
+    if config.up_streamer_config.error_responses {
+        streamer = streamer.with_error_responses();
+    }
+
+    if let Some(dead_letters) = &config.dead_letters {
+        let dead_letter_sink = FileDeadLetterSink::new(&dead_letters.file_path).map_err(|e| {
+            UStatus::fail_with_code(
+                UCode::INVALID_ARGUMENT,
+                format!("Unable to open dead letter file: {e:?}"),
+            )
+        })?;
+        streamer.set_dead_letter_sink(Arc::new(dead_letter_sink));
+    }
+
+    if let Some(access_policy_config) = &config.access_policy {
+        streamer.set_access_policy(access_policy::load(&access_policy_config.file_path)?);
+    }
 
     let mut endpoints: HashMap<String, Endpoint> = HashMap::new();
 
@@ -114,13 +152,50 @@ async fn main() -> Result<(), UStatus> {
     mqtt5_transport.connect().await?;
     let mqtt5_transport: Arc<dyn UTransport> = Arc::new(mqtt5_transport);
 
+    // keep the streamer's subscriptions up to date with changes published by uSubscription
+    let subscription_change_topic = UUri::try_from_parts(
+        &config.streamer_uuri.authority,
+        USUBSCRIPTION_TYPE_ID,
+        USUBSCRIPTION_VERSION_MAJOR,
+        RESOURCE_ID_SUBSCRIPTION_CHANGE,
+    )
+    .map_err(|e| {
+        UStatus::fail_with_code(
+            UCode::INVALID_ARGUMENT,
+            format!("Unable to build subscription change topic: {e:?}"),
+        )
+    })?;
+    let subscription_update_listener = streamer.subscription_update_listener();
+    for transport in [&zenoh_transport, &mqtt5_transport] {
+        if let Err(err) = transport
+            .register_listener(
+                &subscription_change_topic,
+                None,
+                subscription_update_listener.clone(),
+            )
+            .await
+        {
+            warn!(
+                "Unable to listen for subscription changes, subscriptions will not be updated: {err:?}"
+            );
+        }
+    }
+
     // build all zenoh endpoints
     for zenoh_endpoint_config in config.transports.zenoh.endpoints.clone() {
-        let endpoint = Endpoint::new(
+        let mut endpoint = Endpoint::new(
             &zenoh_endpoint_config.endpoint,
             &zenoh_endpoint_config.authority,
             zenoh_transport.clone(),
-        );
+        )
+        .with_queue_config(zenoh_endpoint_config.queue_config())
+        .with_retry_policy(zenoh_endpoint_config.retry_policy());
+        if let Some(rate_limit) = zenoh_endpoint_config.rate_limit() {
+            endpoint = endpoint.with_rate_limit(rate_limit);
+        }
+        if let Some(deduplication) = zenoh_endpoint_config.deduplication() {
+            endpoint = endpoint.with_deduplication(deduplication);
+        }
         if endpoints
             .insert(zenoh_endpoint_config.endpoint.clone(), endpoint)
             .is_some()
@@ -137,11 +212,19 @@ async fn main() -> Result<(), UStatus> {
 
     // build all mqtt endpoints
     for mqtt_endpoint_config in config.transports.mqtt.endpoints.clone() {
-        let endpoint = Endpoint::new(
+        let mut endpoint = Endpoint::new(
             &mqtt_endpoint_config.endpoint,
             &mqtt_endpoint_config.authority,
             mqtt5_transport.clone(),
-        );
+        )
+        .with_queue_config(mqtt_endpoint_config.queue_config())
+        .with_retry_policy(mqtt_endpoint_config.retry_policy());
+        if let Some(rate_limit) = mqtt_endpoint_config.rate_limit() {
+            endpoint = endpoint.with_rate_limit(rate_limit);
+        }
+        if let Some(deduplication) = mqtt_endpoint_config.deduplication() {
+            endpoint = endpoint.with_deduplication(deduplication);
+        }
         if endpoints
             .insert(mqtt_endpoint_config.endpoint.clone(), endpoint)
             .is_some()
@@ -156,31 +239,75 @@ async fn main() -> Result<(), UStatus> {
         }
     }
 
-    // set up the endpoint forwarding for zenoh
+    // set up the endpoint forwarding for zenoh and mqtt, all or nothing
+    let mut rule_changes = Vec::new();
     for zenoh_endpoint in config.transports.zenoh.endpoints {
         for forwarding in zenoh_endpoint.forwarding {
             let left_endpoint = endpoints.get(&zenoh_endpoint.endpoint).unwrap();
-            let right_endpoint = endpoints.get(&forwarding).unwrap();
-            streamer
-                .add_forwarding_rule(left_endpoint.to_owned(), right_endpoint.to_owned())
-                .await
-                .expect("Could not add forwarding rule from {zenoh.endpoint} to {forwarding}");
+            let right_endpoint = endpoints.get(forwarding.endpoint()).unwrap();
+            rule_changes.push(RuleChange::Add {
+                r#in: left_endpoint.to_owned(),
+                out: right_endpoint.to_owned(),
+                options: forwarding.rule_options(),
+            });
         }
     }
-
-    // set up the endpoint forwarding for mqtt
     for mqtt5_endpoint in config.transports.mqtt.endpoints {
         for forwarding in mqtt5_endpoint.forwarding {
             let left_endpoint = endpoints.get(&mqtt5_endpoint.endpoint).unwrap();
-            let right_endpoint = endpoints.get(&forwarding).unwrap();
-            streamer
-                .add_forwarding_rule(left_endpoint.to_owned(), right_endpoint.to_owned())
-                .await
-                .expect("Could not add forwarding rule from {mqtt.endpoint} to {forwarding}");
+            let right_endpoint = endpoints.get(forwarding.endpoint()).unwrap();
+            rule_changes.push(RuleChange::Add {
+                r#in: left_endpoint.to_owned(),
+                out: right_endpoint.to_owned(),
+                options: forwarding.rule_options(),
+            });
         }
     }
+    streamer.apply_rules(rule_changes).await.map_err(|e| {
+        UStatus::fail_with_code(
+            e.get_code(),
+            format!("Could not add forwarding rules: {}", e.get_message()),
+        )
+    })?;
+
+    let streamer = Arc::new(streamer);
+    let mut background_tasks = Vec::new();
+    if let Some(AccessPolicyConfig {
+        file_path,
+        reload_interval_ms: Some(reload_interval_ms),
+    }) = config.access_policy
+    {
+        background_tasks.push(tokio::spawn(access_policy::watch(
+            file_path,
+            Duration::from_millis(reload_interval_ms),
+            streamer.clone(),
+        )));
+    }
+
+    if let Some(metrics_config) = config.metrics {
+        let streamer = streamer.clone();
+        background_tasks.push(tokio::spawn(async move {
+            if let Err(err) = metrics::serve(metrics_config.address, streamer).await {
+                warn!("Metrics endpoint stopped: {err:?}");
+            }
+        }));
+    }
 
-    thread::park();
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        warn!("Unable to listen for ctrl-c, running until killed: {err:?}");
+        std::future::pending::<()>().await;
+    }
+    info!("Shutting down up-linux-streamer-configurable");
+
+    // the background tasks share the streamer, which only shuts down once they ended
+    for background_task in background_tasks {
+        background_task.abort();
+        let _ = background_task.await;
+    }
+    match Arc::into_inner(streamer) {
+        Some(mut streamer) => streamer.shutdown(SHUTDOWN_DRAIN_TIMEOUT).await,
+        None => warn!("Streamer still in use, exiting without draining its queues"),
+    }
 
     Ok(())
 }
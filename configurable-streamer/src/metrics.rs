@@ -0,0 +1,482 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+//! Minimal OpenMetrics exporter serving the [`UStreamerStats`] of a [`UStreamer`] on `/metrics`
+
+use log::{debug, info, warn};
+use std::fmt::Write;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use up_streamer::{ForwardingCounts, UStreamer, UStreamerStats};
+
+const METRICS_PATH: &str = "/metrics";
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+const MAX_REQUEST_HEADER_SIZE: usize = 8 * 1024;
+
+pub(crate) async fn serve(address: String, streamer: Arc<UStreamer>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(&address).await?;
+    info!("Serving metrics on http://{address}{METRICS_PATH}");
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!("Unable to accept metrics connection: {err}");
+                continue;
+            }
+        };
+        let streamer = streamer.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(stream, streamer).await {
+                debug!("Metrics connection from {peer} failed: {err}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, streamer: Arc<UStreamer>) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 || request.len() + read > MAX_REQUEST_HEADER_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (method, path) = (request_line.next(), request_line.next());
+
+    let response = match (method, path) {
+        (Some("GET"), Some(METRICS_PATH)) => {
+            let body = encode(&streamer.stats().await);
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {OPENMETRICS_CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        }
+        (Some("GET"), _) => {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        }
+        _ => "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            .to_string(),
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+struct CounterFamily {
+    name: &'static str,
+    help: &'static str,
+    value: fn(&ForwardingCounts) -> u64,
+}
+
+const RULE_COUNTERS: [CounterFamily; 12] = [
+    CounterFamily {
+        name: "up_streamer_rule_messages_received",
+        help: "Messages received by the listener of a forwarding rule.",
+        value: |counts| counts.received,
+    },
+    CounterFamily {
+        name: "up_streamer_rule_messages_forwarded",
+        help: "Messages of a forwarding rule sent successfully on the out transport.",
+        value: |counts| counts.forwarded,
+    },
+    CounterFamily {
+        name: "up_streamer_rule_messages_dropped",
+        help: "Messages of a forwarding rule discarded before sending.",
+        value: |counts| counts.dropped,
+    },
+    CounterFamily {
+        name: "up_streamer_rule_messages_filtered",
+        help: "Messages of a forwarding rule rejected by its filter or dropped by its transformer.",
+        value: |counts| counts.filtered,
+    },
+    CounterFamily {
+        name: "up_streamer_rule_messages_denied",
+        help: "Messages of a forwarding rule not permitted by the access policy.",
+        value: |counts| counts.denied,
+    },
+    CounterFamily {
+        name: "up_streamer_rule_messages_expired",
+        help: "Messages of a forwarding rule whose time-to-live elapsed before sending.",
+        value: |counts| counts.expired,
+    },
+    CounterFamily {
+        name: "up_streamer_rule_messages_looped",
+        help: "Messages which came back to a forwarding rule they already passed.",
+        value: |counts| counts.looped,
+    },
+    CounterFamily {
+        name: "up_streamer_rule_messages_duplicates",
+        help: "Messages of a forwarding rule suppressed as duplicates on the out transport.",
+        value: |counts| counts.duplicates,
+    },
+    CounterFamily {
+        name: "up_streamer_rule_messages_paused",
+        help: "Messages received while a forwarding rule was paused, buffered or dropped.",
+        value: |counts| counts.paused,
+    },
+    CounterFamily {
+        name: "up_streamer_rule_messages_throttled",
+        help: "Messages of a forwarding rule delayed or dropped by a rate limit.",
+        value: |counts| counts.throttled,
+    },
+    CounterFamily {
+        name: "up_streamer_rule_send_failures",
+        help: "Messages of a forwarding rule which failed to send on the out transport.",
+        value: |counts| counts.send_failures,
+    },
+    CounterFamily {
+        name: "up_streamer_rule_send_retries",
+        help: "Failed send attempts of a forwarding rule's messages which were retried.",
+        value: |counts| counts.retries,
+    },
+];
+
+const OUT_TRANSPORT_COUNTERS: [CounterFamily; 8] = [
+    CounterFamily {
+        name: "up_streamer_out_transport_messages_received",
+        help: "Messages taken from the queue of an out transport.",
+        value: |counts| counts.received,
+    },
+    CounterFamily {
+        name: "up_streamer_out_transport_messages_forwarded",
+        help: "Messages sent successfully on an out transport.",
+        value: |counts| counts.forwarded,
+    },
+    CounterFamily {
+        name: "up_streamer_out_transport_messages_dropped",
+        help: "Messages discarded from the queue of an out transport.",
+        value: |counts| counts.dropped,
+    },
+    CounterFamily {
+        name: "up_streamer_out_transport_messages_expired",
+        help: "Messages whose time-to-live elapsed in the queue of an out transport.",
+        value: |counts| counts.expired,
+    },
+    CounterFamily {
+        name: "up_streamer_out_transport_messages_duplicates",
+        help: "Messages suppressed as duplicates by the queue of an out transport.",
+        value: |counts| counts.duplicates,
+    },
+    CounterFamily {
+        name: "up_streamer_out_transport_messages_throttled",
+        help: "Messages delayed or dropped by the rate limit of an out transport.",
+        value: |counts| counts.throttled,
+    },
+    CounterFamily {
+        name: "up_streamer_out_transport_send_failures",
+        help: "Messages which failed to send on an out transport.",
+        value: |counts| counts.send_failures,
+    },
+    CounterFamily {
+        name: "up_streamer_out_transport_send_retries",
+        help: "Failed send attempts on an out transport which were retried.",
+        value: |counts| counts.retries,
+    },
+];
+
+/// Renders `stats` in the OpenMetrics text format
+pub(crate) fn encode(stats: &UStreamerStats) -> String {
+    let mut output = String::new();
+
+    let rule_labels: Vec<_> = stats
+        .rules
+        .iter()
+        .map(|rule| {
+            labels(&[
+                ("in_endpoint", &rule.in_endpoint),
+                ("in_authority", &rule.in_authority),
+                ("out_endpoint", &rule.out_endpoint),
+                ("out_authority", &rule.out_authority),
+            ])
+        })
+        .collect();
+    let out_transport_labels: Vec<_> = stats
+        .out_transports
+        .iter()
+        .map(|out_transport| labels(&[("out_endpoints", &out_transport.out_endpoints.join(","))]))
+        .collect();
+
+    for family in &RULE_COUNTERS {
+        write_family_header(&mut output, family.name, "counter", family.help);
+        for (rule, labels) in stats.rules.iter().zip(&rule_labels) {
+            let _ = writeln!(
+                output,
+                "{}_total{labels} {}",
+                family.name,
+                (family.value)(&rule.counts)
+            );
+        }
+    }
+
+    write_family_header(
+        &mut output,
+        "up_streamer_rule_registered_listeners",
+        "gauge",
+        "Source / sink filters the listener of a forwarding rule is registered for.",
+    );
+    for (rule, labels) in stats.rules.iter().zip(&rule_labels) {
+        let _ = writeln!(
+            output,
+            "up_streamer_rule_registered_listeners{labels} {}",
+            rule.registered_listeners
+        );
+    }
+
+    for family in &OUT_TRANSPORT_COUNTERS {
+        write_family_header(&mut output, family.name, "counter", family.help);
+        for (out_transport, labels) in stats.out_transports.iter().zip(&out_transport_labels) {
+            let _ = writeln!(
+                output,
+                "{}_total{labels} {}",
+                family.name,
+                (family.value)(&out_transport.counts)
+            );
+        }
+    }
+
+    write_family_header(
+        &mut output,
+        "up_streamer_out_transport_queue_depth",
+        "gauge",
+        "Messages waiting in the queue of an out transport.",
+    );
+    for (out_transport, labels) in stats.out_transports.iter().zip(&out_transport_labels) {
+        let _ = writeln!(
+            output,
+            "up_streamer_out_transport_queue_depth{labels} {}",
+            out_transport.queue_depth
+        );
+    }
+
+    write_family_header(
+        &mut output,
+        "up_streamer_out_transport_queued_bytes",
+        "gauge",
+        "Serialized size in bytes of the messages waiting in the queue of an out transport.",
+    );
+    for (out_transport, labels) in stats.out_transports.iter().zip(&out_transport_labels) {
+        let _ = writeln!(
+            output,
+            "up_streamer_out_transport_queued_bytes{labels} {}",
+            out_transport.queued_bytes
+        );
+    }
+
+    let priority_class_labels: Vec<Vec<_>> = stats
+        .out_transports
+        .iter()
+        .map(|out_transport| {
+            out_transport
+                .priority_classes
+                .iter()
+                .map(|priority_class| {
+                    let priority = format!("{:?}", priority_class.priority);
+                    labels(&[
+                        ("out_endpoints", &out_transport.out_endpoints.join(",")),
+                        ("priority", priority.trim_start_matches("UPRIORITY_")),
+                    ])
+                })
+                .collect()
+        })
+        .collect();
+
+    write_family_header(
+        &mut output,
+        "up_streamer_out_transport_priority_queue_depth",
+        "gauge",
+        "Messages of a priority class waiting in the queue of an out transport.",
+    );
+    for (out_transport, labels) in stats.out_transports.iter().zip(&priority_class_labels) {
+        for (priority_class, labels) in out_transport.priority_classes.iter().zip(labels) {
+            let _ = writeln!(
+                output,
+                "up_streamer_out_transport_priority_queue_depth{labels} {}",
+                priority_class.queue_depth
+            );
+        }
+    }
+
+    write_family_header(
+        &mut output,
+        "up_streamer_out_transport_queue_wait_seconds",
+        "summary",
+        "Time messages of a priority class spent in the queue of an out transport.",
+    );
+    for (out_transport, labels) in stats.out_transports.iter().zip(&priority_class_labels) {
+        for (priority_class, labels) in out_transport.priority_classes.iter().zip(labels) {
+            let _ = writeln!(
+                output,
+                "up_streamer_out_transport_queue_wait_seconds_sum{labels} {}",
+                priority_class.wait_time.as_secs_f64()
+            );
+            let _ = writeln!(
+                output,
+                "up_streamer_out_transport_queue_wait_seconds_count{labels} {}",
+                priority_class.dequeued
+            );
+        }
+    }
+
+    output.push_str("# EOF\n");
+    output
+}
+
+fn write_family_header(output: &mut String, name: &str, metric_type: &str, help: &str) {
+    let _ = writeln!(output, "# TYPE {name} {metric_type}");
+    let _ = writeln!(output, "# HELP {name} {help}");
+}
+
+fn labels(labels: &[(&str, &str)]) -> String {
+    let labels: Vec<_> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{name}=\"{value}\"")
+        })
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encode;
+    use std::time::Duration;
+    use up_rust::UPriority;
+    use up_streamer::{
+        ForwardingCounts, ForwardingRuleStats, OutTransportStats, PriorityClassStats,
+        UStreamerStats,
+    };
+
+    #[test]
+    fn encodes_rule_and_out_transport_metrics() {
+        let stats = UStreamerStats {
+            rules: vec![ForwardingRuleStats {
+                in_endpoint: "endpoint_zenoh_1".to_string(),
+                in_authority: "authority-b".to_string(),
+                out_endpoint: "endpoint_mqtt_1".to_string(),
+                out_authority: "authority-a".to_string(),
+                registered_listeners: 3,
+                counts: ForwardingCounts {
+                    received: 13,
+                    forwarded: 7,
+                    dropped: 1,
+                    filtered: 3,
+                    denied: 4,
+                    expired: 0,
+                    looped: 2,
+                    duplicates: 0,
+                    paused: 1,
+                    throttled: 0,
+                    send_failures: 2,
+                    retries: 5,
+                },
+            }],
+            out_transports: vec![OutTransportStats {
+                out_endpoints: vec![
+                    "endpoint_mqtt_1".to_string(),
+                    "endpoint_mqtt_\"2".to_string(),
+                ],
+                queue_depth: 4,
+                queued_bytes: 512,
+                priority_classes: vec![PriorityClassStats {
+                    priority: UPriority::UPRIORITY_CS4,
+                    queue_depth: 3,
+                    dequeued: 8,
+                    wait_time: Duration::from_millis(1500),
+                }],
+                counts: ForwardingCounts {
+                    received: 9,
+                    forwarded: 7,
+                    dropped: 0,
+                    filtered: 0,
+                    denied: 0,
+                    expired: 0,
+                    looped: 0,
+                    duplicates: 4,
+                    paused: 0,
+                    throttled: 6,
+                    send_failures: 2,
+                    retries: 5,
+                },
+            }],
+        };
+
+        let encoded = encode(&stats);
+        let rule_labels = "{in_endpoint=\"endpoint_zenoh_1\",in_authority=\"authority-b\",out_endpoint=\"endpoint_mqtt_1\",out_authority=\"authority-a\"}";
+        let out_transport_labels = "{out_endpoints=\"endpoint_mqtt_1,endpoint_mqtt_\\\"2\"}";
+
+        assert!(encoded.contains("# TYPE up_streamer_rule_messages_received counter\n"));
+        assert!(encoded.contains(&format!(
+            "up_streamer_rule_messages_received_total{rule_labels} 13\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_rule_messages_filtered_total{rule_labels} 3\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_rule_messages_denied_total{rule_labels} 4\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_rule_messages_looped_total{rule_labels} 2\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_rule_messages_paused_total{rule_labels} 1\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_rule_send_failures_total{rule_labels} 2\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_rule_send_retries_total{rule_labels} 5\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_rule_registered_listeners{rule_labels} 3\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_out_transport_queue_depth{out_transport_labels} 4\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_out_transport_queued_bytes{out_transport_labels} 512\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_out_transport_messages_forwarded_total{out_transport_labels} 7\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_out_transport_messages_duplicates_total{out_transport_labels} 4\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_out_transport_messages_throttled_total{out_transport_labels} 6\n"
+        )));
+        let priority_class_labels =
+            "{out_endpoints=\"endpoint_mqtt_1,endpoint_mqtt_\\\"2\",priority=\"CS4\"}";
+        assert!(encoded.contains(&format!(
+            "up_streamer_out_transport_priority_queue_depth{priority_class_labels} 3\n"
+        )));
+        assert!(encoded.contains("# TYPE up_streamer_out_transport_queue_wait_seconds summary\n"));
+        assert!(encoded.contains(&format!(
+            "up_streamer_out_transport_queue_wait_seconds_sum{priority_class_labels} 1.5\n"
+        )));
+        assert!(encoded.contains(&format!(
+            "up_streamer_out_transport_queue_wait_seconds_count{priority_class_labels} 8\n"
+        )));
+        assert!(encoded.ends_with("# EOF\n"));
+    }
+}
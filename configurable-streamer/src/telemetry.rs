@@ -0,0 +1,61 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+//! Exports the spans of the forwarding path to an OpenTelemetry collector over OTLP/HTTP
+
+use crate::config::TracingConfig;
+use log::info;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing_subscriber::layer::SubscriberExt;
+use up_rust::{UCode, UStatus};
+
+const DEFAULT_SERVICE_NAME: &str = "up-streamer";
+
+// installs a global tracing subscriber exporting every span to the configured collector
+pub(crate) fn init(tracing_config: TracingConfig) -> Result<SdkTracerProvider, UStatus> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&tracing_config.otlp_endpoint)
+        .build()
+        .map_err(|e| {
+            UStatus::fail_with_code(
+                UCode::INVALID_ARGUMENT,
+                format!("Unable to create OTLP exporter: {e:?}"),
+            )
+        })?;
+    let service_name = tracing_config
+        .service_name
+        .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build();
+
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer(DEFAULT_SERVICE_NAME)));
+    tracing::subscriber::set_global_default(subscriber).map_err(|e| {
+        UStatus::fail_with_code(
+            UCode::ALREADY_EXISTS,
+            format!("Unable to install tracing subscriber: {e:?}"),
+        )
+    })?;
+
+    info!(
+        "Exporting traces to {} over OTLP",
+        tracing_config.otlp_endpoint
+    );
+    Ok(provider)
+}
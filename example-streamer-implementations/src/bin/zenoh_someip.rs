@@ -60,11 +60,12 @@ async fn main() -> Result<(), UStatus> {
     let usubscription = Arc::new(USubscriptionStaticFile::new(subscription_path));
 
     // Start the streamer instance.
-    let mut streamer = UStreamer::new(
+    let mut streamer = UStreamer::new_async(
         "up-streamer",
         config.up_streamer_config.message_queue_size,
         usubscription,
     )
+    .await
     .expect("Failed to create uStreamer");
 
     let streamer_uuri = UUri::try_from_parts(
@@ -142,6 +142,63 @@ impl SubscriptionCache {
         })
     }
 
+    /// Adds a subscription to the cache, keyed by the authority of its subscriber.
+    ///
+    /// Returns `true` if the subscription was not yet present.
+    pub fn add_subscription(
+        &self,
+        subscription_information: SubscriptionInformation,
+    ) -> Result<bool, UStatus> {
+        let subscriber_authority_name =
+            Self::subscriber_authority_name(&subscription_information.subscriber)?;
+        let mut map = self.subscription_cache_map.lock().map_err(|_| {
+            UStatus::fail_with_code(UCode::INTERNAL, "Unable to lock subscription cache")
+        })?;
+        Ok(map
+            .entry(subscriber_authority_name)
+            .or_insert_with(HashSet::new)
+            .replace(subscription_information)
+            .is_none())
+    }
+
+    /// Removes the subscription of `subscriber` to `topic` from the cache.
+    ///
+    /// Returns `true` if the subscription was present.
+    pub fn remove_subscription(
+        &self,
+        topic: &UUri,
+        subscriber: &SubscriberInfo,
+    ) -> Result<bool, UStatus> {
+        let subscriber_authority_name = Self::subscriber_authority_name(subscriber)?;
+        let mut map = self.subscription_cache_map.lock().map_err(|_| {
+            UStatus::fail_with_code(UCode::INTERNAL, "Unable to lock subscription cache")
+        })?;
+        let Some(subscriptions) = map.get_mut(&subscriber_authority_name) else {
+            return Ok(false);
+        };
+        let removed = subscriptions.remove(&SubscriptionInformation {
+            topic: topic.clone(),
+            subscriber: subscriber.clone(),
+            status: SubscriptionStatus::default(),
+            attributes: SubscribeAttributes::default(),
+            config: EventDeliveryConfig::default(),
+        });
+        if subscriptions.is_empty() {
+            map.remove(&subscriber_authority_name);
+        }
+        Ok(removed)
+    }
+
+    fn subscriber_authority_name(subscriber: &SubscriberInfo) -> Result<String, UStatus> {
+        match subscriber.uri.as_ref() {
+            Some(uri) => Ok(uri.authority_name.clone()),
+            None => Err(UStatus::fail_with_code(
+                UCode::INVALID_ARGUMENT,
+                "Unable to retrieve authority name",
+            )),
+        }
+    }
+
     pub fn fetch_cache_entry(&self, entry: String) -> Option<HashSet<SubscriptionInformation>> {
         let map = match self.subscription_cache_map.lock() {
             Ok(map) => map,
@@ -182,7 +239,7 @@ impl SubscriptionCache {
 
 #[cfg(test)]
 mod tests {
-    use super::SubscriptionCache;
+    use super::{SubscriptionCache, SubscriptionInformation};
     use std::str::FromStr;
     use up_rust::core::usubscription::{FetchSubscriptionsResponse, SubscriberInfo, Subscription};
     use up_rust::UUri;
@@ -264,6 +321,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn add_and_remove_single_subscription() {
+        let cache = SubscriptionCache::new(FetchSubscriptionsResponse {
+            subscriptions: vec![subscription(
+                "//authority-a/5BA0/1/8001",
+                "//authority-b/5678/1/1234",
+            )],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let added = subscription("//authority-a/5BA1/1/8001", "//authority-b/5678/1/1234");
+        let information = SubscriptionInformation {
+            topic: added.topic.clone().unwrap(),
+            subscriber: added.subscriber.clone().unwrap(),
+            status: Default::default(),
+            attributes: Default::default(),
+            config: Default::default(),
+        };
+
+        assert!(cache.add_subscription(information.clone()).unwrap());
+        assert!(!cache.add_subscription(information.clone()).unwrap());
+        assert_eq!(topics_for_authority(&cache, "authority-b").len(), 2);
+
+        assert!(cache
+            .remove_subscription(&information.topic, &information.subscriber)
+            .unwrap());
+        assert!(!cache
+            .remove_subscription(&information.topic, &information.subscriber)
+            .unwrap());
+        assert_eq!(
+            topics_for_authority(&cache, "authority-b"),
+            vec![UUri::from_str("//authority-a/5BA0/1/8001").unwrap()]
+        );
+    }
+
+    #[test]
+    fn removing_last_subscription_clears_authority_entry() {
+        let cache = SubscriptionCache::new(FetchSubscriptionsResponse {
+            subscriptions: vec![subscription(
+                "//authority-a/5BA0/1/8001",
+                "//authority-b/5678/1/1234",
+            )],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let removed = subscription("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234");
+        assert!(cache
+            .remove_subscription(&removed.topic.unwrap(), &removed.subscriber.unwrap())
+            .unwrap());
+        assert!(cache.fetch_cache_entry("authority-b".to_string()).is_none());
+        assert!(cache
+            .fetch_cache_entry_with_wildcard("authority-b")
+            .is_none());
+    }
+
     #[test]
     #[allow(clippy::mutable_key_type)]
     fn wildcard_lookup_merges_exact_and_wildcard_rows() {
@@ -148,11 +148,13 @@ pub mod plugin {
         let subscription_path = config.usubscription_config.file_path;
         let usubscription = Arc::new(USubscriptionStaticFile::new(subscription_path));
 
-        let mut streamer = match UStreamer::new(
+        let mut streamer = match UStreamer::new_async(
             "up-linux-streamer",
             config.up_streamer_config.message_queue_size,
             usubscription,
-        ) {
+        )
+        .await
+        {
             Ok(streamer) => streamer,
             Err(error) => panic!("Failed to create uStreamer: {}", error),
         };
@@ -0,0 +1,238 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use crate::filter::MessageFilter;
+use std::sync::{Arc, RwLock};
+use up_rust::UMessage;
+
+/// Decides which messages may cross from the in [`Endpoint`][crate::Endpoint] of a forwarding
+/// rule to its out [`Endpoint`][crate::Endpoint]
+///
+/// Set with [`UStreamer::set_access_policy`][crate::UStreamer::set_access_policy]. A message is
+/// denied if it matches any of the `deny` rules, and otherwise allowed if `allow` is empty or it
+/// matches any of the `allow` rules. So the default policy allows everything, while a policy
+/// with `allow` rules only lets through what they list.
+///
+/// Denied messages are counted as `denied` in the [`ForwardingCounts`][crate::ForwardingCounts]
+/// of their rule, logged at debug level and handed to the
+/// [`DeadLetterSink`][crate::DeadLetterSink] as
+/// [`DeadLetterReason::Denied`][crate::DeadLetterReason::Denied].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessPolicy {
+    pub allow: Vec<AccessRule>,
+    pub deny: Vec<AccessRule>,
+}
+
+impl AccessPolicy {
+    /// Whether `message` may be forwarded to the out [`Endpoint`][crate::Endpoint] named
+    /// `out_endpoint` with `out_authority`
+    pub fn permits(&self, message: &UMessage, out_endpoint: &str, out_authority: &str) -> bool {
+        !self
+            .deny
+            .iter()
+            .any(|rule| rule.matches(message, out_endpoint, out_authority))
+            && (self.allow.is_empty()
+                || self
+                    .allow
+                    .iter()
+                    .any(|rule| rule.matches(message, out_endpoint, out_authority)))
+    }
+}
+
+/// An entry of the `allow` or `deny` list of an [`AccessPolicy`]
+///
+/// Matches the messages between the given authorities which pass its `filter` and are forwarded
+/// to the given out [`Endpoint`][crate::Endpoint]. A criterion left out matches any message.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessRule {
+    /// Authority of the source of the message
+    pub source_authority: Option<String>,
+    /// Authority of the sink of the message, or of the out [`Endpoint`][crate::Endpoint] for
+    /// messages without a sink, i.e. publishes
+    pub sink_authority: Option<String>,
+    /// Name of the out [`Endpoint`][crate::Endpoint] the message is forwarded to, and so of the
+    /// `UTransport` it is sent over
+    pub out_endpoint: Option<String>,
+    /// Message types, services and resources the rule applies to, see [`MessageFilter`]
+    pub filter: MessageFilter,
+}
+
+impl AccessRule {
+    /// Whether this rule applies to `message`, forwarded to the out
+    /// [`Endpoint`][crate::Endpoint] named `out_endpoint` with `out_authority`
+    pub fn matches(&self, message: &UMessage, out_endpoint: &str, out_authority: &str) -> bool {
+        let attributes = message.attributes.get_or_default();
+        let source_authority = attributes
+            .source
+            .as_ref()
+            .map(|source| source.authority_name.as_str());
+        let sink_authority = attributes
+            .sink
+            .as_ref()
+            .map_or(out_authority, |sink| sink.authority_name.as_str());
+
+        self.source_authority
+            .as_deref()
+            .is_none_or(|authority| source_authority == Some(authority))
+            && self
+                .sink_authority
+                .as_deref()
+                .is_none_or(|authority| sink_authority == authority)
+            && self
+                .out_endpoint
+                .as_deref()
+                .is_none_or(|endpoint| out_endpoint == endpoint)
+            && self.filter.matches(message)
+    }
+}
+
+// the AccessPolicy of a UStreamer, shared with the listeners of all of its forwarding rules so
+// that replacing it applies to them right away
+#[derive(Default)]
+pub(crate) struct AccessControl {
+    policy: RwLock<Arc<AccessPolicy>>,
+}
+
+impl AccessControl {
+    pub(crate) fn set_policy(&self, policy: AccessPolicy) {
+        *self.policy.write().unwrap() = Arc::new(policy);
+    }
+
+    pub(crate) fn permits(
+        &self,
+        message: &UMessage,
+        out_endpoint: &str,
+        out_authority: &str,
+    ) -> bool {
+        let policy = self.policy.read().unwrap().clone();
+        policy.permits(message, out_endpoint, out_authority)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccessPolicy, AccessRule};
+    use crate::MessageFilter;
+    use std::str::FromStr;
+    use up_rust::{UMessage, UMessageBuilder, UMessageType, UUri};
+
+    fn request(method: &str) -> UMessage {
+        UMessageBuilder::request(
+            UUri::from_str(method).unwrap(),
+            UUri::from_str("//cloud/5678/1/0").unwrap(),
+            1000,
+        )
+        .build()
+        .unwrap()
+    }
+
+    fn publish(topic: &str) -> UMessage {
+        UMessageBuilder::publish(UUri::from_str(topic).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    fn authority_pair(source_authority: &str, sink_authority: &str) -> AccessRule {
+        AccessRule {
+            source_authority: Some(source_authority.to_string()),
+            sink_authority: Some(sink_authority.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_policy_permits_everything() {
+        let policy = AccessPolicy::default();
+        assert!(policy.permits(
+            &request("//vehicle/D1A6/1/3"),
+            "vehicle-endpoint",
+            "vehicle"
+        ));
+        assert!(policy.permits(&publish("//vehicle/D1A6/1/8001"), "cloud-endpoint", "cloud"));
+    }
+
+    #[test]
+    fn allow_list_permits_only_matching_messages() {
+        let policy = AccessPolicy {
+            allow: vec![
+                authority_pair("vehicle", "cloud"),
+                AccessRule {
+                    filter: MessageFilter {
+                        message_types: vec![UMessageType::UMESSAGE_TYPE_REQUEST],
+                        ue_ids: vec![0xD1A6..=0xD1A6],
+                        ..Default::default()
+                    },
+                    ..authority_pair("cloud", "vehicle")
+                },
+            ],
+            deny: vec![],
+        };
+
+        // publishes have no sink, they are checked against the out authority
+        assert!(policy.permits(&publish("//vehicle/1234/1/8001"), "cloud-endpoint", "cloud"));
+        assert!(!policy.permits(
+            &publish("//vehicle/1234/1/8001"),
+            "backend-endpoint",
+            "backend"
+        ));
+        assert!(policy.permits(
+            &request("//vehicle/D1A6/1/3"),
+            "vehicle-endpoint",
+            "vehicle"
+        ));
+        assert!(!policy.permits(
+            &request("//vehicle/1234/1/3"),
+            "vehicle-endpoint",
+            "vehicle"
+        ));
+    }
+
+    #[test]
+    fn deny_list_overrides_allow_list() {
+        let policy = AccessPolicy {
+            allow: vec![authority_pair("cloud", "vehicle")],
+            deny: vec![AccessRule {
+                filter: MessageFilter {
+                    resource_ids: vec![0x0001..=0x0001],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+        };
+
+        assert!(policy.permits(
+            &request("//vehicle/D1A6/1/3"),
+            "vehicle-endpoint",
+            "vehicle"
+        ));
+        assert!(!policy.permits(
+            &request("//vehicle/D1A6/1/1"),
+            "vehicle-endpoint",
+            "vehicle"
+        ));
+    }
+
+    #[test]
+    fn rules_restricted_to_an_out_endpoint_match_only_messages_forwarded_to_it() {
+        let policy = AccessPolicy {
+            allow: vec![],
+            deny: vec![AccessRule {
+                out_endpoint: Some("vehicle-mqtt".to_string()),
+                ..Default::default()
+            }],
+        };
+
+        assert!(policy.permits(&request("//vehicle/D1A6/1/3"), "vehicle-zenoh", "vehicle"));
+        assert!(!policy.permits(&request("//vehicle/D1A6/1/3"), "vehicle-mqtt", "vehicle"));
+    }
+}
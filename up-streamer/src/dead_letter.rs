@@ -0,0 +1,354 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use crate::observer::{DropReason, SharedObserver};
+use crate::rule::RuleId;
+use log::*;
+use protobuf::Message;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use up_rust::{UMessage, UStatus};
+
+const FILE_DEAD_LETTER_SINK_TAG: &str = "FileDeadLetterSink:";
+const FILE_DEAD_LETTER_SINK_FN_ON_DEAD_LETTER_TAG: &str = "on_dead_letter():";
+
+/// Why a message could not be forwarded
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeadLetterReason {
+    /// The payload refers to shared memory, which is not usable on another device
+    SharedMemoryPayload,
+    /// The queue of the out `UTransport` had no room for the message, as per its
+    /// [`OverflowPolicy`][crate::OverflowPolicy]
+    QueueFull,
+    /// The time-to-live of the message elapsed before it could be sent
+    Expired,
+    /// The message alone exceeds the byte limit of the queue of the out `UTransport`
+    MessageTooLarge,
+    /// The message exceeded a [`RateLimit`][crate::RateLimit] with
+    /// [`RateLimitAction::Drop`][crate::RateLimitAction::Drop]
+    RateLimited,
+    /// The message arrived while its forwarding rule was paused with
+    /// [`PauseAction::Drop`][crate::PauseAction::Drop], or its buffer was full
+    Paused,
+    /// The response arrived after its request had been answered with `DEADLINE_EXCEEDED`, see
+    /// [`RequestTimeouts`][crate::RequestTimeouts]
+    LateResponse,
+    /// The [`AccessPolicy`][crate::AccessPolicy] doesn't permit the message to cross from the
+    /// in [`Endpoint`][crate::Endpoint] of its forwarding rule to the out
+    /// [`Endpoint`][crate::Endpoint]
+    Denied,
+    /// The message was still queued when its forwarding rule was deleted or the
+    /// [`UStreamer`][crate::UStreamer] shut down
+    ShuttingDown,
+    /// Sending over the out `UTransport` failed, after all retries
+    SendFailed(UStatus),
+}
+
+impl Display for DeadLetterReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeadLetterReason::SharedMemoryPayload => write!(f, "shared memory payload"),
+            DeadLetterReason::QueueFull => write!(f, "queue full"),
+            DeadLetterReason::Expired => write!(f, "expired"),
+            DeadLetterReason::MessageTooLarge => write!(f, "message too large for queue"),
+            DeadLetterReason::RateLimited => write!(f, "rate limited"),
+            DeadLetterReason::Paused => write!(f, "forwarding rule paused"),
+            DeadLetterReason::LateResponse => write!(f, "late response"),
+            DeadLetterReason::Denied => write!(f, "denied by access policy"),
+            DeadLetterReason::ShuttingDown => write!(f, "shutting down"),
+            DeadLetterReason::SendFailed(status) => write!(
+                f,
+                "send failed: {:?} {}",
+                status.get_code(),
+                status.get_message()
+            ),
+        }
+    }
+}
+
+/// A message which could not be forwarded
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeadLetter {
+    /// The forwarding rule the message arrived on, as returned by
+    /// [`UStreamer::rules`][crate::UStreamer::rules]
+    pub rule_id: RuleId,
+    pub reason: DeadLetterReason,
+    pub message: UMessage,
+}
+
+/// Receives the messages a [`UStreamer`][crate::UStreamer] is unable to forward
+///
+/// Set with [`UStreamer::set_dead_letter_sink`][crate::UStreamer::set_dead_letter_sink].
+/// Called from the listeners and forwarders on the message path, so implementations should
+/// return quickly.
+pub trait DeadLetterSink: Send + Sync {
+    fn on_dead_letter(&self, dead_letter: DeadLetter);
+}
+
+/// [`DeadLetterSink`] keeping the most recent dead letters in memory
+pub struct RingBufferDeadLetterSink {
+    capacity: usize,
+    dead_letters: Mutex<VecDeque<DeadLetter>>,
+}
+
+impl RingBufferDeadLetterSink {
+    /// Creates a sink holding up to `capacity` dead letters, dropping the oldest ones beyond that
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            dead_letters: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Returns a copy of the dead letters held, oldest first
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Removes and returns the dead letters held, oldest first, e.g. to re-inject them
+    pub fn take(&self) -> Vec<DeadLetter> {
+        self.dead_letters.lock().unwrap().drain(..).collect()
+    }
+}
+
+impl DeadLetterSink for RingBufferDeadLetterSink {
+    fn on_dead_letter(&self, dead_letter: DeadLetter) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut dead_letters = self.dead_letters.lock().unwrap();
+        if dead_letters.len() == self.capacity {
+            dead_letters.pop_front();
+        }
+        dead_letters.push_back(dead_letter);
+    }
+}
+
+/// [`DeadLetterSink`] appending dead letters to a file
+///
+/// Each entry starts with the [`RuleId`] as big-endian `u64`, followed by two fields, each
+/// prefixed with its length as big-endian `u32`: the UTF-8 rendering of the
+/// [`DeadLetterReason`] and the protobuf serialized [`UMessage`]. Use
+/// [`FileDeadLetterSink::read`] to read them back.
+pub struct FileDeadLetterSink {
+    file: Mutex<File>,
+}
+
+/// A dead letter as read back from the file of a [`FileDeadLetterSink`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredDeadLetter {
+    pub rule_id: RuleId,
+    /// Rendering of the [`DeadLetterReason`]
+    pub reason: String,
+    pub message: UMessage,
+}
+
+impl FileDeadLetterSink {
+    /// Opens `path` for appending, creating it if necessary
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Reads all dead letters from a file written by a [`FileDeadLetterSink`]
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<StoredDeadLetter>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut stored_dead_letters = Vec::new();
+
+        loop {
+            let mut rule_id = [0u8; 8];
+            match reader.read_exact(&mut rule_id) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+            let reason = read_field(&mut reader)?;
+            let message = read_field(&mut reader)?;
+            stored_dead_letters.push(StoredDeadLetter {
+                rule_id: RuleId(u64::from_be_bytes(rule_id)),
+                reason: String::from_utf8(reason)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+                message: UMessage::parse_from_bytes(&message)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            });
+        }
+
+        Ok(stored_dead_letters)
+    }
+
+    fn encode(dead_letter: &DeadLetter) -> io::Result<Vec<u8>> {
+        let message = dead_letter
+            .message
+            .write_to_bytes()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let mut entry = dead_letter.rule_id.0.to_be_bytes().to_vec();
+        for field in [dead_letter.reason.to_string().as_bytes(), &message] {
+            let len = u32::try_from(field.len())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            entry.extend_from_slice(&len.to_be_bytes());
+            entry.extend_from_slice(field);
+        }
+        Ok(entry)
+    }
+}
+
+fn read_field(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let mut field = vec![0u8; u32::from_be_bytes(len) as usize];
+    reader.read_exact(&mut field)?;
+    Ok(field)
+}
+
+impl DeadLetterSink for FileDeadLetterSink {
+    fn on_dead_letter(&self, dead_letter: DeadLetter) {
+        // written in one go, so that a failure doesn't leave a partial entry behind
+        let write_res = Self::encode(&dead_letter)
+            .and_then(|entry| self.file.lock().unwrap().write_all(&entry));
+        if let Err(err) = write_res {
+            warn!(
+                "{FILE_DEAD_LETTER_SINK_TAG}:{FILE_DEAD_LETTER_SINK_FN_ON_DEAD_LETTER_TAG} unable to write dead letter of rule {}: {err}",
+                dead_letter.rule_id
+            );
+        }
+    }
+}
+
+// Shared by the listeners, queues and forwarders of a UStreamer, so that a sink set at any time
+// applies to all forwarding rules. Dead letters are reported to the StreamerObserver as well.
+#[derive(Default)]
+pub(crate) struct DeadLetters {
+    sink: RwLock<Option<Arc<dyn DeadLetterSink>>>,
+    observer: Arc<SharedObserver>,
+}
+
+impl DeadLetters {
+    pub(crate) fn new(observer: Arc<SharedObserver>) -> Self {
+        Self {
+            sink: RwLock::new(None),
+            observer,
+        }
+    }
+
+    pub(crate) fn set_sink(&self, sink: Arc<dyn DeadLetterSink>) {
+        *self.sink.write().unwrap() = Some(sink);
+    }
+
+    pub(crate) fn observer(&self) -> &SharedObserver {
+        &self.observer
+    }
+
+    pub(crate) fn post(&self, rule_id: RuleId, reason: DeadLetterReason, message: &UMessage) {
+        self.observer.notify(|observer| match &reason {
+            DeadLetterReason::SendFailed(status) => {
+                observer.on_send_failed(rule_id, status, message)
+            }
+            reason => observer.on_message_dropped(
+                rule_id,
+                &DropReason::DeadLetter(reason.clone()),
+                message,
+            ),
+        });
+        let Some(sink) = self.sink.read().unwrap().clone() else {
+            return;
+        };
+        sink.on_dead_letter(DeadLetter {
+            rule_id,
+            reason,
+            message: message.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DeadLetter, DeadLetterReason, DeadLetterSink, FileDeadLetterSink, RingBufferDeadLetterSink,
+    };
+    use crate::rule::RuleId;
+    use up_rust::{UCode, UMessage, UMessageBuilder, UStatus, UUri};
+
+    fn dead_letter(rule_id: u64, reason: DeadLetterReason) -> DeadLetter {
+        DeadLetter {
+            rule_id: RuleId(rule_id),
+            reason,
+            message: UMessageBuilder::publish(
+                UUri::try_from_parts("authority-a", 0x5BA0, 1, 0x8001).unwrap(),
+            )
+            .build_with_payload("payload", up_rust::UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap(),
+        }
+    }
+
+    #[test]
+    fn ring_buffer_keeps_most_recent_dead_letters() {
+        let sink = RingBufferDeadLetterSink::new(2);
+
+        sink.on_dead_letter(dead_letter(1, DeadLetterReason::QueueFull));
+        sink.on_dead_letter(dead_letter(2, DeadLetterReason::ShuttingDown));
+        sink.on_dead_letter(dead_letter(3, DeadLetterReason::MessageTooLarge));
+
+        let rule_ids: Vec<_> = sink
+            .dead_letters()
+            .into_iter()
+            .map(|dead_letter| dead_letter.rule_id)
+            .collect();
+        assert_eq!(rule_ids, vec![RuleId(2), RuleId(3)]);
+        assert_eq!(sink.take().len(), 2);
+        assert!(sink.dead_letters().is_empty());
+    }
+
+    #[test]
+    fn file_sink_round_trips_dead_letters() {
+        let path = std::env::temp_dir().join(format!(
+            "up-streamer-dead-letters-{}.bin",
+            up_rust::UUID::build().to_hyphenated_string()
+        ));
+
+        let written = [
+            dead_letter(1, DeadLetterReason::SharedMemoryPayload),
+            dead_letter(
+                2,
+                DeadLetterReason::SendFailed(UStatus::fail_with_code(
+                    UCode::UNAVAILABLE,
+                    "broker down",
+                )),
+            ),
+        ];
+        {
+            let sink = FileDeadLetterSink::new(&path).unwrap();
+            sink.on_dead_letter(written[0].clone());
+        }
+        // appends to what is already there
+        FileDeadLetterSink::new(&path)
+            .unwrap()
+            .on_dead_letter(written[1].clone());
+
+        let read = FileDeadLetterSink::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].rule_id, RuleId(1));
+        assert_eq!(read[0].reason, "shared memory payload");
+        assert_eq!(read[0].message, written[0].message);
+        assert_eq!(read[1].rule_id, RuleId(2));
+        assert_eq!(read[1].reason, "send failed: UNAVAILABLE broker down");
+        assert_ne!(read[1].message, UMessage::default());
+    }
+}
@@ -0,0 +1,42 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use std::time::Duration;
+
+/// Suppression of duplicate messages sent over an out `UTransport`, e.g. copies of the same
+/// message arriving over redundant in [`Endpoint`][crate::Endpoint]s
+///
+/// Set on an out [`Endpoint`][crate::Endpoint] with
+/// [`Endpoint::with_deduplication`][crate::Endpoint::with_deduplication]. The queue of the out
+/// `UTransport` remembers the id of every message it receives and drops any further message
+/// with the same id, counting it as `duplicates` in the
+/// [`ForwardingCounts`][crate::ForwardingCounts]. The id of a message the queue drops is
+/// forgotten again, so that its retransmission gets through. Messages without an id are never
+/// considered duplicates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deduplication {
+    /// How long the id of a message is remembered
+    pub window: Duration,
+    /// Maximum number of ids remembered at once, bounding the memory used, the oldest are
+    /// forgotten first
+    pub max_entries: usize,
+}
+
+impl Default for Deduplication {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(10),
+            max_entries: 10_000,
+        }
+    }
+}
@@ -11,6 +11,10 @@
  * SPDX-License-Identifier: Apache-2.0
  ********************************************************************************/
 
+use crate::dedup::Deduplication;
+use crate::queue::QueueConfig;
+use crate::rate_limit::RateLimit;
+use crate::retry::RetryPolicy;
 use log::*;
 use std::sync::Arc;
 use up_rust::UTransport;
@@ -91,6 +95,10 @@ pub struct Endpoint {
     pub(crate) name: String,
     pub(crate) authority: String,
     pub(crate) transport: Arc<dyn UTransport>,
+    pub(crate) queue_config: QueueConfig,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) rate_limit: Option<RateLimit>,
+    pub(crate) deduplication: Option<Deduplication>,
 }
 
 impl Endpoint {
@@ -107,6 +115,50 @@ impl Endpoint {
             name: name.to_string(),
             authority: authority.to_string(),
             transport,
+            queue_config: QueueConfig::default(),
+            retry_policy: RetryPolicy::default(),
+            rate_limit: None,
+            deduplication: None,
         }
     }
+
+    /// Sets the limits and [`OverflowPolicy`][crate::OverflowPolicy] of the queue of messages
+    /// waiting to be sent when this `Endpoint` is used as `out` in a forwarding rule
+    ///
+    /// All forwarding rules onto the same `UTransport` share one queue, which is configured by
+    /// the first rule added for it.
+    pub fn with_queue_config(mut self, queue_config: QueueConfig) -> Self {
+        self.queue_config = queue_config;
+        self
+    }
+
+    /// Sets how sending over this endpoint's `UTransport` is retried when it is used as `out` in
+    /// a forwarding rule
+    ///
+    /// Like the queue, the [`RetryPolicy`] is shared by all forwarding rules onto the same
+    /// `UTransport` and set by the first rule added for it.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Limits the rate of sending over this endpoint's `UTransport` when it is used as `out` in
+    /// a forwarding rule
+    ///
+    /// Like the queue, the [`RateLimit`] is shared by all forwarding rules onto the same
+    /// `UTransport` and set by the first rule added for it.
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Suppresses duplicate messages sent over this endpoint's `UTransport` when it is used as
+    /// `out` in a forwarding rule
+    ///
+    /// Like the queue, the [`Deduplication`] is shared by all forwarding rules onto the same
+    /// `UTransport` and set by the first rule added for it.
+    pub fn with_deduplication(mut self, deduplication: Deduplication) -> Self {
+        self.deduplication = Some(deduplication);
+        self
+    }
 }
@@ -0,0 +1,129 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use crate::dead_letter::DeadLetterReason;
+use crate::request_timeout::InFlightRequests;
+use crate::rule::RuleId;
+use log::*;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use up_rust::{UCode, UMessage, UMessageBuilder, UTransport};
+
+const ERROR_RESPONDER_TAG: &str = "ErrorResponder:";
+const ERROR_RESPONDER_FN_RESPOND_TAG: &str = "respond():";
+
+// answers the requests a forwarding rule is unable to forward with an error response, sent back
+// over the in UTransport of the rule
+pub(crate) struct ErrorResponder {
+    in_transport: Arc<dyn UTransport>,
+    // runs the sends, as requests are dropped outside of async code too
+    runtime: Handle,
+    // which no longer need to time out once answered
+    in_flight_requests: Option<Arc<InFlightRequests>>,
+}
+
+impl Debug for ErrorResponder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ErrorResponder").finish_non_exhaustive()
+    }
+}
+
+impl ErrorResponder {
+    pub(crate) fn new(in_transport: Arc<dyn UTransport>, runtime: Handle) -> Self {
+        Self {
+            in_transport,
+            runtime,
+            in_flight_requests: None,
+        }
+    }
+
+    pub(crate) fn with_in_flight_requests(
+        mut self,
+        in_flight_requests: Arc<InFlightRequests>,
+    ) -> Self {
+        self.in_flight_requests = Some(in_flight_requests);
+        self
+    }
+
+    // answers `message` with `commstatus`, if it is a request
+    pub(crate) fn respond(&self, rule_id: RuleId, message: &UMessage, commstatus: UCode) {
+        let Some(attributes) = message
+            .attributes
+            .as_ref()
+            .filter(|attributes| attributes.is_request())
+        else {
+            return;
+        };
+        if let Some(in_flight_requests) = &self.in_flight_requests {
+            in_flight_requests.forget(attributes.id.get_or_default());
+        }
+
+        let request_id = attributes.id.get_or_default().to_hyphenated_string();
+        let response = match UMessageBuilder::response_for_request(attributes)
+            .with_comm_status(commstatus)
+            .build()
+        {
+            Ok(response) => response,
+            Err(err) => {
+                warn!(
+                    "{}:{}:{} Unable to build error response to request {}: {err:?}",
+                    rule_id, ERROR_RESPONDER_TAG, ERROR_RESPONDER_FN_RESPOND_TAG, request_id
+                );
+                return;
+            }
+        };
+        debug!(
+            "{}:{}:{} Responding to request {} with {:?}",
+            rule_id, ERROR_RESPONDER_TAG, ERROR_RESPONDER_FN_RESPOND_TAG, request_id, commstatus
+        );
+        let in_transport = self.in_transport.clone();
+        self.runtime.spawn(async move {
+            if let Err(err) = in_transport.send(response).await {
+                warn!(
+                    "{}:{}:{} Unable to send error response to request {}: {err:?}",
+                    rule_id, ERROR_RESPONDER_TAG, ERROR_RESPONDER_FN_RESPOND_TAG, request_id
+                );
+            }
+        });
+    }
+
+    // answers `message` if it is a request which became a dead letter for a reason worth telling
+    // the requester about
+    pub(crate) fn respond_to_dead_letter(
+        &self,
+        rule_id: RuleId,
+        reason: &DeadLetterReason,
+        message: &UMessage,
+    ) {
+        if let Some(commstatus) = commstatus(reason) {
+            self.respond(rule_id, message, commstatus);
+        }
+    }
+}
+
+// the commstatus of the error response to a request which became a dead letter for `reason`
+fn commstatus(reason: &DeadLetterReason) -> Option<UCode> {
+    match reason {
+        DeadLetterReason::QueueFull
+        | DeadLetterReason::MessageTooLarge
+        | DeadLetterReason::RateLimited => Some(UCode::RESOURCE_EXHAUSTED),
+        DeadLetterReason::Paused
+        | DeadLetterReason::ShuttingDown
+        | DeadLetterReason::SendFailed(_) => Some(UCode::UNAVAILABLE),
+        DeadLetterReason::SharedMemoryPayload => Some(UCode::INVALID_ARGUMENT),
+        DeadLetterReason::Denied => Some(UCode::PERMISSION_DENIED),
+        // the requester has given up already
+        DeadLetterReason::Expired | DeadLetterReason::LateResponse => None,
+    }
+}
@@ -0,0 +1,171 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use std::ops::RangeInclusive;
+use up_rust::{UMessage, UMessageType, UPayloadFormat, UPriority};
+
+/// Restricts which messages a forwarding rule forwards
+///
+/// Attached to a rule with
+/// [`ForwardingRuleOptions::with_filter`][crate::ForwardingRuleOptions::with_filter] or
+/// [`UStreamer::add_filtered_forwarding_rule`][crate::UStreamer::add_filtered_forwarding_rule].
+/// Every criterion left empty allows all messages, a message is forwarded only if it satisfies
+/// all of the others.
+///
+/// The `ue_ids` and `resource_ids` are matched against the URI of the service side of a message,
+/// which is the sink of a request and the source of any other message type. So
+/// `resource_ids: vec![0x0001..=0x7FFF]` allows calls to the methods of a service and their
+/// responses, but none of its publishes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageFilter {
+    pub message_types: Vec<UMessageType>,
+    pub ue_ids: Vec<RangeInclusive<u32>>,
+    pub resource_ids: Vec<RangeInclusive<u32>>,
+    /// An unspecified priority counts as [`UPriority::UPRIORITY_CS1`], the uProtocol default
+    pub priorities: Vec<UPriority>,
+    pub payload_formats: Vec<UPayloadFormat>,
+}
+
+impl MessageFilter {
+    /// Whether `message` passes this filter
+    pub fn matches(&self, message: &UMessage) -> bool {
+        let attributes = message.attributes.get_or_default();
+
+        let message_type = attributes.type_.enum_value_or_default();
+        if !self.message_types.is_empty() && !self.message_types.contains(&message_type) {
+            return false;
+        }
+
+        let service_uri = if message_type == UMessageType::UMESSAGE_TYPE_REQUEST {
+            attributes.sink.as_ref()
+        } else {
+            attributes.source.as_ref()
+        };
+        if !self.ue_ids.is_empty()
+            && !service_uri
+                .is_some_and(|uri| self.ue_ids.iter().any(|ue_ids| ue_ids.contains(&uri.ue_id)))
+        {
+            return false;
+        }
+        if !self.resource_ids.is_empty()
+            && !service_uri.is_some_and(|uri| {
+                self.resource_ids
+                    .iter()
+                    .any(|resource_ids| resource_ids.contains(&uri.resource_id))
+            })
+        {
+            return false;
+        }
+
+        let priority = match attributes.priority.enum_value_or_default() {
+            UPriority::UPRIORITY_UNSPECIFIED => UPriority::UPRIORITY_CS1,
+            priority => priority,
+        };
+        if !self.priorities.is_empty() && !self.priorities.contains(&priority) {
+            return false;
+        }
+
+        self.payload_formats.is_empty()
+            || self
+                .payload_formats
+                .contains(&attributes.payload_format.enum_value_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MessageFilter;
+    use std::str::FromStr;
+    use up_rust::{UMessageBuilder, UMessageType, UPayloadFormat, UPriority, UUri};
+
+    fn diagnostics_service() -> UUri {
+        UUri::from_str("//vehicle/D1A6/1/3").unwrap()
+    }
+
+    fn diagnostics_client() -> UUri {
+        UUri::from_str("//cloud/5678/1/0").unwrap()
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let publish = UMessageBuilder::publish(UUri::from_str("//vehicle/5BA0/1/8001").unwrap())
+            .build()
+            .unwrap();
+        assert!(MessageFilter::default().matches(&publish));
+    }
+
+    #[test]
+    fn service_criteria_apply_to_sink_of_requests_and_source_of_responses() {
+        let filter = MessageFilter {
+            message_types: vec![
+                UMessageType::UMESSAGE_TYPE_REQUEST,
+                UMessageType::UMESSAGE_TYPE_RESPONSE,
+            ],
+            ue_ids: vec![0xD1A6..=0xD1A6],
+            resource_ids: vec![0x0001..=0x7FFF],
+            ..Default::default()
+        };
+
+        let request = UMessageBuilder::request(diagnostics_service(), diagnostics_client(), 1000)
+            .build()
+            .unwrap();
+        let response = UMessageBuilder::response_for_request(request.attributes.get_or_default())
+            .build()
+            .unwrap();
+        let other_request = UMessageBuilder::request(
+            UUri::from_str("//vehicle/1234/1/3").unwrap(),
+            diagnostics_client(),
+            1000,
+        )
+        .build()
+        .unwrap();
+        let publish = UMessageBuilder::publish(UUri::from_str("//vehicle/D1A6/1/8001").unwrap())
+            .build()
+            .unwrap();
+
+        assert!(filter.matches(&request));
+        assert!(filter.matches(&response));
+        assert!(!filter.matches(&other_request));
+        assert!(!filter.matches(&publish));
+    }
+
+    #[test]
+    fn priority_and_payload_format_criteria() {
+        let filter = MessageFilter {
+            priorities: vec![UPriority::UPRIORITY_CS1, UPriority::UPRIORITY_CS4],
+            payload_formats: vec![UPayloadFormat::UPAYLOAD_FORMAT_PROTOBUF],
+            ..Default::default()
+        };
+        let topic = UUri::from_str("//vehicle/5BA0/1/8001").unwrap();
+
+        let default_priority = UMessageBuilder::publish(topic.clone())
+            .build_with_payload("", UPayloadFormat::UPAYLOAD_FORMAT_PROTOBUF)
+            .unwrap();
+        let cs4 = UMessageBuilder::publish(topic.clone())
+            .with_priority(UPriority::UPRIORITY_CS4)
+            .build_with_payload("", UPayloadFormat::UPAYLOAD_FORMAT_PROTOBUF)
+            .unwrap();
+        let cs2 = UMessageBuilder::publish(topic.clone())
+            .with_priority(UPriority::UPRIORITY_CS2)
+            .build_with_payload("", UPayloadFormat::UPAYLOAD_FORMAT_PROTOBUF)
+            .unwrap();
+        let json = UMessageBuilder::publish(topic)
+            .build_with_payload("{}", UPayloadFormat::UPAYLOAD_FORMAT_JSON)
+            .unwrap();
+
+        assert!(filter.matches(&default_priority));
+        assert!(filter.matches(&cs4));
+        assert!(!filter.matches(&cs2));
+        assert!(!filter.matches(&json));
+    }
+}
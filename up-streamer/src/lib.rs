@@ -16,8 +16,64 @@
 //! `up-streamer` implements the `UStreamer` spec to allow bridging between different
 //! transports.
 
+mod access_control;
+pub use access_control::{AccessPolicy, AccessRule};
+
+mod dead_letter;
+pub use dead_letter::{
+    DeadLetter, DeadLetterReason, DeadLetterSink, FileDeadLetterSink, RingBufferDeadLetterSink,
+    StoredDeadLetter,
+};
+
+mod dedup;
+pub use dedup::Deduplication;
+
 mod endpoint;
 pub use endpoint::Endpoint;
 
+mod error_response;
+
+mod filter;
+pub use filter::MessageFilter;
+
+mod loop_detection;
+pub use loop_detection::LoopDetection;
+
+mod observer;
+pub use observer::{DropReason, StreamerObserver};
+
+mod pause;
+pub use pause::PauseAction;
+
+mod queue;
+pub use queue::{OverflowPolicy, PriorityScheduling, QueueConfig};
+
+mod rate_limit;
+pub use rate_limit::{RateLimit, RateLimitAction};
+
+mod retry;
+pub use retry::RetryPolicy;
+
+mod recently_seen;
+
+mod request_timeout;
+pub use request_timeout::RequestTimeouts;
+
+mod rule;
+pub use rule::{ForwardingRuleDescriptor, ForwardingRuleOptions, RuleChange, RuleId, RuleState};
+
+mod runtime;
+pub use runtime::ForwarderRuntime;
+
+mod stats;
+pub use stats::{
+    ForwardingCounts, ForwardingRuleStats, OutTransportStats, PriorityClassStats, UStreamerStats,
+};
+
+mod trace_context;
+
+mod transformer;
+pub use transformer::MessageTransformer;
+
 mod ustreamer;
 pub use ustreamer::UStreamer;
@@ -0,0 +1,38 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+/// Settings for detecting messages which come back to a forwarding rule they already passed,
+/// e.g. because bidirectional rules of two streamers bounce them between endpoints
+///
+/// Set with [`UStreamer::with_loop_detection`][crate::UStreamer::with_loop_detection]. Each rule
+/// remembers the ids of the last `capacity` messages it received from its in
+/// [`Endpoint`][crate::Endpoint] and drops any message whose id it has already seen, counting it
+/// as `looped` in its [`ForwardingCounts`][crate::ForwardingCounts]. Messages without an id are
+/// never considered looping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoopDetection {
+    /// Number of message ids remembered per forwarding rule
+    pub capacity: usize,
+    /// Whether to log a warning with the path of each looping message, in addition to counting
+    /// it
+    pub log_loops: bool,
+}
+
+impl Default for LoopDetection {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            log_loops: false,
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use crate::dead_letter::DeadLetterReason;
+use crate::rule::{ForwardingRuleDescriptor, RuleId};
+use std::sync::{Arc, RwLock};
+use up_rust::{UMessage, UStatus, UUri};
+
+/// Why a forwarding rule didn't forward a message, as passed to
+/// [`StreamerObserver::on_message_dropped`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum DropReason {
+    /// The message didn't pass the rule's [`MessageFilter`][crate::MessageFilter] or was dropped
+    /// by its [`MessageTransformer`][crate::MessageTransformer]
+    Filtered,
+    /// The message came back to the rule, see [`LoopDetection`][crate::LoopDetection]
+    Looped,
+    /// A message with the same id was sent shortly before, see
+    /// [`Deduplication`][crate::Deduplication]
+    Duplicate,
+    /// The message was handed to the [`DeadLetterSink`][crate::DeadLetterSink], for any reason
+    /// but [`DeadLetterReason::SendFailed`], which is reported by
+    /// [`StreamerObserver::on_send_failed`]
+    DeadLetter(DeadLetterReason),
+}
+
+/// Receives the lifecycle and forwarding events of a [`UStreamer`][crate::UStreamer]
+///
+/// Set with [`UStreamer::set_observer`][crate::UStreamer::set_observer]. Every method does
+/// nothing by default, so implementations only override the events they are interested in.
+/// Called from the listeners and forwarders on the message path, so implementations should
+/// return quickly.
+///
+/// The message events identify their forwarding rule by the [`RuleId`] it was added with, as
+/// passed to [`StreamerObserver::on_rule_added`] and listed by
+/// [`UStreamer::rules`][crate::UStreamer::rules].
+pub trait StreamerObserver: Send + Sync {
+    /// A forwarding rule was added, including when it was moved by
+    /// [`UStreamer::refresh_endpoint`][crate::UStreamer::refresh_endpoint] or restored by
+    /// [`UStreamer::apply_rules`][crate::UStreamer::apply_rules]
+    fn on_rule_added(&self, _rule: &ForwardingRuleDescriptor) {}
+
+    /// A forwarding rule was deleted, including when the [`UStreamer`][crate::UStreamer] shut
+    /// down
+    fn on_rule_removed(&self, _rule: &ForwardingRuleDescriptor) {}
+
+    /// A listener was registered on an in `UTransport`
+    fn on_listener_registered(&self, _source_filter: &UUri, _sink_filter: Option<&UUri>) {}
+
+    /// Registering a listener on an in `UTransport` failed
+    fn on_listener_registration_failed(
+        &self,
+        _source_filter: &UUri,
+        _sink_filter: Option<&UUri>,
+        _status: &UStatus,
+    ) {
+    }
+
+    /// A message was sent successfully over the out `UTransport`
+    fn on_message_forwarded(&self, _rule_id: RuleId, _message: &UMessage) {}
+
+    /// A message was discarded before an attempt to send it was made
+    fn on_message_dropped(&self, _rule_id: RuleId, _reason: &DropReason, _message: &UMessage) {}
+
+    /// Sending a message over the out `UTransport` failed, after all retries
+    fn on_send_failed(&self, _rule_id: RuleId, _status: &UStatus, _message: &UMessage) {}
+}
+
+// the StreamerObserver of a UStreamer, shared with its listeners, queues and forwarders so that
+// an observer set at any time applies to all forwarding rules
+#[derive(Default)]
+pub(crate) struct SharedObserver {
+    observer: RwLock<Option<Arc<dyn StreamerObserver>>>,
+}
+
+impl SharedObserver {
+    pub(crate) fn set(&self, observer: Arc<dyn StreamerObserver>) {
+        *self.observer.write().unwrap() = Some(observer);
+    }
+
+    pub(crate) fn is_set(&self) -> bool {
+        self.observer.read().unwrap().is_some()
+    }
+
+    // calls `event` on the StreamerObserver, if one is set
+    pub(crate) fn notify(&self, event: impl FnOnce(&dyn StreamerObserver)) {
+        let Some(observer) = self.observer.read().unwrap().clone() else {
+            return;
+        };
+        event(observer.as_ref());
+    }
+}
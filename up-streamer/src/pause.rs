@@ -0,0 +1,195 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use up_rust::UMessage;
+
+/// What a paused forwarding rule does with the messages it receives, passed to
+/// [`UStreamer::pause_rule`][crate::UStreamer::pause_rule]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseAction {
+    /// Drops the messages, counting them as `dropped` in addition to `paused`
+    Drop,
+    /// Holds back up to `max_messages` messages and forwards them once the rule is resumed,
+    /// further messages are dropped as with [`PauseAction::Drop`]
+    Buffer { max_messages: usize },
+}
+
+// what happened to a message received by a forwarding rule
+pub(crate) enum Paused {
+    // the rule isn't paused, the message is to be forwarded
+    No(UMessage),
+    Buffered,
+    Dropped(UMessage),
+}
+
+struct PausedRule {
+    action: PauseAction,
+    buffered: VecDeque<UMessage>,
+    // the buffered messages are being forwarded, messages received meanwhile queue up behind
+    // them to keep their order
+    resuming: bool,
+}
+
+// whether a forwarding rule is paused, shared between the rule and its own listener
+#[derive(Default)]
+pub(crate) struct RulePause {
+    paused: Mutex<Option<PausedRule>>,
+}
+
+impl RulePause {
+    // pauses the rule, or changes the action of the already paused rule, keeping what it buffered
+    pub(crate) fn pause(&self, action: PauseAction) {
+        let mut paused = self.paused.lock().unwrap();
+        match paused.as_mut() {
+            Some(paused_rule) => {
+                paused_rule.action = action;
+                paused_rule.resuming = false;
+            }
+            None => {
+                *paused = Some(PausedRule {
+                    action,
+                    buffered: VecDeque::new(),
+                    resuming: false,
+                })
+            }
+        }
+    }
+
+    // starts resuming the rule, whose buffered messages are then handed out by next_buffered(),
+    // returns false if the rule isn't paused or already resuming
+    pub(crate) fn resume(&self) -> bool {
+        match self.paused.lock().unwrap().as_mut() {
+            Some(paused_rule) if !paused_rule.resuming => {
+                paused_rule.resuming = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // returns the oldest buffered message of a resuming rule, including those received since
+    // resume(), the rule is no longer paused once none are left
+    pub(crate) fn next_buffered(&self) -> Option<UMessage> {
+        let mut paused = self.paused.lock().unwrap();
+        let paused_rule = paused.as_mut().filter(|paused_rule| paused_rule.resuming)?;
+        let message = paused_rule.buffered.pop_front();
+        if message.is_none() {
+            *paused = None;
+        }
+        message
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|paused_rule| !paused_rule.resuming)
+    }
+
+    pub(crate) fn intercept(&self, message: UMessage) -> Paused {
+        let mut paused = self.paused.lock().unwrap();
+        let Some(paused_rule) = paused.as_mut() else {
+            return Paused::No(message);
+        };
+        if paused_rule.resuming {
+            paused_rule.buffered.push_back(message);
+            return Paused::Buffered;
+        }
+        match paused_rule.action {
+            PauseAction::Buffer { max_messages } if paused_rule.buffered.len() < max_messages => {
+                paused_rule.buffered.push_back(message);
+                Paused::Buffered
+            }
+            _ => Paused::Dropped(message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PauseAction, Paused, RulePause};
+    use up_rust::{UMessage, UMessageBuilder, UUri};
+
+    fn message() -> UMessage {
+        UMessageBuilder::publish(UUri::try_from_parts("a", 0x5BA0, 1, 0x8001).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    fn resume(pause: &RulePause) -> Vec<UMessage> {
+        pause.resume();
+        std::iter::from_fn(|| pause.next_buffered()).collect()
+    }
+
+    #[test]
+    fn buffers_up_to_max_messages_until_resumed() {
+        let pause = RulePause::default();
+        assert!(matches!(pause.intercept(message()), Paused::No(_)));
+
+        pause.pause(PauseAction::Buffer { max_messages: 2 });
+        let (first, second) = (message(), message());
+        assert!(matches!(pause.intercept(first.clone()), Paused::Buffered));
+        assert!(matches!(pause.intercept(second.clone()), Paused::Buffered));
+        assert!(matches!(pause.intercept(message()), Paused::Dropped(_)));
+        assert!(pause.is_paused());
+
+        assert_eq!(resume(&pause), vec![first, second]);
+        assert!(!pause.is_paused());
+        assert!(matches!(pause.intercept(message()), Paused::No(_)));
+        assert!(!pause.resume());
+    }
+
+    #[test]
+    fn changing_the_action_keeps_buffered_messages() {
+        let pause = RulePause::default();
+        pause.pause(PauseAction::Buffer { max_messages: 10 });
+        let buffered = message();
+        assert!(matches!(
+            pause.intercept(buffered.clone()),
+            Paused::Buffered
+        ));
+
+        pause.pause(PauseAction::Drop);
+        assert!(matches!(pause.intercept(message()), Paused::Dropped(_)));
+
+        assert_eq!(resume(&pause), vec![buffered]);
+    }
+
+    #[test]
+    fn messages_received_while_resuming_queue_up_behind_the_buffered_ones() {
+        let pause = RulePause::default();
+        pause.pause(PauseAction::Buffer { max_messages: 1 });
+        let (buffered, received) = (message(), message());
+        assert!(matches!(
+            pause.intercept(buffered.clone()),
+            Paused::Buffered
+        ));
+
+        assert!(pause.resume());
+        assert!(!pause.resume());
+        assert!(!pause.is_paused());
+        // beyond max_messages, as they are forwarded right after
+        assert!(matches!(
+            pause.intercept(received.clone()),
+            Paused::Buffered
+        ));
+        assert_eq!(pause.next_buffered(), Some(buffered));
+        assert_eq!(pause.next_buffered(), Some(received));
+        assert_eq!(pause.next_buffered(), None);
+
+        assert!(matches!(pause.intercept(message()), Paused::No(_)));
+    }
+}
@@ -0,0 +1,941 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use crate::dead_letter::{DeadLetterReason, DeadLetters};
+use crate::dedup::Deduplication;
+use crate::error_response::ErrorResponder;
+use crate::observer::{DropReason, SharedObserver};
+use crate::recently_seen::RecentlySeen;
+use crate::request_timeout::InFlightRequests;
+use crate::rule::RuleId;
+use crate::stats::{ForwardingCounters, PriorityClassStats};
+use log::*;
+use protobuf::Message;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use tracing::Span;
+use up_rust::{UMessage, UPriority};
+
+const FORWARDING_QUEUE_TAG: &str = "ForwardingQueue:";
+const FORWARDING_QUEUE_FN_PUSH_TAG: &str = "push():";
+
+// the priority classes CS0 to CS6, in ascending order of priority
+const PRIORITY_CLASSES: [UPriority; 7] = [
+    UPriority::UPRIORITY_CS0,
+    UPriority::UPRIORITY_CS1,
+    UPriority::UPRIORITY_CS2,
+    UPriority::UPRIORITY_CS3,
+    UPriority::UPRIORITY_CS4,
+    UPriority::UPRIORITY_CS5,
+    UPriority::UPRIORITY_CS6,
+];
+
+/// What to do with a message when the queue of an out `UTransport` is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued messages until the new message fits
+    ///
+    /// Unless the queue is scheduled [`PriorityScheduling::Fifo`], only messages of the same or
+    /// a lower priority class than the new message are discarded, lowest class first.
+    #[default]
+    DropOldest,
+    /// Discard the new message
+    DropNewest,
+    /// Wait up to the given duration for the queue to make room for the new message, then
+    /// discard it
+    Block(Duration),
+}
+
+/// In which order the queue of an out `UTransport` hands its messages to the forwarder
+///
+/// Messages are kept in one queue per priority class, `CS0` to `CS6`, messages without a
+/// priority counting as `CS1`. Within a class, messages are always sent in the order they arrived.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PriorityScheduling {
+    /// Send messages in the order they arrived, regardless of their priority
+    #[default]
+    Fifo,
+    /// Always send messages of the highest priority class queued first
+    Strict,
+    /// Share sending between the queued priority classes in proportion to their weights, given
+    /// for `CS0` to `CS6`, so that lower classes still progress under load. A weight of `0`
+    /// counts as `1`.
+    Weighted([u32; 7]),
+}
+
+/// Limits, overflow behavior and scheduling of the queue of messages waiting to be sent over an
+/// out `UTransport`
+///
+/// Set on an out [`Endpoint`][crate::Endpoint] with
+/// [`Endpoint::with_queue_config`][crate::Endpoint::with_queue_config].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueConfig {
+    /// Maximum number of queued messages, defaults to the `message_queue_size` of the
+    /// [`UStreamer`][crate::UStreamer]
+    pub max_messages: Option<usize>,
+    /// Maximum total serialized size of queued messages in bytes, unlimited by default
+    pub max_bytes: Option<usize>,
+    pub overflow_policy: OverflowPolicy,
+    pub scheduling: PriorityScheduling,
+}
+
+// A message handed over from a ForwardingListener to the TransportForwarder of its out UTransport,
+// along with the ID, counters and error responder of the forwarding rule it arrived on, the
+// in-flight requests it may answer and the span it was received in, which its send is traced as
+// part of
+#[derive(Clone, Debug)]
+pub(crate) struct QueuedMessage {
+    pub(crate) message: Arc<UMessage>,
+    pub(crate) rule_id: RuleId,
+    pub(crate) rule_stats: Arc<ForwardingCounters>,
+    pub(crate) error_responder: Option<Arc<ErrorResponder>>,
+    pub(crate) in_flight_requests: Option<Arc<InFlightRequests>>,
+    pub(crate) span: Span,
+}
+
+enum PushAttempt {
+    Closed,
+    Queued { evicted: Vec<QueuedMessage> },
+    Full,
+}
+
+struct QueueEntry {
+    queued_message: QueuedMessage,
+    size: usize,
+    // position in arrival order across all priority classes
+    sequence: u64,
+    enqueued_at: Instant,
+}
+
+#[derive(Default)]
+struct PriorityClass {
+    entries: VecDeque<QueueEntry>,
+    dequeued: u64,
+    wait_time: Duration,
+    // running credit of the smooth weighted round robin of PriorityScheduling::Weighted
+    credit: i64,
+}
+
+#[derive(Default)]
+struct QueueState {
+    classes: [PriorityClass; 7],
+    len: usize,
+    bytes: usize,
+    next_sequence: u64,
+    closed: bool,
+}
+
+impl QueueState {
+    fn remove_front(&mut self, class: usize) -> Option<QueueEntry> {
+        let entry = self.classes[class].entries.pop_front()?;
+        if self.classes[class].entries.is_empty() {
+            // an idle class doesn't keep credit from before
+            self.classes[class].credit = 0;
+        }
+        self.len -= 1;
+        self.bytes -= entry.size;
+        Some(entry)
+    }
+
+    // the class whose front message arrived first
+    fn oldest_class(&self) -> Option<usize> {
+        (0..PRIORITY_CLASSES.len())
+            .filter_map(|class| {
+                self.classes[class]
+                    .entries
+                    .front()
+                    .map(|entry| (entry.sequence, class))
+            })
+            .min()
+            .map(|(_, class)| class)
+    }
+
+    fn highest_class(&self) -> Option<usize> {
+        (0..PRIORITY_CLASSES.len())
+            .rev()
+            .find(|&class| !self.classes[class].entries.is_empty())
+    }
+
+    // smooth weighted round robin between the classes with queued messages
+    fn weighted_class(&mut self, weights: &[u32; 7]) -> Option<usize> {
+        let mut total_weight = 0;
+        let mut selected: Option<(usize, i64)> = None;
+        for (class, (priority_class, weight)) in self.classes.iter_mut().zip(weights).enumerate() {
+            if priority_class.entries.is_empty() {
+                continue;
+            }
+            let weight = i64::from((*weight).max(1));
+            total_weight += weight;
+            priority_class.credit += weight;
+            if selected.is_none_or(|(_, credit)| priority_class.credit >= credit) {
+                selected = Some((class, priority_class.credit));
+            }
+        }
+        let (selected, _) = selected?;
+        self.classes[selected].credit -= total_weight;
+        Some(selected)
+    }
+}
+
+fn priority_class(message: &UMessage) -> usize {
+    match message.attributes.priority.enum_value_or_default() {
+        UPriority::UPRIORITY_UNSPECIFIED => 1,
+        priority => PRIORITY_CLASSES
+            .iter()
+            .position(|class| *class == priority)
+            .unwrap_or(1),
+    }
+}
+
+// Bounded multi-producer, single-consumer queue in front of the TransportForwarder of an out
+// UTransport, which suppresses duplicates, applies the OverflowPolicy and PriorityScheduling and
+// accounts for every message it discards
+pub(crate) struct ForwardingQueue {
+    queue_config: QueueConfig,
+    deduplication: Option<(Deduplication, RecentlySeen)>,
+    max_messages: usize,
+    max_bytes: Option<usize>,
+    overflow_policy: OverflowPolicy,
+    scheduling: PriorityScheduling,
+    stats: Arc<ForwardingCounters>,
+    dead_letters: Arc<DeadLetters>,
+    state: Mutex<QueueState>,
+    not_empty: Notify,
+    not_full: Notify,
+}
+
+impl ForwardingQueue {
+    pub(crate) fn new(
+        default_max_messages: usize,
+        queue_config: QueueConfig,
+        stats: Arc<ForwardingCounters>,
+        dead_letters: Arc<DeadLetters>,
+    ) -> Self {
+        Self {
+            queue_config,
+            deduplication: None,
+            max_messages: queue_config
+                .max_messages
+                .unwrap_or(default_max_messages)
+                .max(1),
+            max_bytes: queue_config.max_bytes,
+            overflow_policy: queue_config.overflow_policy,
+            scheduling: queue_config.scheduling,
+            stats,
+            dead_letters,
+            state: Mutex::new(QueueState::default()),
+            not_empty: Notify::new(),
+            not_full: Notify::new(),
+        }
+    }
+
+    pub(crate) fn with_deduplication(mut self, deduplication: Deduplication) -> Self {
+        self.deduplication = Some((
+            deduplication,
+            RecentlySeen::new(deduplication.max_entries).with_window(deduplication.window),
+        ));
+        self
+    }
+
+    pub(crate) fn queue_config(&self) -> QueueConfig {
+        self.queue_config
+    }
+
+    pub(crate) fn deduplication(&self) -> Option<Deduplication> {
+        self.deduplication
+            .as_ref()
+            .map(|(deduplication, _)| *deduplication)
+    }
+
+    pub(crate) fn dead_letters(&self) -> &DeadLetters {
+        &self.dead_letters
+    }
+
+    pub(crate) fn observer(&self) -> &SharedObserver {
+        self.dead_letters.observer()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.state.lock().unwrap().len
+    }
+
+    pub(crate) fn bytes(&self) -> usize {
+        self.state.lock().unwrap().bytes
+    }
+
+    /// Queue depth and wait times per priority class, `CS0` to `CS6`
+    pub(crate) fn priority_class_stats(&self) -> Vec<PriorityClassStats> {
+        let state = self.state.lock().unwrap();
+        PRIORITY_CLASSES
+            .iter()
+            .zip(&state.classes)
+            .map(|(priority, class)| PriorityClassStats {
+                priority: *priority,
+                queue_depth: class.entries.len(),
+                dequeued: class.dequeued,
+                wait_time: class.wait_time,
+            })
+            .collect()
+    }
+
+    fn fits(&self, state: &QueueState, size: usize) -> bool {
+        self.fits_within(state.len, state.bytes, size)
+    }
+
+    fn fits_within(&self, len: usize, bytes: usize, size: usize) -> bool {
+        len < self.max_messages
+            && self
+                .max_bytes
+                .is_none_or(|max_bytes| bytes + size <= max_bytes)
+    }
+
+    // not to be called with the state locked, as it hands the message to the DeadLetterSink. The
+    // discarded message no longer counts as seen, so that a retransmission of it isn't suppressed
+    // as a duplicate
+    fn discard(&self, queued_message: &QueuedMessage, reason: DeadLetterReason) {
+        if let Some((_, recently_seen)) = &self.deduplication {
+            recently_seen.remove(&queued_message.message);
+        }
+        self.stats.record_dropped();
+        queued_message.rule_stats.record_dropped();
+        debug!(
+            "{FORWARDING_QUEUE_TAG}:{FORWARDING_QUEUE_FN_PUSH_TAG} dropping message, {reason}: {:?}",
+            queued_message.message.attributes
+        );
+        if let Some(error_responder) = &queued_message.error_responder {
+            error_responder.respond_to_dead_letter(
+                queued_message.rule_id,
+                &reason,
+                &queued_message.message,
+            );
+        }
+        self.dead_letters
+            .post(queued_message.rule_id, reason, &queued_message.message);
+    }
+
+    // the class to evict from under OverflowPolicy::DropOldest to make room for a message of
+    // class `incoming`
+    fn eviction_class(&self, state: &QueueState, incoming: usize) -> Option<usize> {
+        match self.scheduling {
+            PriorityScheduling::Fifo => state.oldest_class(),
+            PriorityScheduling::Strict | PriorityScheduling::Weighted(_) => {
+                (0..=incoming).find(|&class| !state.classes[class].entries.is_empty())
+            }
+        }
+    }
+
+    // whether evicting every message `eviction_class` may pick for a message of class `incoming`
+    // would make room for `size` bytes, so that nothing is evicted in vain
+    fn fits_after_eviction(&self, state: &QueueState, incoming: usize, size: usize) -> bool {
+        let evictable = match self.scheduling {
+            PriorityScheduling::Fifo => 0..PRIORITY_CLASSES.len(),
+            PriorityScheduling::Strict | PriorityScheduling::Weighted(_) => 0..incoming + 1,
+        };
+        let (len, bytes) = evictable
+            .flat_map(|class| &state.classes[class].entries)
+            .fold((state.len, state.bytes), |(len, bytes), entry| {
+                (len - 1, bytes - entry.size)
+            });
+        self.fits_within(len, bytes, size)
+    }
+
+    // queues the message if there is room for it, after evicting older messages under
+    // OverflowPolicy::DropOldest
+    fn try_push(&self, queued_message: &QueuedMessage, size: usize) -> PushAttempt {
+        let class = priority_class(&queued_message.message);
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return PushAttempt::Closed;
+        }
+
+        let mut evicted = Vec::new();
+        if self.overflow_policy == OverflowPolicy::DropOldest
+            && self.fits_after_eviction(&state, class, size)
+        {
+            while !self.fits(&state, size) {
+                let Some(entry) = self
+                    .eviction_class(&state, class)
+                    .and_then(|victim| state.remove_front(victim))
+                else {
+                    break;
+                };
+                evicted.push(entry.queued_message);
+            }
+        }
+
+        if !self.fits(&state, size) {
+            return PushAttempt::Full;
+        }
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state.len += 1;
+        state.bytes += size;
+        state.classes[class].entries.push_back(QueueEntry {
+            queued_message: queued_message.clone(),
+            size,
+            sequence,
+            enqueued_at: Instant::now(),
+        });
+        PushAttempt::Queued { evicted }
+    }
+
+    /// Queues `queued_message` according to the [`OverflowPolicy`], possibly discarding it or
+    /// older messages, returns whether it was queued
+    pub(crate) async fn push(&self, queued_message: QueuedMessage) -> bool {
+        if self
+            .deduplication
+            .as_ref()
+            .is_some_and(|(_, recently_seen)| {
+                recently_seen.check_and_insert(&queued_message.message)
+            })
+        {
+            self.stats.record_duplicate();
+            queued_message.rule_stats.record_duplicate();
+            debug!(
+                "{FORWARDING_QUEUE_TAG}:{FORWARDING_QUEUE_FN_PUSH_TAG} suppressing duplicate message: {:?}",
+                queued_message.message.attributes
+            );
+            self.observer().notify(|observer| {
+                observer.on_message_dropped(
+                    queued_message.rule_id,
+                    &DropReason::Duplicate,
+                    &queued_message.message,
+                )
+            });
+            return false;
+        }
+
+        let size = queued_message.message.compute_size() as usize;
+        if self.max_bytes.is_some_and(|max_bytes| size > max_bytes) {
+            self.discard(&queued_message, DeadLetterReason::MessageTooLarge);
+            return false;
+        }
+
+        let deadline = match self.overflow_policy {
+            OverflowPolicy::Block(timeout) => Some(Instant::now() + timeout),
+            _ => None,
+        };
+
+        loop {
+            // created before checking for room, so that no pop() in between is missed
+            let not_full = self.not_full.notified();
+
+            match self.try_push(&queued_message, size) {
+                PushAttempt::Closed => {
+                    self.discard(&queued_message, DeadLetterReason::ShuttingDown);
+                    return false;
+                }
+                PushAttempt::Queued { evicted } => {
+                    for oldest in &evicted {
+                        self.discard(oldest, DeadLetterReason::QueueFull);
+                    }
+                    self.not_empty.notify_one();
+                    return true;
+                }
+                PushAttempt::Full => {}
+            }
+
+            let Some(deadline) = deadline else {
+                self.discard(&queued_message, DeadLetterReason::QueueFull);
+                return false;
+            };
+            if tokio::time::timeout_at(deadline, not_full).await.is_err() {
+                self.discard(&queued_message, DeadLetterReason::QueueFull);
+                return false;
+            }
+        }
+    }
+
+    /// Waits for the next message as per the [`PriorityScheduling`], returns `None` once the
+    /// queue is closed and drained
+    pub(crate) async fn pop(&self) -> Option<QueuedMessage> {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                let class = match self.scheduling {
+                    PriorityScheduling::Fifo => state.oldest_class(),
+                    PriorityScheduling::Strict => state.highest_class(),
+                    PriorityScheduling::Weighted(weights) => state.weighted_class(&weights),
+                };
+                if let Some(entry) = class.and_then(|class| {
+                    let entry = state.remove_front(class)?;
+                    state.classes[class].dequeued += 1;
+                    state.classes[class].wait_time += entry.enqueued_at.elapsed();
+                    Some(entry)
+                }) {
+                    drop(state);
+                    self.not_full.notify_waiters();
+                    return Some(entry.queued_message);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            self.not_empty.notified().await;
+        }
+    }
+
+    /// Closes the queue, so that new messages are discarded while those already queued can
+    /// still be drained with [`pop`][Self::pop]
+    pub(crate) fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.not_empty.notify_one();
+        self.not_full.notify_waiters();
+    }
+
+    /// Discards all messages still queued
+    pub(crate) fn discard_queued(&self) {
+        let remaining: Vec<_> = {
+            let mut state = self.state.lock().unwrap();
+            state.len = 0;
+            state.bytes = 0;
+            state
+                .classes
+                .iter_mut()
+                .flat_map(|class| std::mem::take(&mut class.entries))
+                .collect()
+        };
+        for entry in &remaining {
+            self.discard(&entry.queued_message, DeadLetterReason::ShuttingDown);
+        }
+        self.not_full.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ForwardingQueue, OverflowPolicy, PriorityScheduling, QueueConfig, QueuedMessage};
+    use crate::dead_letter::{DeadLetterReason, DeadLetters, RingBufferDeadLetterSink};
+    use crate::dedup::Deduplication;
+    use crate::rule::RuleId;
+    use crate::stats::ForwardingCounters;
+    use protobuf::Message;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tracing::Span;
+    use up_rust::{UMessageBuilder, UPayloadFormat, UPriority, UUri};
+
+    fn queued_message(payload_size: usize, rule_stats: &Arc<ForwardingCounters>) -> QueuedMessage {
+        let message =
+            UMessageBuilder::publish(UUri::try_from_parts("a", 0x5BA0, 1, 0x8001).unwrap())
+                .build_with_payload(vec![0u8; payload_size], UPayloadFormat::UPAYLOAD_FORMAT_RAW)
+                .unwrap();
+        QueuedMessage {
+            message: Arc::new(message),
+            rule_id: RuleId(0),
+            rule_stats: rule_stats.clone(),
+            error_responder: None,
+            in_flight_requests: None,
+            span: Span::none(),
+        }
+    }
+
+    fn prioritized_message(
+        priority: UPriority,
+        payload_size: usize,
+        rule_stats: &Arc<ForwardingCounters>,
+    ) -> QueuedMessage {
+        let mut queued_message = queued_message(payload_size, rule_stats);
+        Arc::make_mut(&mut queued_message.message)
+            .attributes
+            .mut_or_insert_default()
+            .priority = priority.into();
+        queued_message
+    }
+
+    fn payload_len(queued_message: &QueuedMessage) -> usize {
+        queued_message
+            .message
+            .payload
+            .as_ref()
+            .map(|payload| payload.len())
+            .unwrap_or_default()
+    }
+
+    fn queue(queue_config: QueueConfig) -> (ForwardingQueue, Arc<ForwardingCounters>) {
+        let stats = Arc::new(ForwardingCounters::default());
+        (
+            ForwardingQueue::new(2, queue_config, stats.clone(), Default::default()),
+            stats,
+        )
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_evicts_front_of_queue() {
+        let (queue, stats) = queue(QueueConfig::default());
+        let rule_stats = Arc::new(ForwardingCounters::default());
+
+        for payload_size in 1..=3 {
+            queue.push(queued_message(payload_size, &rule_stats)).await;
+        }
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 2);
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 3);
+        assert_eq!(stats.counts().dropped, 1);
+        assert_eq!(rule_stats.counts().dropped, 1);
+    }
+
+    #[tokio::test]
+    async fn drop_newest_rejects_new_message() {
+        let (queue, stats) = queue(QueueConfig {
+            overflow_policy: OverflowPolicy::DropNewest,
+            ..Default::default()
+        });
+        let rule_stats = Arc::new(ForwardingCounters::default());
+
+        for payload_size in 1..=3 {
+            queue.push(queued_message(payload_size, &rule_stats)).await;
+        }
+
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 1);
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 2);
+        assert_eq!(stats.counts().dropped, 1);
+    }
+
+    #[tokio::test]
+    async fn byte_limit_is_enforced() {
+        let rule_stats = Arc::new(ForwardingCounters::default());
+        let message_size = queued_message(100, &rule_stats).message.compute_size() as usize;
+        let (queue, stats) = queue(QueueConfig {
+            max_messages: Some(10),
+            max_bytes: Some(message_size * 2),
+            ..Default::default()
+        });
+
+        for _ in 0..3 {
+            queue.push(queued_message(100, &rule_stats)).await;
+        }
+        queue
+            .push(queued_message(message_size * 3, &rule_stats))
+            .await;
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.bytes(), message_size * 2);
+        assert_eq!(stats.counts().dropped, 2);
+    }
+
+    #[tokio::test]
+    async fn discarded_messages_are_not_remembered_as_duplicates() {
+        let deduplicating_queue = |overflow_policy| {
+            let stats = Arc::new(ForwardingCounters::default());
+            let queue = ForwardingQueue::new(
+                1,
+                QueueConfig {
+                    overflow_policy,
+                    ..Default::default()
+                },
+                stats.clone(),
+                Default::default(),
+            )
+            .with_deduplication(Deduplication::default());
+            (queue, stats)
+        };
+        let rule_stats = Arc::new(ForwardingCounters::default());
+        let (first, second) = (
+            queued_message(1, &rule_stats),
+            queued_message(2, &rule_stats),
+        );
+
+        let (queue, stats) = deduplicating_queue(OverflowPolicy::DropNewest);
+        queue.push(first.clone()).await;
+        queue.push(second.clone()).await;
+        assert_eq!(stats.counts().dropped, 1);
+        queue.pop().await.unwrap();
+        // the retransmission of the discarded message is queued
+        queue.push(second.clone()).await;
+        queue.push(second.clone()).await;
+
+        assert_eq!(queue.len(), 1);
+        assert_eq!(stats.counts().duplicates, 1);
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 2);
+
+        // the same goes for a message evicted to make room
+        let (queue, stats) = deduplicating_queue(OverflowPolicy::DropOldest);
+        queue.push(first.clone()).await;
+        queue.push(second).await;
+        assert_eq!(stats.counts().dropped, 1);
+        queue.pop().await.unwrap();
+        queue.push(first).await;
+
+        assert_eq!(queue.len(), 1);
+        assert_eq!(stats.counts().duplicates, 0);
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 1);
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_room_and_times_out() {
+        let (queue, stats) = queue(QueueConfig {
+            overflow_policy: OverflowPolicy::Block(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        let queue = Arc::new(queue);
+        let rule_stats = Arc::new(ForwardingCounters::default());
+
+        queue.push(queued_message(1, &rule_stats)).await;
+        queue.push(queued_message(2, &rule_stats)).await;
+
+        // nobody pops, so the producer gives up after the timeout
+        queue.push(queued_message(3, &rule_stats)).await;
+        assert_eq!(stats.counts().dropped, 1);
+
+        let consumer = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                queue.pop().await
+            })
+        };
+        queue.push(queued_message(4, &rule_stats)).await;
+
+        assert_eq!(payload_len(&consumer.await.unwrap().unwrap()), 1);
+        assert_eq!(stats.counts().dropped, 1);
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 2);
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 4);
+    }
+
+    #[tokio::test]
+    async fn close_drains_remaining_and_ends_pop() {
+        let (queue, stats) = queue(QueueConfig::default());
+        let rule_stats = Arc::new(ForwardingCounters::default());
+
+        queue.push(queued_message(1, &rule_stats)).await;
+        queue.close();
+        queue.push(queued_message(2, &rule_stats)).await;
+
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 1);
+        assert!(queue.pop().await.is_none());
+        assert_eq!(stats.counts().dropped, 1);
+    }
+
+    #[tokio::test]
+    async fn discard_queued_empties_queue() {
+        let (queue, stats) = queue(QueueConfig::default());
+        let rule_stats = Arc::new(ForwardingCounters::default());
+
+        queue.push(queued_message(1, &rule_stats)).await;
+        queue.push(queued_message(2, &rule_stats)).await;
+        queue.close();
+        queue.discard_queued();
+
+        assert!(queue.pop().await.is_none());
+        assert_eq!(queue.bytes(), 0);
+        assert_eq!(stats.counts().dropped, 2);
+        assert_eq!(rule_stats.counts().dropped, 2);
+    }
+
+    #[tokio::test]
+    async fn discarded_messages_become_dead_letters() {
+        let dead_letters = Arc::new(DeadLetters::default());
+        let dead_letter_sink = Arc::new(RingBufferDeadLetterSink::new(10));
+        dead_letters.set_sink(dead_letter_sink.clone());
+        let queue = ForwardingQueue::new(
+            1,
+            QueueConfig {
+                max_bytes: Some(100),
+                ..Default::default()
+            },
+            Default::default(),
+            dead_letters,
+        );
+        let rule_stats = Arc::new(ForwardingCounters::default());
+
+        queue.push(queued_message(1, &rule_stats)).await;
+        queue.push(queued_message(2, &rule_stats)).await;
+        queue.push(queued_message(200, &rule_stats)).await;
+
+        let reasons: Vec<_> = dead_letter_sink
+            .dead_letters()
+            .into_iter()
+            .map(|dead_letter| (dead_letter.rule_id, dead_letter.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (RuleId(0), DeadLetterReason::QueueFull),
+                (RuleId(0), DeadLetterReason::MessageTooLarge),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn strict_scheduling_sends_highest_priority_first() {
+        let (queue, _) = queue(QueueConfig {
+            max_messages: Some(10),
+            scheduling: PriorityScheduling::Strict,
+            ..Default::default()
+        });
+        let rule_stats = Arc::new(ForwardingCounters::default());
+
+        queue
+            .push(prioritized_message(
+                UPriority::UPRIORITY_CS0,
+                1,
+                &rule_stats,
+            ))
+            .await;
+        queue.push(queued_message(2, &rule_stats)).await;
+        queue
+            .push(prioritized_message(
+                UPriority::UPRIORITY_CS4,
+                3,
+                &rule_stats,
+            ))
+            .await;
+        queue
+            .push(prioritized_message(
+                UPriority::UPRIORITY_CS4,
+                4,
+                &rule_stats,
+            ))
+            .await;
+
+        let mut sent = Vec::new();
+        while queue.len() > 0 {
+            sent.push(payload_len(&queue.pop().await.unwrap()));
+        }
+        assert_eq!(sent, vec![3, 4, 2, 1]);
+
+        let priority_classes = queue.priority_class_stats();
+        assert_eq!(priority_classes.len(), 7);
+        assert_eq!(priority_classes[4].priority, UPriority::UPRIORITY_CS4);
+        assert_eq!(priority_classes[4].dequeued, 2);
+        // no priority counts as CS1
+        assert_eq!(priority_classes[1].dequeued, 1);
+        assert!(priority_classes
+            .iter()
+            .all(|priority_class| priority_class.queue_depth == 0));
+    }
+
+    #[tokio::test]
+    async fn weighted_scheduling_shares_sending_between_classes() {
+        let (queue, _) = queue(QueueConfig {
+            max_messages: Some(100),
+            scheduling: PriorityScheduling::Weighted([1, 1, 1, 1, 3, 1, 1]),
+            ..Default::default()
+        });
+        let rule_stats = Arc::new(ForwardingCounters::default());
+
+        for _ in 0..20 {
+            queue
+                .push(prioritized_message(
+                    UPriority::UPRIORITY_CS0,
+                    0,
+                    &rule_stats,
+                ))
+                .await;
+            queue
+                .push(prioritized_message(
+                    UPriority::UPRIORITY_CS4,
+                    4,
+                    &rule_stats,
+                ))
+                .await;
+        }
+
+        let mut first_sent = Vec::new();
+        for _ in 0..8 {
+            first_sent.push(payload_len(&queue.pop().await.unwrap()));
+        }
+        assert_eq!(first_sent.iter().filter(|&&class| class == 4).count(), 6);
+        assert_eq!(first_sent.iter().filter(|&&class| class == 0).count(), 2);
+        assert_eq!(queue.priority_class_stats()[0].queue_depth, 18);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_evicts_lower_priority_classes_first() {
+        let (queue, stats) = queue(QueueConfig {
+            scheduling: PriorityScheduling::Strict,
+            ..Default::default()
+        });
+        let rule_stats = Arc::new(ForwardingCounters::default());
+
+        queue
+            .push(prioritized_message(
+                UPriority::UPRIORITY_CS4,
+                1,
+                &rule_stats,
+            ))
+            .await;
+        queue
+            .push(prioritized_message(
+                UPriority::UPRIORITY_CS0,
+                2,
+                &rule_stats,
+            ))
+            .await;
+        // evicts the CS0 message although the CS4 one is older
+        queue
+            .push(prioritized_message(
+                UPriority::UPRIORITY_CS2,
+                3,
+                &rule_stats,
+            ))
+            .await;
+        // finds nothing of its class or lower to evict
+        queue
+            .push(prioritized_message(
+                UPriority::UPRIORITY_CS0,
+                4,
+                &rule_stats,
+            ))
+            .await;
+
+        assert_eq!(stats.counts().dropped, 2);
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 1);
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 3);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_lower_priority_messages_when_evicting_them_makes_no_room() {
+        let rule_stats = Arc::new(ForwardingCounters::default());
+        let message_size = queued_message(100, &rule_stats).message.compute_size() as usize;
+        let (queue, stats) = queue(QueueConfig {
+            max_messages: Some(10),
+            max_bytes: Some(message_size * 2),
+            scheduling: PriorityScheduling::Strict,
+            ..Default::default()
+        });
+
+        queue
+            .push(prioritized_message(
+                UPriority::UPRIORITY_CS0,
+                10,
+                &rule_stats,
+            ))
+            .await;
+        queue
+            .push(prioritized_message(
+                UPriority::UPRIORITY_CS4,
+                100,
+                &rule_stats,
+            ))
+            .await;
+        // evicting the CS0 message would still leave too little room next to the CS4 one
+        queue
+            .push(prioritized_message(
+                UPriority::UPRIORITY_CS2,
+                150,
+                &rule_stats,
+            ))
+            .await;
+
+        assert_eq!(stats.counts().dropped, 1);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 100);
+        assert_eq!(payload_len(&queue.pop().await.unwrap()), 10);
+    }
+}
@@ -0,0 +1,241 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use protobuf::Message;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+use up_rust::UMessage;
+
+/// What to do with a message exceeding a [`RateLimit`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RateLimitAction {
+    /// Hold the message back until the rate limit allows it
+    #[default]
+    Delay,
+    /// Discard the message
+    Drop,
+}
+
+/// Token bucket limits for the messages of a forwarding rule or an out `UTransport`
+///
+/// Set on a rule with
+/// [`ForwardingRuleOptions::with_rate_limit`][crate::ForwardingRuleOptions::with_rate_limit] and
+/// on an out [`Endpoint`][crate::Endpoint] with
+/// [`Endpoint::with_rate_limit`][crate::Endpoint::with_rate_limit]. Limits left at `None` don't
+/// apply, rates of `0` count as `1`. The size of a message is its serialized size.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Sustained number of messages per second
+    pub messages_per_second: Option<u32>,
+    /// Number of messages which may pass at once after a pause, defaults to
+    /// `messages_per_second`
+    pub message_burst: Option<u32>,
+    /// Sustained number of bytes per second
+    pub bytes_per_second: Option<u64>,
+    /// Number of bytes which may pass at once after a pause, defaults to `bytes_per_second`.
+    /// A larger message passes once the bucket is full, leaving it in debt.
+    pub byte_burst: Option<u64>,
+    pub action: RateLimitAction,
+}
+
+// How a message fared with a RateLimiter
+pub(crate) enum Admission {
+    Passed,
+    Delayed,
+    Rejected,
+}
+
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, burst: Option<u64>) -> Self {
+        let rate = rate.max(1) as f64;
+        let capacity = burst.map_or(rate, |burst| burst.max(1) as f64);
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.tokens = (self.tokens + self.rate * elapsed.as_secs_f64()).min(self.capacity);
+    }
+
+    // time until `tokens` are available, zero if they already are
+    fn wait_for(&self, tokens: f64) -> Duration {
+        let missing = tokens.min(self.capacity) - self.tokens;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.rate)
+        }
+    }
+}
+
+struct Buckets {
+    messages: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+    refilled_at: Instant,
+}
+
+// Enforces a RateLimit, shared by everything sending under it
+pub(crate) struct RateLimiter {
+    rate_limit: RateLimit,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(rate_limit: RateLimit) -> Self {
+        Self {
+            rate_limit,
+            buckets: Mutex::new(Buckets {
+                messages: rate_limit.messages_per_second.map(|rate| {
+                    TokenBucket::new(rate.into(), rate_limit.message_burst.map(u64::from))
+                }),
+                bytes: rate_limit
+                    .bytes_per_second
+                    .map(|rate| TokenBucket::new(rate, rate_limit.byte_burst)),
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    // takes the tokens for a message of `size` bytes if all buckets have them, otherwise returns
+    // how long until they do
+    fn try_acquire(&self, size: usize) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now - buckets.refilled_at;
+        buckets.refilled_at = now;
+
+        let size = size as f64;
+        let mut wait = Duration::ZERO;
+        if let Some(messages) = &mut buckets.messages {
+            messages.refill(elapsed);
+            wait = wait.max(messages.wait_for(1.0));
+        }
+        if let Some(bytes) = &mut buckets.bytes {
+            bytes.refill(elapsed);
+            wait = wait.max(bytes.wait_for(size));
+        }
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+
+        if let Some(messages) = &mut buckets.messages {
+            messages.tokens -= 1.0;
+        }
+        if let Some(bytes) = &mut buckets.bytes {
+            bytes.tokens -= size;
+        }
+        Ok(())
+    }
+
+    /// Lets `message` pass, holds it back until it may or rejects it, as per the
+    /// [`RateLimitAction`]
+    pub(crate) async fn admit(&self, message: &UMessage) -> Admission {
+        let size = if self.rate_limit.bytes_per_second.is_some() {
+            message.compute_size() as usize
+        } else {
+            0
+        };
+
+        let Err(mut wait) = self.try_acquire(size) else {
+            return Admission::Passed;
+        };
+        if self.rate_limit.action == RateLimitAction::Drop {
+            return Admission::Rejected;
+        }
+        loop {
+            tokio::time::sleep(wait).await;
+            match self.try_acquire(size) {
+                Ok(()) => return Admission::Delayed,
+                Err(remaining) => wait = remaining,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Admission, RateLimit, RateLimitAction, RateLimiter};
+    use std::time::Duration;
+    use up_rust::{UMessage, UMessageBuilder, UPayloadFormat, UUri};
+
+    fn message(payload_size: usize) -> UMessage {
+        UMessageBuilder::publish(UUri::try_from_parts("a", 0x5BA0, 1, 0x8001).unwrap())
+            .build_with_payload(vec![0u8; payload_size], UPayloadFormat::UPAYLOAD_FORMAT_RAW)
+            .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drop_rejects_messages_beyond_burst_until_refilled() {
+        let rate_limiter = RateLimiter::new(RateLimit {
+            messages_per_second: Some(10),
+            message_burst: Some(2),
+            action: RateLimitAction::Drop,
+            ..Default::default()
+        });
+
+        assert!(matches!(
+            rate_limiter.admit(&message(1)).await,
+            Admission::Passed
+        ));
+        assert!(matches!(
+            rate_limiter.admit(&message(1)).await,
+            Admission::Passed
+        ));
+        assert!(matches!(
+            rate_limiter.admit(&message(1)).await,
+            Admission::Rejected
+        ));
+
+        tokio::time::advance(Duration::from_millis(100)).await;
+        assert!(matches!(
+            rate_limiter.admit(&message(1)).await,
+            Admission::Passed
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delay_holds_messages_back_to_byte_rate() {
+        let message = message(1000);
+        let size = protobuf::Message::compute_size(&message);
+        let rate_limiter = RateLimiter::new(RateLimit {
+            bytes_per_second: Some(size * 10),
+            byte_burst: Some(size),
+            ..Default::default()
+        });
+
+        let start = tokio::time::Instant::now();
+        assert!(matches!(
+            rate_limiter.admit(&message).await,
+            Admission::Passed
+        ));
+        for _ in 0..5 {
+            assert!(matches!(
+                rate_limiter.admit(&message).await,
+                Admission::Delayed
+            ));
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(500));
+        assert!(elapsed < Duration::from_millis(600));
+    }
+}
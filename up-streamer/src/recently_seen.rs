@@ -0,0 +1,191 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+use up_rust::{UMessage, UUID};
+
+// the most and least significant bits of a message's UUID
+type MessageId = (u64, u64);
+
+#[derive(Default)]
+struct SeenIds {
+    seen: HashSet<MessageId>,
+    // oldest first
+    order: VecDeque<(MessageId, Instant)>,
+}
+
+// the ids of the last `capacity` messages received, optionally only those received within the
+// last `window`
+pub(crate) struct RecentlySeen {
+    capacity: usize,
+    window: Option<Duration>,
+    ids: Mutex<SeenIds>,
+}
+
+impl RecentlySeen {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            window: None,
+            ids: Mutex::new(SeenIds::default()),
+        }
+    }
+
+    pub(crate) fn with_window(mut self, window: Duration) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    // remembers the id of `message`, returns whether it had been seen before
+    pub(crate) fn check_and_insert(&self, message: &UMessage) -> bool {
+        message
+            .attributes
+            .as_ref()
+            .and_then(|attributes| attributes.id.as_ref())
+            .is_some_and(|id| self.check_and_insert_id(id))
+    }
+
+    pub(crate) fn check_and_insert_id(&self, id: &UUID) -> bool {
+        let id = (id.msb, id.lsb);
+        let mut ids = self.ids.lock().unwrap();
+        self.forget_outside_window(&mut ids);
+        if !ids.seen.insert(id) {
+            return true;
+        }
+        ids.order.push_back((id, Instant::now()));
+        if ids.order.len() > self.capacity {
+            if let Some((oldest, _)) = ids.order.pop_front() {
+                ids.seen.remove(&oldest);
+            }
+        }
+        false
+    }
+
+    // forgets the id of `message`, so that it is no longer seen
+    pub(crate) fn remove(&self, message: &UMessage) {
+        let Some(id) = message
+            .attributes
+            .as_ref()
+            .and_then(|attributes| attributes.id.as_ref())
+        else {
+            return;
+        };
+        self.remove_id(id);
+    }
+
+    pub(crate) fn remove_id(&self, id: &UUID) {
+        let id = (id.msb, id.lsb);
+        let mut ids = self.ids.lock().unwrap();
+        if ids.seen.remove(&id) {
+            ids.order.retain(|(seen, _)| *seen != id);
+        }
+    }
+
+    pub(crate) fn contains(&self, id: &UUID) -> bool {
+        let mut ids = self.ids.lock().unwrap();
+        self.forget_outside_window(&mut ids);
+        ids.seen.contains(&(id.msb, id.lsb))
+    }
+
+    fn forget_outside_window(&self, ids: &mut SeenIds) {
+        let Some(window) = self.window else {
+            return;
+        };
+        let now = Instant::now();
+        while let Some(&(oldest, seen_at)) = ids.order.front() {
+            if now.duration_since(seen_at) < window {
+                break;
+            }
+            ids.order.pop_front();
+            ids.seen.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecentlySeen;
+    use std::time::Duration;
+    use up_rust::{UMessage, UMessageBuilder, UUri};
+
+    fn message() -> UMessage {
+        UMessageBuilder::publish(UUri::try_from_parts("a", 0x5BA0, 1, 0x8001).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn remembers_only_the_last_capacity_ids() {
+        let recently_seen = RecentlySeen::new(2);
+        let (first, second, third) = (message(), message(), message());
+
+        assert!(!recently_seen.check_and_insert(&first));
+        assert!(!recently_seen.check_and_insert(&second));
+        assert!(recently_seen.check_and_insert(&first));
+        assert!(!recently_seen.check_and_insert(&third));
+        // first was evicted by third
+        assert!(!recently_seen.check_and_insert(&first));
+        assert!(recently_seen.check_and_insert(&third));
+    }
+
+    #[test]
+    fn removed_ids_are_no_longer_seen() {
+        let recently_seen = RecentlySeen::new(2);
+        let (first, second) = (message(), message());
+
+        assert!(!recently_seen.check_and_insert(&first));
+        assert!(!recently_seen.check_and_insert(&second));
+        recently_seen.remove(&first);
+
+        assert!(!recently_seen.check_and_insert(&first));
+        assert!(recently_seen.check_and_insert(&second));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn forgets_ids_after_the_window() {
+        let recently_seen = RecentlySeen::new(10).with_window(Duration::from_secs(1));
+        let (first, second) = (message(), message());
+
+        assert!(!recently_seen.check_and_insert(&first));
+        tokio::time::advance(Duration::from_millis(600)).await;
+        assert!(!recently_seen.check_and_insert(&second));
+        tokio::time::advance(Duration::from_millis(600)).await;
+
+        assert!(!recently_seen.check_and_insert(&first));
+        assert!(recently_seen.check_and_insert(&second));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn contains_only_ids_within_the_window() {
+        let recently_seen = RecentlySeen::new(10).with_window(Duration::from_secs(1));
+        let message = message();
+        let id = message.attributes.id.get_or_default();
+
+        assert!(!recently_seen.contains(id));
+        assert!(!recently_seen.check_and_insert_id(id));
+        assert!(recently_seen.contains(id));
+        tokio::time::advance(Duration::from_millis(1100)).await;
+        assert!(!recently_seen.contains(id));
+    }
+
+    #[test]
+    fn messages_without_id_are_never_seen() {
+        let recently_seen = RecentlySeen::new(2);
+
+        assert!(!recently_seen.check_and_insert(&UMessage::default()));
+        assert!(!recently_seen.check_and_insert(&UMessage::default()));
+    }
+}
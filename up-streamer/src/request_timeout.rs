@@ -0,0 +1,228 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use crate::recently_seen::RecentlySeen;
+use crate::rule::RuleId;
+use log::*;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::runtime::Handle;
+use tokio::task::AbortHandle;
+use up_rust::{UAttributes, UCode, UMessage, UMessageBuilder, UTransport, UUID};
+
+const IN_FLIGHT_REQUESTS_TAG: &str = "InFlightRequests:";
+const IN_FLIGHT_REQUESTS_FN_TRACK_TAG: &str = "track():";
+const IN_FLIGHT_REQUESTS_FN_TIME_OUT_TAG: &str = "time_out():";
+
+/// Responses synthesized for forwarded RPC requests which aren't answered in time
+///
+/// Set with [`UStreamer::with_request_timeouts`][crate::UStreamer::with_request_timeouts].
+/// Forwarding rules track the requests they forward by their id. When no response to a request
+/// is forwarded by any forwarding rule before the time-to-live of the request elapses, the rule that
+/// forwarded it sends a response with `commstatus` `DEADLINE_EXCEEDED` back over its in
+/// [`Endpoint`][crate::Endpoint], so that the caller doesn't wait forever. Responses arriving
+/// after that are dropped, counting as `dropped` in the
+/// [`ForwardingCounts`][crate::ForwardingCounts] of their rule. Requests without a
+/// time-to-live are not tracked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RequestTimeouts {
+    /// Maximum number of requests tracked at once, further requests are forwarded untracked
+    pub max_in_flight: usize,
+    /// How long the ids of timed out requests are remembered to recognize late responses
+    pub late_response_window: Duration,
+}
+
+impl Default for RequestTimeouts {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 10_000,
+            late_response_window: Duration::from_secs(60),
+        }
+    }
+}
+
+// the most and least significant bits of a request's UUID
+type RequestId = (u64, u64);
+
+// the requests forwarded by any forwarding rule of a UStreamer which are awaiting a response
+pub(crate) struct InFlightRequests {
+    request_timeouts: RequestTimeouts,
+    // runs a timer per request
+    runtime: Handle,
+    in_flight: Mutex<HashMap<RequestId, AbortHandle>>,
+    timed_out: RecentlySeen,
+}
+
+impl Debug for InFlightRequests {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InFlightRequests")
+            .field("request_timeouts", &self.request_timeouts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl InFlightRequests {
+    pub(crate) fn new(request_timeouts: RequestTimeouts, runtime: Handle) -> Self {
+        Self {
+            request_timeouts,
+            runtime,
+            in_flight: Mutex::new(HashMap::new()),
+            timed_out: RecentlySeen::new(request_timeouts.max_in_flight)
+                .with_window(request_timeouts.late_response_window),
+        }
+    }
+
+    // awaits a response to `request`, sending a timeout response over `respond_on` unless one
+    // passes before the time-to-live of `request` elapses
+    pub(crate) fn track(
+        self: &Arc<Self>,
+        rule_id: RuleId,
+        request: &UMessage,
+        respond_on: Arc<dyn UTransport>,
+    ) {
+        let Some(attributes) = request
+            .attributes
+            .as_ref()
+            .filter(|attributes| attributes.is_request())
+        else {
+            return;
+        };
+        let (Some(id), Some(remaining)) = (attributes.id.as_ref(), remaining_ttl(attributes))
+        else {
+            return;
+        };
+        let request_id = (id.msb, id.lsb);
+
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.contains_key(&request_id) {
+            return;
+        }
+        if in_flight.len() >= self.request_timeouts.max_in_flight {
+            warn!(
+                "{}:{}:{} Already tracking {} requests, not tracking request {}",
+                rule_id,
+                IN_FLIGHT_REQUESTS_TAG,
+                IN_FLIGHT_REQUESTS_FN_TRACK_TAG,
+                in_flight.len(),
+                id.to_hyphenated_string()
+            );
+            return;
+        }
+        let requests = self.clone();
+        let attributes = attributes.clone();
+        // the timer only looks the request up once the lock is released
+        let timer = self.runtime.spawn(async move {
+            tokio::time::sleep(remaining).await;
+            requests.time_out(rule_id, &attributes, respond_on).await;
+        });
+        in_flight.insert(request_id, timer.abort_handle());
+    }
+
+    pub(crate) fn request_timeouts(&self) -> RequestTimeouts {
+        self.request_timeouts
+    }
+
+    // whether `response` answers a request which timed out already
+    pub(crate) fn is_late(&self, response: &UMessage) -> bool {
+        response
+            .attributes
+            .as_ref()
+            .and_then(|attributes| attributes.reqid.as_ref())
+            .is_some_and(|request_id| self.timed_out.contains(request_id))
+    }
+
+    // stops waiting for the request `response` answers, once `response` was sent
+    pub(crate) fn answered(&self, response: &UMessage) {
+        if let Some(request_id) = response
+            .attributes
+            .as_ref()
+            .and_then(|attributes| attributes.reqid.as_ref())
+        {
+            self.forget(request_id);
+        }
+    }
+
+    // stops waiting for a response to the request with `request_id`, e.g. as the streamer
+    // answered it already, returns whether it was waited for
+    pub(crate) fn forget(&self, request_id: &UUID) -> bool {
+        let timer = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .remove(&(request_id.msb, request_id.lsb));
+        timer.map(|timer| timer.abort()).is_some()
+    }
+
+    async fn time_out(
+        &self,
+        rule_id: RuleId,
+        request_attributes: &UAttributes,
+        respond_on: Arc<dyn UTransport>,
+    ) {
+        let id = request_attributes.id.get_or_default();
+        if self
+            .in_flight
+            .lock()
+            .unwrap()
+            .remove(&(id.msb, id.lsb))
+            .is_none()
+        {
+            return;
+        }
+        self.timed_out.check_and_insert_id(id);
+
+        debug!(
+            "{}:{}:{} No response to request {} before its deadline, responding with DEADLINE_EXCEEDED",
+            rule_id,
+            IN_FLIGHT_REQUESTS_TAG,
+            IN_FLIGHT_REQUESTS_FN_TIME_OUT_TAG,
+            id.to_hyphenated_string()
+        );
+        let response = match UMessageBuilder::response_for_request(request_attributes)
+            .with_comm_status(UCode::DEADLINE_EXCEEDED)
+            .build()
+        {
+            Ok(response) => response,
+            Err(err) => {
+                warn!(
+                    "{}:{}:{} Unable to build timeout response to request {}: {err:?}",
+                    rule_id,
+                    IN_FLIGHT_REQUESTS_TAG,
+                    IN_FLIGHT_REQUESTS_FN_TIME_OUT_TAG,
+                    id.to_hyphenated_string()
+                );
+                return;
+            }
+        };
+        if let Err(err) = respond_on.send(response).await {
+            warn!(
+                "{}:{}:{} Unable to send timeout response to request {}: {err:?}",
+                rule_id,
+                IN_FLIGHT_REQUESTS_TAG,
+                IN_FLIGHT_REQUESTS_FN_TIME_OUT_TAG,
+                id.to_hyphenated_string()
+            );
+        }
+    }
+}
+
+// the time left until the request described by `attributes` expires, none for messages without
+// a time-to-live
+fn remaining_ttl(attributes: &UAttributes) -> Option<Duration> {
+    let ttl = attributes.ttl.filter(|ttl| *ttl > 0)?;
+    let created = attributes.id.as_ref()?.get_time()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    Some(Duration::from_millis(created.saturating_add(u64::from(ttl))).saturating_sub(now))
+}
@@ -0,0 +1,104 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use std::time::Duration;
+use up_rust::{UCode, UStatus};
+
+/// How sending a message over an out `UTransport` is retried after it failed
+///
+/// Set on an out [`Endpoint`][crate::Endpoint] with
+/// [`Endpoint::with_retry_policy`][crate::Endpoint::with_retry_policy]. The default policy
+/// makes a single attempt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts to send a message, `1` disables retries
+    pub max_attempts: u32,
+    /// Backoff before the first retry, doubled for each further retry
+    pub initial_backoff: Duration,
+    /// Upper bound for the backoff between two attempts
+    pub max_backoff: Duration,
+    /// Codes of the [`UStatus`] returned by `send()` which warrant another attempt
+    pub retryable_codes: Vec<UCode>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            retryable_codes: vec![
+                UCode::UNAVAILABLE,
+                UCode::RESOURCE_EXHAUSTED,
+                UCode::ABORTED,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a failed `attempt` (starting at 1) ending in `status` should be retried
+    pub(crate) fn should_retry(&self, attempt: u32, status: &UStatus) -> bool {
+        attempt < self.max_attempts && self.retryable_codes.contains(&status.get_code())
+    }
+
+    /// Backoff after a failed `attempt` (starting at 1), with jitter spreading it between half
+    /// and all of the exponential backoff so that forwarders don't retry in lockstep
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+        let half = exponential / 2;
+        half + half.mul_f64(rand::random::<f64>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use std::time::Duration;
+    use up_rust::{UCode, UStatus};
+
+    #[test]
+    fn retries_only_retryable_codes_up_to_max_attempts() {
+        let retry_policy = RetryPolicy {
+            max_attempts: 3,
+            ..Default::default()
+        };
+        let unavailable = UStatus::fail_with_code(UCode::UNAVAILABLE, "broker unavailable");
+        let invalid = UStatus::fail_with_code(UCode::INVALID_ARGUMENT, "bad message");
+
+        assert!(retry_policy.should_retry(1, &unavailable));
+        assert!(retry_policy.should_retry(2, &unavailable));
+        assert!(!retry_policy.should_retry(3, &unavailable));
+        assert!(!retry_policy.should_retry(1, &invalid));
+        assert!(!RetryPolicy::default().should_retry(1, &unavailable));
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_with_jitter_up_to_max() {
+        let retry_policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            ..Default::default()
+        };
+
+        for (attempt, exponential) in [(1, 100), (2, 200), (3, 400), (4, 500), (9, 500)] {
+            let backoff = retry_policy.backoff(attempt);
+            assert!(backoff >= Duration::from_millis(exponential / 2));
+            assert!(backoff <= Duration::from_millis(exponential));
+        }
+    }
+}
@@ -0,0 +1,119 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use crate::endpoint::Endpoint;
+use crate::filter::MessageFilter;
+use crate::rate_limit::RateLimit;
+use crate::transformer::MessageTransformer;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Identifies a forwarding rule of a [`UStreamer`][crate::UStreamer]
+///
+/// Returned when adding the rule. A [`UStreamer`][crate::UStreamer] never hands out the same
+/// ID twice, so an ID stays valid until its rule is deleted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RuleId(pub(crate) u64);
+
+impl Display for RuleId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "rule-{}", self.0)
+    }
+}
+
+/// Whether a forwarding rule is currently forwarding messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuleState {
+    /// The rule's listener is registered and messages are forwarded
+    Active,
+    /// The rule's listener is registered, but messages are held back or dropped as per the
+    /// [`PauseAction`][crate::PauseAction] the rule was paused with
+    Paused,
+}
+
+/// Description of a forwarding rule installed on a [`UStreamer`][crate::UStreamer], as returned
+/// by [`UStreamer::rules`][crate::UStreamer::rules]
+///
+/// The options described are those applied to the messages of the rule, also when it shares
+/// the listener on its in `UTransport` with other rules.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForwardingRuleDescriptor {
+    pub id: RuleId,
+    pub in_endpoint: String,
+    pub in_authority: String,
+    pub out_endpoint: String,
+    pub out_authority: String,
+    /// When the rule was added
+    pub created_at: SystemTime,
+    /// The [`MessageFilter`] the rule was added with
+    pub filter: MessageFilter,
+    /// The [`RateLimit`] the rule was added with
+    pub rate_limit: Option<RateLimit>,
+    /// Whether the rule was added with a [`MessageTransformer`]
+    pub transformed: bool,
+    pub state: RuleState,
+}
+
+/// Settings of a single forwarding rule, passed to
+/// [`UStreamer::add_forwarding_rule_with_options`][crate::UStreamer::add_forwarding_rule_with_options]
+///
+/// The default options forward every message unchanged.
+#[derive(Clone, Default)]
+pub struct ForwardingRuleOptions {
+    pub(crate) filter: MessageFilter,
+    pub(crate) transformer: Option<Arc<dyn MessageTransformer>>,
+    pub(crate) rate_limit: Option<RateLimit>,
+}
+
+impl ForwardingRuleOptions {
+    /// Forwards only the messages passing `filter`
+    pub fn with_filter(mut self, filter: MessageFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Passes every message which made it through the filter to `transformer` before queueing it
+    /// for the out `UTransport`
+    pub fn with_transformer(mut self, transformer: Arc<dyn MessageTransformer>) -> Self {
+        self.transformer = Some(transformer);
+        self
+    }
+
+    /// Limits the rate of messages the rule's listener hands on for sending
+    ///
+    /// With [`RateLimitAction::Delay`][crate::RateLimitAction::Delay] the listener holds back
+    /// further messages from the in `UTransport` while waiting.
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+}
+
+/// A change to the forwarding rules of a [`UStreamer`][crate::UStreamer], applied together with
+/// others by [`UStreamer::apply_rules`][crate::UStreamer::apply_rules]
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum RuleChange {
+    /// Adds a forwarding rule, like
+    /// [`UStreamer::add_forwarding_rule_with_options`][crate::UStreamer::add_forwarding_rule_with_options]
+    Add {
+        r#in: Endpoint,
+        out: Endpoint,
+        options: ForwardingRuleOptions,
+    },
+    /// Deletes a forwarding rule, like
+    /// [`UStreamer::delete_forwarding_rule_by_id`][crate::UStreamer::delete_forwarding_rule_by_id]
+    Delete(RuleId),
+}
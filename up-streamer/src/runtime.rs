@@ -0,0 +1,73 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use std::future::Future;
+use std::thread;
+use tokio::runtime::{Builder, Handle};
+use tokio::task;
+
+/// Where the [`UStreamer`][crate::UStreamer] runs the forwarder sending the messages queued for
+/// each out `UTransport`
+///
+/// Set with [`UStreamer::with_forwarder_runtime`][crate::UStreamer::with_forwarder_runtime].
+/// [`UStreamer::new_async`][crate::UStreamer::new_async] defaults to tasks on the runtime it is
+/// called from, [`UStreamer::new`][crate::UStreamer::new] to tasks on an internal runtime.
+#[derive(Clone, Debug)]
+pub enum ForwarderRuntime {
+    /// Spawns each forwarder as a task on the given runtime
+    Tasks(Handle),
+    /// Gives each forwarder an OS thread with its own current-thread runtime, for out
+    /// `UTransport`s whose `send()` blocks
+    DedicatedThreads,
+}
+
+// a running forwarder, to be joined once it finished
+pub(crate) enum ForwarderWorker {
+    Task(task::JoinHandle<()>),
+    Thread(thread::JoinHandle<()>),
+}
+
+impl ForwarderRuntime {
+    pub(crate) fn spawn<F>(&self, forwarder: F) -> ForwarderWorker
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        match self {
+            ForwarderRuntime::Tasks(handle) => ForwarderWorker::Task(handle.spawn(forwarder)),
+            ForwarderRuntime::DedicatedThreads => {
+                ForwarderWorker::Thread(thread::spawn(move || {
+                    Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .expect("Failed to create Tokio runtime")
+                        .block_on(forwarder);
+                }))
+            }
+        }
+    }
+}
+
+impl ForwarderWorker {
+    // waits for the finished forwarder without blocking, returns whether it ended normally
+    pub(crate) async fn join(self) -> bool {
+        match self {
+            ForwarderWorker::Task(handle) => handle.await.is_ok(),
+            ForwarderWorker::Thread(handle) => {
+                matches!(
+                    task::spawn_blocking(move || handle.join()).await,
+                    Ok(Ok(()))
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use up_rust::UPriority;
+
+/// Message counts of a forwarding rule or of an out `UTransport`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ForwardingCounts {
+    /// Messages which were received, either by the rule's listener or from the out
+    /// `UTransport`'s queue
+    pub received: u64,
+    /// Messages which were sent successfully over the out `UTransport`
+    pub forwarded: u64,
+    /// Messages which were discarded before an attempt to send them was made
+    pub dropped: u64,
+    /// Messages which didn't pass the rule's [`MessageFilter`][crate::MessageFilter] or were
+    /// dropped by its [`MessageTransformer`][crate::MessageTransformer], always `0` for an out
+    /// `UTransport`
+    pub filtered: u64,
+    /// Messages which the [`AccessPolicy`][crate::AccessPolicy] didn't permit to cross from the
+    /// rule's in [`Endpoint`][crate::Endpoint] to its out [`Endpoint`][crate::Endpoint], always
+    /// `0` for an out `UTransport`
+    pub denied: u64,
+    /// Messages whose time-to-live elapsed before they could be sent
+    pub expired: u64,
+    /// Messages which came back to the rule after passing it before, see
+    /// [`LoopDetection`][crate::LoopDetection], always `0` for an out `UTransport`
+    pub looped: u64,
+    /// Messages with the id of a message sent shortly before over the same out `UTransport`,
+    /// see [`Deduplication`][crate::Deduplication]
+    pub duplicates: u64,
+    /// Messages received while the rule was paused, which were buffered or dropped as per its
+    /// [`PauseAction`][crate::PauseAction], always `0` for an out `UTransport`
+    pub paused: u64,
+    /// Messages exceeding a [`RateLimit`][crate::RateLimit], which were delayed or dropped as per
+    /// its [`RateLimitAction`][crate::RateLimitAction]
+    pub throttled: u64,
+    /// Messages for which sending over the out `UTransport` failed, after all retries
+    pub send_failures: u64,
+    /// Attempts to send a message which failed and were retried
+    pub retries: u64,
+}
+
+/// Snapshot of the counters of a single forwarding rule
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ForwardingRuleStats {
+    pub in_endpoint: String,
+    pub in_authority: String,
+    pub out_endpoint: String,
+    pub out_authority: String,
+    /// Number of source / sink filters the rule's listener is currently registered for on the
+    /// in `UTransport`
+    pub registered_listeners: u64,
+    pub counts: ForwardingCounts,
+}
+
+/// Snapshot of the counters of the forwarder of a single out `UTransport`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutTransportStats {
+    /// Names of the out [`Endpoint`][crate::Endpoint]s sharing this `UTransport`
+    pub out_endpoints: Vec<String>,
+    /// Number of messages currently waiting to be sent
+    pub queue_depth: usize,
+    /// Total serialized size in bytes of the messages currently waiting to be sent
+    pub queued_bytes: usize,
+    /// Queue depth and wait times per priority class, from `CS0` to `CS6`
+    pub priority_classes: Vec<PriorityClassStats>,
+    pub counts: ForwardingCounts,
+}
+
+/// Snapshot of the queue of an out `UTransport` for a single priority class
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PriorityClassStats {
+    pub priority: UPriority,
+    /// Number of messages of this class currently waiting to be sent
+    pub queue_depth: usize,
+    /// Number of messages of this class taken from the queue so far
+    pub dequeued: u64,
+    /// Total time the `dequeued` messages spent waiting in the queue
+    pub wait_time: Duration,
+}
+
+/// Snapshot of all counters of a [`UStreamer`][crate::UStreamer], as returned by
+/// [`UStreamer::stats`][crate::UStreamer::stats]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UStreamerStats {
+    pub rules: Vec<ForwardingRuleStats>,
+    pub out_transports: Vec<OutTransportStats>,
+}
+
+// Shared between the listeners and forwarders which update them and the UStreamer reading them
+#[derive(Debug, Default)]
+pub(crate) struct ForwardingCounters {
+    received: AtomicU64,
+    forwarded: AtomicU64,
+    dropped: AtomicU64,
+    filtered: AtomicU64,
+    denied: AtomicU64,
+    expired: AtomicU64,
+    looped: AtomicU64,
+    duplicates: AtomicU64,
+    paused: AtomicU64,
+    throttled: AtomicU64,
+    send_failures: AtomicU64,
+    retries: AtomicU64,
+    registered_listeners: AtomicU64,
+}
+
+impl ForwardingCounters {
+    pub(crate) fn record_received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_forwarded(&self) {
+        self.forwarded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_filtered(&self) {
+        self.filtered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_denied(&self) {
+        self.denied.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_expired(&self) {
+        self.expired.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_looped(&self) {
+        self.looped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_duplicate(&self) {
+        self.duplicates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_paused(&self) {
+        self.paused.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_throttled(&self) {
+        self.throttled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_send_failure(&self) {
+        self.send_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_registered_listeners(&self, registered_listeners: u64) {
+        self.registered_listeners
+            .store(registered_listeners, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_listener_registered(&self) {
+        self.registered_listeners.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_listener_unregistered(&self) {
+        let _ = self.registered_listeners.fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |registered| Some(registered.saturating_sub(1)),
+        );
+    }
+
+    pub(crate) fn registered_listeners(&self) -> u64 {
+        self.registered_listeners.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn counts(&self) -> ForwardingCounts {
+        ForwardingCounts {
+            received: self.received.load(Ordering::Relaxed),
+            forwarded: self.forwarded.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            filtered: self.filtered.load(Ordering::Relaxed),
+            denied: self.denied.load(Ordering::Relaxed),
+            expired: self.expired.load(Ordering::Relaxed),
+            looped: self.looped.load(Ordering::Relaxed),
+            duplicates: self.duplicates.load(Ordering::Relaxed),
+            paused: self.paused.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+            send_failures: self.send_failures.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+        }
+    }
+}
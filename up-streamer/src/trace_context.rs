@@ -0,0 +1,169 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use opentelemetry::Context;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use up_rust::UMessage;
+
+const TRACEPARENT_VERSION: &str = "00";
+const INVALID_TRACEPARENT_VERSION: &str = "ff";
+
+// the span context of a W3C traceparent, `<version>-<trace id>-<parent id>-<trace flags>`
+fn parse_traceparent(traceparent: &str) -> Option<SpanContext> {
+    let mut fields = traceparent.trim().split('-');
+    let version = fields.next()?;
+    let trace_id = fields.next()?;
+    let span_id = fields.next()?;
+    let trace_flags = fields.next()?;
+    // later versions may append fields, which we don't know about
+    if version.len() != 2
+        || version == INVALID_TRACEPARENT_VERSION
+        || (version == TRACEPARENT_VERSION && fields.next().is_some())
+        || trace_id.len() != 32
+        || span_id.len() != 16
+        || trace_flags.len() != 2
+    {
+        return None;
+    }
+
+    let span_context = SpanContext::new(
+        TraceId::from_hex(trace_id).ok()?,
+        SpanId::from_hex(span_id).ok()?,
+        TraceFlags::new(u8::from_str_radix(trace_flags, 16).ok()?) & TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    );
+    span_context.is_valid().then_some(span_context)
+}
+
+fn format_traceparent(span_context: &SpanContext) -> String {
+    format!(
+        "{TRACEPARENT_VERSION}-{}-{}-{:02x}",
+        span_context.trace_id(),
+        span_context.span_id(),
+        (span_context.trace_flags() & TraceFlags::SAMPLED).to_u8()
+    )
+}
+
+// makes `span` continue the trace carried in the traceparent of `message`, if any
+//
+// Only has an effect when spans are recorded by a tracing-opentelemetry layer.
+pub(crate) fn continue_trace(span: &Span, message: &UMessage) {
+    if let Some(parent) = message
+        .attributes
+        .as_ref()
+        .and_then(|attributes| attributes.traceparent.as_deref())
+        .and_then(parse_traceparent)
+    {
+        // fails only without a tracing-opentelemetry layer, there is no trace to continue then
+        let _ = span.set_parent(Context::new().with_remote_span_context(parent));
+    }
+}
+
+// writes the context of `span` as the traceparent of `message`, so that the next hop continues
+// the trace from `span`
+//
+// Without a tracing-opentelemetry layer, spans have no context and the message is left as it is.
+pub(crate) fn inject(span: &Span, message: &mut UMessage) {
+    let span_context = span.context().span().span_context().clone();
+    if span_context.is_valid() {
+        message.attributes.mut_or_insert_default().traceparent =
+            Some(format_traceparent(&span_context));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{continue_trace, format_traceparent, inject, parse_traceparent};
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use tracing_subscriber::layer::SubscriberExt;
+    use up_rust::{UMessage, UMessageBuilder, UUri};
+
+    const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+    fn message(traceparent: Option<&str>) -> UMessage {
+        let mut message =
+            UMessageBuilder::publish(UUri::try_from_parts("a", 0x5BA0, 1, 0x8001).unwrap())
+                .build()
+                .unwrap();
+        message.attributes.mut_or_insert_default().traceparent =
+            traceparent.map(ToString::to_string);
+        message
+    }
+
+    #[test]
+    fn traceparent_round_trips() {
+        let span_context = parse_traceparent(TRACEPARENT).unwrap();
+        assert!(span_context.is_remote());
+        assert!(span_context.is_sampled());
+        assert_eq!(format_traceparent(&span_context), TRACEPARENT);
+
+        // later versions may add fields
+        assert!(parse_traceparent(&format!("cc{}-extra", &TRACEPARENT[2..])).is_some());
+        for invalid in [
+            "",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra",
+            "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01",
+            "00-0af7651916cd43dd8448eb211c8031-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319x-b7ad6b7169203331-01",
+        ] {
+            assert!(parse_traceparent(invalid).is_none(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn forwarded_message_continues_the_trace() {
+        let tracer = SdkTracerProvider::builder().build().tracer("up-streamer");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut traced = message(Some(TRACEPARENT));
+            let span = tracing::info_span!("forward");
+            continue_trace(&span, &traced);
+            inject(&span, &mut traced);
+            let traceparent = traced.attributes.traceparent.clone().unwrap();
+            assert_eq!(&traceparent[..36], &TRACEPARENT[..36]);
+            assert_ne!(traceparent, TRACEPARENT);
+            assert!(traceparent.ends_with("-01"));
+
+            // messages without a trace start one
+            let mut untraced = message(None);
+            let span = tracing::info_span!("forward");
+            continue_trace(&span, &untraced);
+            inject(&span, &mut untraced);
+            assert!(
+                parse_traceparent(untraced.attributes.traceparent.as_deref().unwrap()).is_some()
+            );
+        });
+    }
+
+    #[test]
+    fn message_is_unchanged_without_opentelemetry_layer() {
+        let span = tracing::info_span!("forward");
+        let mut traced = message(Some(TRACEPARENT));
+        continue_trace(&span, &traced);
+        inject(&span, &mut traced);
+        assert_eq!(traced.attributes.traceparent.as_deref(), Some(TRACEPARENT));
+
+        let mut untraced = message(None);
+        inject(&span, &mut untraced);
+        assert_eq!(untraced.attributes.traceparent, None);
+    }
+}
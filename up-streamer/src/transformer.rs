@@ -0,0 +1,36 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+
+use up_rust::UMessage;
+
+/// Rewrites or drops the messages of a forwarding rule before they are queued for the out
+/// `UTransport`
+///
+/// Set on a rule with [`ForwardingRuleOptions::with_transformer`][crate::ForwardingRuleOptions::with_transformer].
+/// Called from the rule's listener for every message which passed the rule's
+/// [`MessageFilter`][crate::MessageFilter], so implementations should return quickly.
+///
+/// Closures of type `Fn(UMessage) -> Option<UMessage>` implement this trait.
+pub trait MessageTransformer: Send + Sync {
+    /// Returns the message to forward in place of `message`, or `None` to drop it
+    fn transform(&self, message: UMessage) -> Option<UMessage>;
+}
+
+impl<F> MessageTransformer for F
+where
+    F: Fn(UMessage) -> Option<UMessage> + Send + Sync,
+{
+    fn transform(&self, message: UMessage) -> Option<UMessage> {
+        self(message)
+    }
+}
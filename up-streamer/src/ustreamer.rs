@@ -11,32 +11,59 @@
  * SPDX-License-Identifier: Apache-2.0
  ********************************************************************************/
 
+use crate::access_control::{AccessControl, AccessPolicy};
+use crate::dead_letter::{DeadLetterReason, DeadLetterSink, DeadLetters};
 use crate::endpoint::Endpoint;
+use crate::error_response::ErrorResponder;
+use crate::filter::MessageFilter;
+use crate::loop_detection::LoopDetection;
+use crate::observer::{DropReason, SharedObserver, StreamerObserver};
+use crate::pause::{PauseAction, Paused, RulePause};
+use crate::queue::{ForwardingQueue, QueuedMessage};
+use crate::rate_limit::{Admission, RateLimit, RateLimiter};
+use crate::recently_seen::RecentlySeen;
+use crate::request_timeout::{InFlightRequests, RequestTimeouts};
+use crate::retry::RetryPolicy;
+use crate::rule::{ForwardingRuleDescriptor, ForwardingRuleOptions, RuleChange, RuleId, RuleState};
+use crate::runtime::{ForwarderRuntime, ForwarderWorker};
+use crate::stats::{ForwardingCounters, ForwardingRuleStats, OutTransportStats, UStreamerStats};
+use crate::trace_context;
 use async_trait::async_trait;
+use futures::future::join_all;
 use lazy_static::lazy_static;
 use log::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use std::str;
-use std::sync::Arc;
-use std::thread;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, SystemTime};
 use subscription_cache::{SubscriptionCache, SubscriptionInformation};
-use tokio::runtime::Builder;
-use tokio::runtime::Runtime;
-use tokio::sync::broadcast::{Receiver, Sender};
-use tokio::sync::Mutex;
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::{oneshot, Mutex};
 use tokio::task;
-use up_rust::core::usubscription::{FetchSubscriptionsRequest, SubscriberInfo, USubscription};
+use tokio::time::Instant;
+use tracing::{Instrument, Span};
+use up_rust::core::usubscription::{
+    FetchSubscriptionsRequest, NotificationsRequest, State, SubscriberInfo, USubscription, Update,
+};
 use up_rust::{UCode, UListener, UMessage, UPayloadFormat, UStatus, UTransport, UUri, UUID};
 
 const USTREAMER_TAG: &str = "UStreamer:";
 const USTREAMER_FN_NEW_TAG: &str = "new():";
 const USTREAMER_FN_ADD_FORWARDING_RULE_TAG: &str = "add_forwarding_rule():";
 const USTREAMER_FN_DELETE_FORWARDING_RULE_TAG: &str = "delete_forwarding_rule():";
+const USTREAMER_FN_DELETE_FORWARDING_RULE_BY_ID_TAG: &str = "delete_forwarding_rule_by_id():";
+const USTREAMER_FN_APPLY_RULES_TAG: &str = "apply_rules():";
+const USTREAMER_FN_REFRESH_ENDPOINT_TAG: &str = "refresh_endpoint():";
+const USTREAMER_FN_PAUSE_RULE_TAG: &str = "pause_rule():";
+const USTREAMER_FN_RESUME_RULE_TAG: &str = "resume_rule():";
+const USTREAMER_FN_APPLY_SUBSCRIPTION_UPDATE_TAG: &str = "apply_subscription_update():";
+const USTREAMER_FN_SHUTDOWN_TAG: &str = "shutdown():";
+const USTREAMER_FN_DROP_TAG: &str = "drop():";
 
 const THREAD_NUM: usize = 10;
 
@@ -49,6 +76,14 @@ lazy_static! {
         .expect("Unable to create callback runtime");
 }
 
+// The time-to-live in the attributes counts from the creation time in the message's UUIDv7 id,
+// so the TTL of a forwarded copy needs no adjustment: receivers see the same remaining time.
+fn is_expired(msg: &UMessage) -> bool {
+    msg.attributes
+        .as_ref()
+        .is_some_and(|attributes| attributes.check_expired().is_err())
+}
+
 fn uauthority_to_uuri(authority_name: &str) -> UUri {
     // let mut uuri = UUri::any();
     // uuri.authority_name = authority_name.to_string();
@@ -100,48 +135,197 @@ impl Display for ForwardingListenerError {
 
 impl Error for ForwardingListenerError {}
 
+type ForwardingRuleKey = (String, String, ComparableTransport, ComparableTransport);
+
+// what we keep around about a forwarding rule, beyond what identifies it
+struct RegisteredForwardingRule {
+    id: RuleId,
+    in_endpoint: Endpoint,
+    out_endpoint: Endpoint,
+    created_at: SystemTime,
+    options: ForwardingRuleOptions,
+    stats: Arc<ForwardingCounters>,
+    // shared with the rule's own listener
+    pause: Arc<RulePause>,
+}
+
+impl RegisteredForwardingRule {
+    fn descriptor(&self) -> ForwardingRuleDescriptor {
+        ForwardingRuleDescriptor {
+            id: self.id,
+            in_endpoint: self.in_endpoint.name.clone(),
+            in_authority: self.in_endpoint.authority.clone(),
+            out_endpoint: self.out_endpoint.name.clone(),
+            out_authority: self.out_endpoint.authority.clone(),
+            created_at: self.created_at,
+            filter: self.options.filter.clone(),
+            rate_limit: self.options.rate_limit,
+            transformed: self.options.transformer.is_some(),
+            state: if self.pause.is_paused() {
+                RuleState::Paused
+            } else {
+                RuleState::Active
+            },
+        }
+    }
+
+    // the same rule, using `endpoint` in place of the in or out endpoint of the same name
+    fn with_endpoint(&self, endpoint: &Endpoint) -> Self {
+        let replace = |rule_endpoint: &Endpoint| {
+            if rule_endpoint.name == endpoint.name {
+                endpoint.clone()
+            } else {
+                rule_endpoint.clone()
+            }
+        };
+        Self {
+            id: self.id,
+            in_endpoint: replace(&self.in_endpoint),
+            out_endpoint: replace(&self.out_endpoint),
+            created_at: self.created_at,
+            options: self.options.clone(),
+            stats: self.stats.clone(),
+            pause: self.pause.clone(),
+        }
+    }
+}
+
+// a change made by UStreamer::apply_rules(), along with what it takes to undo it
+enum AppliedRuleChange {
+    Added(RuleId),
+    Deleted(Box<RegisteredForwardingRule>),
+}
+
 // the 'gatekeeper' which will prevent us from erroneously being able to add duplicate
 // forwarding rules or delete those rules which don't exist
-type ForwardingRules = Mutex<HashSet<(String, String, ComparableTransport, ComparableTransport)>>;
+type ForwardingRules = Mutex<HashMap<ForwardingRuleKey, RegisteredForwardingRule>>;
 
 const TRANSPORT_FORWARDERS_TAG: &str = "TransportForwarders:";
 const TRANSPORT_FORWARDERS_FN_INSERT_TAG: &str = "insert:";
 const TRANSPORT_FORWARDERS_FN_REMOVE_TAG: &str = "remove:";
+const TRANSPORT_FORWARDERS_FN_SHUTDOWN_TAG: &str = "shutdown:";
 
 type TransportForwardersContainer =
-    Mutex<HashMap<ComparableTransport, (usize, Arc<TransportForwarder>, Sender<Arc<UMessage>>)>>;
+    Mutex<HashMap<ComparableTransport, (usize, Arc<TransportForwarder>, Arc<ForwardingQueue>)>>;
 
 // we only need one TransportForwarder per out `UTransport`, so we keep track of that one here
-// and the Sender necessary to hand off to the listener for the in `UTransport`
+// and the ForwardingQueue necessary to hand off to the listener for the in `UTransport`
 struct TransportForwarders {
     message_queue_size: usize,
+    dead_letters: Arc<DeadLetters>,
+    forwarder_runtime: ForwarderRuntime,
     forwarders: TransportForwardersContainer,
 }
 
 impl TransportForwarders {
-    pub fn new(message_queue_size: usize) -> Self {
+    pub fn new(
+        message_queue_size: usize,
+        dead_letters: Arc<DeadLetters>,
+        forwarder_runtime: ForwarderRuntime,
+    ) -> Self {
         Self {
             message_queue_size,
+            dead_letters,
+            forwarder_runtime,
             forwarders: Mutex::new(HashMap::new()),
         }
     }
 
-    pub async fn insert(&mut self, out_transport: Arc<dyn UTransport>) -> Sender<Arc<UMessage>> {
+    pub async fn insert(&mut self, out: &Endpoint) -> Arc<ForwardingQueue> {
+        let out_transport = out.transport.clone();
+        let queue_config = out.queue_config;
         let out_comparable_transport = ComparableTransport::new(out_transport.clone());
 
         let mut transport_forwarders = self.forwarders.lock().await;
 
-        let (active, _, sender) = transport_forwarders
+        let (active, transport_forwarder, queue) = transport_forwarders
             .entry(out_comparable_transport)
             .or_insert_with(|| {
                 debug!(
                     "{TRANSPORT_FORWARDERS_TAG}:{TRANSPORT_FORWARDERS_FN_INSERT_TAG} Inserting..."
                 );
-                let (tx, rx) = tokio::sync::broadcast::channel(self.message_queue_size);
-                (0, Arc::new(TransportForwarder::new(out_transport, rx)), tx)
+                let stats = Arc::new(ForwardingCounters::default());
+                let mut queue = ForwardingQueue::new(
+                    self.message_queue_size,
+                    queue_config,
+                    stats.clone(),
+                    self.dead_letters.clone(),
+                );
+                if let Some(deduplication) = out.deduplication {
+                    queue = queue.with_deduplication(deduplication);
+                }
+                let queue = Arc::new(queue);
+                (
+                    0,
+                    Arc::new(TransportForwarder::new(
+                        out_transport,
+                        queue.clone(),
+                        stats,
+                        out.retry_policy.clone(),
+                        out.rate_limit,
+                        &self.forwarder_runtime,
+                    )),
+                    queue,
+                )
             });
+        if queue.queue_config() != queue_config {
+            warn!(
+                "{TRANSPORT_FORWARDERS_TAG}:{TRANSPORT_FORWARDERS_FN_INSERT_TAG} out transport already has a queue with {:?}, ignoring {queue_config:?}",
+                queue.queue_config()
+            );
+        }
+        if queue.deduplication() != out.deduplication {
+            warn!(
+                "{TRANSPORT_FORWARDERS_TAG}:{TRANSPORT_FORWARDERS_FN_INSERT_TAG} out transport already has a queue with deduplication {:?}, ignoring {:?}",
+                queue.deduplication(),
+                out.deduplication
+            );
+        }
+        if transport_forwarder.retry_policy != out.retry_policy {
+            warn!(
+                "{TRANSPORT_FORWARDERS_TAG}:{TRANSPORT_FORWARDERS_FN_INSERT_TAG} out transport already has a forwarder with {:?}, ignoring {:?}",
+                transport_forwarder.retry_policy, out.retry_policy
+            );
+        }
+        if transport_forwarder.rate_limit != out.rate_limit {
+            warn!(
+                "{TRANSPORT_FORWARDERS_TAG}:{TRANSPORT_FORWARDERS_FN_INSERT_TAG} out transport already has a forwarder with rate limit {:?}, ignoring {:?}",
+                transport_forwarder.rate_limit, out.rate_limit
+            );
+        }
         *active += 1;
-        sender.clone()
+        queue.clone()
+    }
+
+    pub async fn stats(
+        &self,
+        registered_forwarding_rules: &HashMap<ForwardingRuleKey, RegisteredForwardingRule>,
+    ) -> Vec<OutTransportStats> {
+        let transport_forwarders = self.forwarders.lock().await;
+
+        let mut out_transport_stats: Vec<_> = transport_forwarders
+            .iter()
+            .map(
+                |(out_comparable_transport, (_, transport_forwarder, queue))| {
+                    let out_endpoints: BTreeSet<_> = registered_forwarding_rules
+                        .iter()
+                        .filter(|((_, _, _, rule_out_transport), _)| {
+                            rule_out_transport == out_comparable_transport
+                        })
+                        .map(|(_, rule)| rule.out_endpoint.name.clone())
+                        .collect();
+                    OutTransportStats {
+                        out_endpoints: out_endpoints.into_iter().collect(),
+                        queue_depth: queue.len(),
+                        queued_bytes: queue.bytes(),
+                        priority_classes: queue.priority_class_stats(),
+                        counts: transport_forwarder.stats.counts(),
+                    }
+                },
+            )
+            .collect();
+        out_transport_stats.sort_by(|a, b| a.out_endpoints.cmp(&b.out_endpoints));
+        out_transport_stats
     }
 
     pub async fn remove(&mut self, out_transport: Arc<dyn UTransport>) {
@@ -163,23 +347,59 @@ impl TransportForwarders {
         if active_num == 0 {
             let removed = transport_forwarders.remove(&out_comparable_transport);
             debug!("{TRANSPORT_FORWARDERS_TAG}:{TRANSPORT_FORWARDERS_FN_REMOVE_TAG} went to remove TransportForwarder for this transport");
-            if removed.is_none() {
-                warn!("{TRANSPORT_FORWARDERS_TAG}:{TRANSPORT_FORWARDERS_FN_REMOVE_TAG} was none to remove");
-            } else {
+            if let Some((_, _, queue)) = removed {
                 debug!("{TRANSPORT_FORWARDERS_TAG}:{TRANSPORT_FORWARDERS_FN_REMOVE_TAG} had one to remove");
+                // lets the TransportForwarder finish
+                queue.close();
+            } else {
+                warn!("{TRANSPORT_FORWARDERS_TAG}:{TRANSPORT_FORWARDERS_FN_REMOVE_TAG} was none to remove");
             }
         }
     }
+
+    // removes all TransportForwarders, giving them until `deadline` to send what is still queued
+    pub async fn shutdown(&mut self, deadline: Instant) {
+        let transport_forwarders: Vec<_> = self
+            .forwarders
+            .lock()
+            .await
+            .drain()
+            .map(|(_, (_, transport_forwarder, queue))| (transport_forwarder, queue))
+            .collect();
+        debug!(
+            "{TRANSPORT_FORWARDERS_TAG}:{TRANSPORT_FORWARDERS_FN_SHUTDOWN_TAG} shutting down {} TransportForwarders",
+            transport_forwarders.len()
+        );
+
+        // close all queues up front, so that they drain concurrently
+        for (_, queue) in &transport_forwarders {
+            queue.close();
+        }
+        for (transport_forwarder, queue) in transport_forwarders {
+            transport_forwarder.join(&queue, deadline).await;
+        }
+    }
+
+    // removes all TransportForwarders without waiting, they end after the current send
+    fn discard_all(&mut self) {
+        for (_, (_, _, queue)) in self.forwarders.get_mut().drain() {
+            queue.close();
+            queue.discard_queued();
+        }
+    }
 }
 
 const FORWARDING_LISTENERS_TAG: &str = "ForwardingListeners:";
 const FORWARDING_LISTENERS_FN_INSERT_TAG: &str = "insert:";
 const FORWARDING_LISTENERS_FN_REMOVE_TAG: &str = "remove:";
+const FORWARDING_LISTENERS_FN_REFRESH_TAG: &str = "refresh:";
+const FORWARDING_LISTENERS_FN_APPLY_SUBSCRIPTION_UPDATE_TAG: &str = "apply_subscription_update:";
 
 type ForwardingListenersContainer =
-    Mutex<HashMap<(ComparableTransport, String, String), (usize, Arc<ForwardingListener>)>>;
+    Mutex<HashMap<(ComparableTransport, String, String), Arc<ForwardingListenerGroup>>>;
 
-// we must have only a single listener per in UTransport and out UAuthority
+// we must have only a single listener per in UTransport and out UAuthority, which hands the
+// messages it receives to the ForwardingListener of every rule sharing it
 struct ForwardingListeners {
     listeners: ForwardingListenersContainer,
 }
@@ -249,28 +469,23 @@ impl ForwardingListeners {
         in_transport: Arc<dyn UTransport>,
         in_authority: &str,
         out_authority: &str,
-        forwarding_id: &str,
-        out_sender: Sender<Arc<UMessage>>,
+        forwarding_listener: ForwardingListener,
         subscription_cache: Arc<Mutex<SubscriptionCache>>,
-    ) -> Result<Option<Arc<ForwardingListener>>, ForwardingListenerError> {
+    ) -> Result<Arc<ForwardingListener>, ForwardingListenerError> {
         let in_comparable_transport = ComparableTransport::new(in_transport.clone());
         let mut forwarding_listeners = self.listeners.lock().await;
 
-        if let Some((active, forwarding_listener)) = forwarding_listeners.get_mut(&(
+        let rule_listener = Arc::new(forwarding_listener);
+        if let Some(forwarding_listener) = forwarding_listeners.get(&(
             in_comparable_transport.clone(),
             in_authority.to_string(),
             out_authority.to_string(),
         )) {
-            *active += 1;
-            if *active > 1 {
-                return Ok(None);
-            } else {
-                return Ok(Some(forwarding_listener.clone()));
-            }
+            forwarding_listener.add(rule_listener.clone());
+            return Ok(rule_listener);
         }
 
-        let forwarding_listener =
-            Arc::new(ForwardingListener::new(forwarding_id, out_sender.clone()));
+        let forwarding_listener = Arc::new(ForwardingListenerGroup::new(rule_listener.clone()));
 
         type SourceSinkFilterPair = (UUri, Option<UUri>);
         #[allow(clippy::mutable_key_type)]
@@ -297,6 +512,11 @@ impl ForwardingListeners {
                 "{}:{} unable to register request listener, error: {}",
                 FORWARDING_LISTENERS_TAG, FORWARDING_LISTENERS_FN_INSERT_TAG, err
             );
+            forwarding_listener.notify_listener_registration(
+                &request_source_filter,
+                Some(&request_sink_filter),
+                Err(&err),
+            );
             for uuri_pair in &uuris_to_backpedal {
                 if let Err(err) = in_transport
                     .unregister_listener(
@@ -318,6 +538,11 @@ impl ForwardingListeners {
                 "{}:{} able to register request listener",
                 FORWARDING_LISTENERS_TAG, FORWARDING_LISTENERS_FN_INSERT_TAG
             );
+            forwarding_listener.notify_listener_registration(
+                &request_source_filter,
+                Some(&request_sink_filter),
+                Ok(()),
+            );
         }
 
         #[allow(clippy::mutable_key_type)]
@@ -358,6 +583,7 @@ impl ForwardingListeners {
                     "{}:{} unable to register listener, error: {}",
                     FORWARDING_LISTENERS_TAG, FORWARDING_LISTENERS_FN_INSERT_TAG, err
                 );
+                forwarding_listener.notify_listener_registration(&source_uri, None, Err(&err));
                 // Perform async unregister_listener
                 for uuri_pair in &uuris_to_backpedal {
                     if let Err(err) = in_transport
@@ -378,21 +604,24 @@ impl ForwardingListeners {
                     source_uri,
                 ));
             } else {
+                forwarding_listener.notify_listener_registration(&source_uri, None, Ok(()));
                 uuris_to_backpedal.insert((source_uri, None));
                 debug!("{FORWARDING_LISTENERS_TAG}:{FORWARDING_LISTENERS_FN_INSERT_TAG} able to register listener");
             }
         }
 
-        // Insert the new listener and update the active count
+        forwarding_listener.set_registered_listeners(uuris_to_backpedal.len() as u64);
+
+        // Insert the new listener
         forwarding_listeners.insert(
             (
                 in_comparable_transport,
                 in_authority.to_string(),
                 out_authority.to_string(),
             ),
-            (1, forwarding_listener.clone()),
+            forwarding_listener,
         );
-        Ok(Some(forwarding_listener))
+        Ok(rule_listener)
     }
 
     pub async fn remove(
@@ -400,14 +629,15 @@ impl ForwardingListeners {
         in_transport: Arc<dyn UTransport>,
         in_authority: &str,
         out_authority: &str,
+        rule_id: RuleId,
         subscription_cache: Arc<Mutex<SubscriptionCache>>,
     ) {
         let in_comparable_transport = ComparableTransport::new(in_transport.clone());
 
         let mut forwarding_listeners = self.listeners.lock().await;
 
-        let active_num = {
-            let Some((active, _)) = forwarding_listeners.get_mut(&(
+        let is_empty = {
+            let Some(forwarding_listener) = forwarding_listeners.get(&(
                 in_comparable_transport.clone(),
                 in_authority.to_string(),
                 out_authority.to_string(),
@@ -415,17 +645,20 @@ impl ForwardingListeners {
                 warn!("{FORWARDING_LISTENERS_TAG}:{FORWARDING_LISTENERS_FN_REMOVE_TAG} no such out_comparable_transport, out_authority: {out_authority:?}");
                 return;
             };
-            *active -= 1;
-            *active
+            match forwarding_listener.remove(rule_id) {
+                Some(rule_listener) => rule_listener.stats.set_registered_listeners(0),
+                None => warn!("{FORWARDING_LISTENERS_TAG}:{FORWARDING_LISTENERS_FN_REMOVE_TAG} no listener of {rule_id}, out_authority: {out_authority:?}"),
+            }
+            forwarding_listener.is_empty()
         };
 
-        if active_num == 0 {
+        if is_empty {
             let removed = forwarding_listeners.remove(&(
                 in_comparable_transport,
                 in_authority.to_string(),
                 out_authority.to_string(),
             ));
-            if let Some((_, forwarding_listener)) = removed {
+            if let Some(forwarding_listener) = removed {
                 let request_source_filter = uauthority_to_uuri(in_authority);
                 let request_sink_filter = uauthority_to_uuri(out_authority);
 
@@ -484,6 +717,248 @@ impl ForwardingListeners {
             }
         }
     }
+
+    pub async fn listener(
+        &self,
+        in_transport: Arc<dyn UTransport>,
+        in_authority: &str,
+        out_authority: &str,
+        rule_id: RuleId,
+    ) -> Option<Arc<ForwardingListener>> {
+        self.listeners
+            .lock()
+            .await
+            .get(&(
+                ComparableTransport::new(in_transport),
+                in_authority.to_string(),
+                out_authority.to_string(),
+            ))
+            .and_then(|forwarding_listener| forwarding_listener.get(rule_id))
+    }
+
+    /// Registers the request and notification listener and the publish listeners of every
+    /// listener from `in_authority` on `in_transport` again, e.g. after the transport reconnected
+    /// and lost them
+    ///
+    /// Registrations the transport still has, reported as [`UCode::ALREADY_EXISTS`], count as
+    /// registered. Returns the error of the last registration which failed, after trying all.
+    pub async fn refresh(
+        &self,
+        in_transport: Arc<dyn UTransport>,
+        in_authority: &str,
+        subscription_cache: Arc<Mutex<SubscriptionCache>>,
+    ) -> Result<(), UStatus> {
+        let in_comparable_transport = ComparableTransport::new(in_transport.clone());
+
+        // lock in the same order as insert() and remove()
+        let forwarding_listeners = self.listeners.lock().await;
+        let subscription_cache = subscription_cache.lock().await;
+
+        let mut result = Ok(());
+        for ((comparable_transport, listener_in_authority, out_authority), forwarding_listener) in
+            forwarding_listeners.iter()
+        {
+            if *comparable_transport != in_comparable_transport
+                || listener_in_authority != in_authority
+            {
+                continue;
+            }
+
+            let mut filters = vec![(
+                uauthority_to_uuri(in_authority),
+                Some(uauthority_to_uuri(out_authority)),
+            )];
+            filters.extend(
+                Self::effective_publish_source_filters(
+                    in_authority,
+                    out_authority,
+                    &subscription_cache
+                        .fetch_cache_entry_with_wildcard(out_authority)
+                        .unwrap_or_default(),
+                    FORWARDING_LISTENERS_FN_REFRESH_TAG,
+                )
+                .into_iter()
+                .map(|source_uri| (source_uri, None)),
+            );
+
+            let mut registered_listeners = 0;
+            for (source_filter, sink_filter) in filters {
+                match in_transport
+                    .register_listener(
+                        &source_filter,
+                        sink_filter.as_ref(),
+                        forwarding_listener.clone(),
+                    )
+                    .await
+                {
+                    Ok(()) => {
+                        forwarding_listener.notify_listener_registration(
+                            &source_filter,
+                            sink_filter.as_ref(),
+                            Ok(()),
+                        );
+                        registered_listeners += 1
+                    }
+                    Err(err) if err.get_code() == UCode::ALREADY_EXISTS => {
+                        registered_listeners += 1
+                    }
+                    Err(err) => {
+                        forwarding_listener.notify_listener_registration(
+                            &source_filter,
+                            sink_filter.as_ref(),
+                            Err(&err),
+                        );
+                        warn!("{FORWARDING_LISTENERS_TAG}:{FORWARDING_LISTENERS_FN_REFRESH_TAG} unable to register listener again for in_authority='{in_authority}', out_authority='{out_authority}', source filter: {source_filter:?}, error: {err}");
+                        result = Err(UStatus::fail_with_code(
+                            err.get_code(),
+                            format!(
+                                "Unable to register listener again for source filter {source_filter:?}: {}",
+                                err.get_message()
+                            ),
+                        ));
+                    }
+                }
+            }
+            debug!("{FORWARDING_LISTENERS_TAG}:{FORWARDING_LISTENERS_FN_REFRESH_TAG} registered {registered_listeners} listeners again for in_authority='{in_authority}', out_authority='{out_authority}'");
+            forwarding_listener.set_registered_listeners(registered_listeners);
+        }
+
+        result
+    }
+
+    /// Applies a uSubscription [`Update`] to the `subscription_cache` and registers or
+    /// unregisters the publish source filters that change as a result on every in `UTransport`
+    /// which forwards towards the subscriber's authority
+    pub async fn apply_subscription_update(
+        &self,
+        update: Update,
+        subscription_cache: Arc<Mutex<SubscriptionCache>>,
+    ) -> Result<(), UStatus> {
+        let Some(topic) = update.topic.into_option() else {
+            return Err(UStatus::fail_with_code(
+                UCode::INVALID_ARGUMENT,
+                "Subscription update contains no topic",
+            ));
+        };
+        let Some(subscriber) = update.subscriber.into_option() else {
+            return Err(UStatus::fail_with_code(
+                UCode::INVALID_ARGUMENT,
+                "Subscription update contains no subscriber",
+            ));
+        };
+        let Some(subscriber_authority) = subscriber
+            .uri
+            .as_ref()
+            .map(|uri| uri.authority_name.clone())
+        else {
+            return Err(UStatus::fail_with_code(
+                UCode::INVALID_ARGUMENT,
+                "Subscription update contains no subscriber URI",
+            ));
+        };
+        let status = update.status.into_option().unwrap_or_default();
+        let state = status.state.enum_value_or_default();
+
+        // lock in the same order as insert() and remove() so that the set of registered publish
+        // listeners can't drift from the contents of the subscription cache
+        let forwarding_listeners = self.listeners.lock().await;
+        let subscription_cache = subscription_cache.lock().await;
+
+        let affected_listeners: Vec<_> = forwarding_listeners
+            .iter()
+            .filter(|((_, _, out_authority), _)| {
+                subscriber_authority == "*" || *out_authority == subscriber_authority
+            })
+            .map(
+                |((in_comparable_transport, in_authority, out_authority), listener)| {
+                    (
+                        in_comparable_transport.transport.clone(),
+                        in_authority.clone(),
+                        out_authority.clone(),
+                        listener.clone(),
+                    )
+                },
+            )
+            .collect();
+
+        #[allow(clippy::mutable_key_type)]
+        let publish_source_filters =
+            |subscription_cache: &SubscriptionCache| -> Vec<HashSet<UUri>> {
+                affected_listeners
+                    .iter()
+                    .map(|(_, in_authority, out_authority, _)| {
+                        Self::effective_publish_source_filters(
+                            in_authority,
+                            out_authority,
+                            &subscription_cache
+                                .fetch_cache_entry_with_wildcard(out_authority)
+                                .unwrap_or_default(),
+                            FORWARDING_LISTENERS_FN_APPLY_SUBSCRIPTION_UPDATE_TAG,
+                        )
+                    })
+                    .collect()
+            };
+
+        let filters_before = publish_source_filters(&subscription_cache);
+
+        let changed = match state {
+            State::SUBSCRIBED | State::SUBSCRIBE_PENDING => {
+                subscription_cache.add_subscription(SubscriptionInformation {
+                    topic,
+                    subscriber,
+                    status,
+                    attributes: update.attributes.into_option().unwrap_or_default(),
+                    config: Default::default(),
+                })?
+            }
+            State::UNSUBSCRIBED => subscription_cache.remove_subscription(&topic, &subscriber)?,
+            State::UNSUBSCRIBE_PENDING => {
+                debug!("{FORWARDING_LISTENERS_TAG}:{FORWARDING_LISTENERS_FN_APPLY_SUBSCRIPTION_UPDATE_TAG} ignoring pending unsubscribe of topic={topic:?}");
+                false
+            }
+        };
+
+        if !changed {
+            return Ok(());
+        }
+
+        let filters_after = publish_source_filters(&subscription_cache);
+        drop(subscription_cache);
+
+        for ((in_transport, in_authority, out_authority, forwarding_listener), (before, after)) in
+            affected_listeners
+                .iter()
+                .zip(filters_before.iter().zip(filters_after.iter()))
+        {
+            for source_uri in after.difference(before) {
+                if let Err(err) = in_transport
+                    .register_listener(source_uri, None, forwarding_listener.clone())
+                    .await
+                {
+                    warn!("{FORWARDING_LISTENERS_TAG}:{FORWARDING_LISTENERS_FN_APPLY_SUBSCRIPTION_UPDATE_TAG} unable to register publish listener for in_authority='{in_authority}', out_authority='{out_authority}', source URI filter: {source_uri:?}, error: {err}");
+                    forwarding_listener.notify_listener_registration(source_uri, None, Err(&err));
+                } else {
+                    forwarding_listener.record_listener_registered();
+                    forwarding_listener.notify_listener_registration(source_uri, None, Ok(()));
+                    debug!("{FORWARDING_LISTENERS_TAG}:{FORWARDING_LISTENERS_FN_APPLY_SUBSCRIPTION_UPDATE_TAG} able to register publish listener for in_authority='{in_authority}', out_authority='{out_authority}', source URI filter: {source_uri:?}");
+                }
+            }
+
+            for source_uri in before.difference(after) {
+                if let Err(err) = in_transport
+                    .unregister_listener(source_uri, None, forwarding_listener.clone())
+                    .await
+                {
+                    warn!("{FORWARDING_LISTENERS_TAG}:{FORWARDING_LISTENERS_FN_APPLY_SUBSCRIPTION_UPDATE_TAG} unable to unregister publish listener for in_authority='{in_authority}', out_authority='{out_authority}', source URI filter: {source_uri:?}, error: {err}");
+                } else {
+                    forwarding_listener.record_listener_unregistered();
+                    debug!("{FORWARDING_LISTENERS_TAG}:{FORWARDING_LISTENERS_FN_APPLY_SUBSCRIPTION_UPDATE_TAG} able to unregister publish listener for in_authority='{in_authority}', out_authority='{out_authority}', source URI filter: {source_uri:?}");
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 /// A [`UStreamer`] is used to coordinate the addition and deletion of forwarding rules between
@@ -622,28 +1097,26 @@ impl ForwardingListeners {
 ///
 /// let subscription_path = "../utils/usubscription-static-file/static-configs/testdata.json".to_string();
 /// let usubscription = Arc::new(USubscriptionStaticFile::new(subscription_path));
-/// let mut streamer = match UStreamer::new(
+/// let mut streamer = match UStreamer::new_async(
 ///    "hoge",
 ///    100,
 ///    usubscription,
-/// ) {
+/// )
+/// .await
+/// {
 ///     Ok(streamer) => streamer,
 ///     Err(error) => panic!("Failed to create uStreamer: {}", error),
 /// };
 ///
 /// // Add forwarding rules to endpoint local<->remote
-/// assert_eq!(
-///     streamer
-///         .add_forwarding_rule(local_endpoint.clone(), remote_endpoint.clone())
-///         .await,
-///     Ok(())
-/// );
-/// assert_eq!(
-///     streamer
-///         .add_forwarding_rule(remote_endpoint.clone(), local_endpoint.clone())
-///         .await,
-///     Ok(())
-/// );
+/// assert!(streamer
+///     .add_forwarding_rule(local_endpoint.clone(), remote_endpoint.clone())
+///     .await
+///     .is_ok());
+/// assert!(streamer
+///     .add_forwarding_rule(remote_endpoint.clone(), local_endpoint.clone())
+///     .await
+///     .is_ok());
 ///
 /// // Add forwarding rules to endpoint local<->local, should report an error
 /// assert!(streamer
@@ -687,24 +1160,74 @@ impl ForwardingListeners {
 pub struct UStreamer {
     name: String,
     registered_forwarding_rules: ForwardingRules,
+    next_rule_id: u64,
+    loop_detection: Option<LoopDetection>,
+    in_flight_requests: Option<Arc<InFlightRequests>>,
+    error_responses: bool,
     transport_forwarders: TransportForwarders,
-    forwarding_listeners: ForwardingListeners,
+    forwarding_listeners: Arc<ForwardingListeners>,
     subscription_cache: Arc<Mutex<SubscriptionCache>>,
+    dead_letters: Arc<DeadLetters>,
+    observer: Arc<SharedObserver>,
+    access_control: Arc<AccessControl>,
+    // the uSubscription service notifying of subscription changes, unregistered from on shutdown
+    subscription_notifications: Option<(Arc<dyn USubscription>, NotificationsRequest)>,
 }
 
 impl UStreamer {
     /// Creates a new UStreamer which can be used to add forwarding rules.
     ///
+    /// Blocks on an internal runtime while fetching the subscriptions, which panics when called
+    /// from within a current-thread Tokio runtime. Prefer [`UStreamer::new_async`] from async
+    /// code. The forwarders run as tasks on the internal runtime, unless set otherwise with
+    /// [`UStreamer::with_forwarder_runtime`]. As with [`UStreamer::new_async`], the caller must
+    /// register the [`UStreamer::subscription_update_listener`] to receive subscription changes.
+    ///
     /// # Parameters
     ///
     /// * name - Used to uniquely identify this UStreamer in logs
     /// * message_queue_size - Determines size of channel used to communicate between `ForwardingListener`
-    ///   and the worker tasks for each currently endpointd `UTransport`
+    ///   and the worker tasks for each currently endpointd `UTransport`, unless overridden with
+    ///   [`Endpoint::with_queue_config`][crate::Endpoint::with_queue_config]
     /// * usubscription - Subscription service which will be used to store subscription info for topics.
+    ///
+    /// # Errors
+    ///
+    /// Same as for [`UStreamer::new_async`]
     pub fn new(
         name: &str,
         message_queue_size: u16,
         usubscription: Arc<dyn USubscription>,
+    ) -> Result<Self, UStatus> {
+        task::block_in_place(|| {
+            CB_RUNTIME.block_on(Self::new_async(name, message_queue_size, usubscription))
+        })
+    }
+
+    /// Creates a new UStreamer which can be used to add forwarding rules, awaiting the
+    /// `usubscription` on the caller's runtime
+    ///
+    /// The forwarders run as tasks on the caller's runtime, unless set otherwise with
+    /// [`UStreamer::with_forwarder_runtime`].
+    ///
+    /// The subscriptions are fetched once. To keep them up to date, the caller must register
+    /// the [`UStreamer::subscription_update_listener`] on every `UTransport` over which the
+    /// uSubscription service publishes its subscription changes; the [`UStreamer`] doesn't know
+    /// where that service runs and doesn't register it by itself.
+    ///
+    /// # Parameters
+    ///
+    /// Same as for [`UStreamer::new`]
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`UStatus`][up_rust::UStatus] if it isn't awaited on a Tokio runtime, or the
+    /// subscriptions can't be fetched from `usubscription` or don't form a valid subscription
+    /// cache
+    pub async fn new_async(
+        name: &str,
+        message_queue_size: u16,
+        usubscription: Arc<dyn USubscription>,
     ) -> Result<Self, UStatus> {
         let name = format!("{USTREAMER_TAG}:{name}:");
         debug!(
@@ -712,6 +1235,16 @@ impl UStreamer {
             &name, USTREAMER_TAG, USTREAMER_FN_NEW_TAG
         );
 
+        let runtime = Handle::try_current().map_err(|err| {
+            UStatus::fail_with_code(
+                UCode::FAILED_PRECONDITION,
+                format!(
+                    "{}:{}:{} Not running on a Tokio runtime: {}",
+                    name, USTREAMER_TAG, USTREAMER_FN_NEW_TAG, err
+                ),
+            )
+        })?;
+
         let uuri: UUri = UUri {
             authority_name: "*".to_string(),
             ue_id: 0x0000_FFFF,     // any instance, any service
@@ -725,7 +1258,11 @@ impl UStreamer {
             ..Default::default()
         };
 
-        // TODO: Create a NotificationsRequest and send over host transport
+        // Register for notifications before fetching so that no change in between is missed
+        let notifications_request = NotificationsRequest {
+            topic: Some(uuri.clone()).into(),
+            ..Default::default()
+        };
 
         // TODO: We need to form a FetchSubscriptionsRequest and send over host transport
         let mut fetch_request = FetchSubscriptionsRequest {
@@ -733,11 +1270,36 @@ impl UStreamer {
             ..Default::default()
         };
         fetch_request.set_subscriber(subscriber_info);
-        let subscriptions = task::block_in_place(|| {
-            CB_RUNTIME
-                .block_on(usubscription.fetch_subscriptions(fetch_request))
-                .expect("Failed to fetch subscriptions")
-        });
+        let subscription_notifications = match usubscription
+            .register_for_notifications(notifications_request.clone())
+            .await
+        {
+            Ok(()) => Some((usubscription.clone(), notifications_request)),
+            Err(err) => {
+                warn!(
+                    "{}:{}:{} Unable to register for subscription change notifications, \
+                    subscriptions will not be updated after start: {:?}",
+                    name, USTREAMER_TAG, USTREAMER_FN_NEW_TAG, err
+                );
+                None
+            }
+        };
+
+        let subscriptions = usubscription
+            .fetch_subscriptions(fetch_request)
+            .await
+            .map_err(|err| {
+                UStatus::fail_with_code(
+                    err.get_code(),
+                    format!(
+                        "{}:{}:{} Unable to fetch subscriptions: {}",
+                        name,
+                        USTREAMER_TAG,
+                        USTREAMER_FN_NEW_TAG,
+                        err.get_message()
+                    ),
+                )
+            })?;
 
         let subscription_cache_result = SubscriptionCache::new(subscriptions);
 
@@ -760,54 +1322,206 @@ impl UStreamer {
             }
         };
 
+        let observer = Arc::new(SharedObserver::default());
+        let dead_letters = Arc::new(DeadLetters::new(observer.clone()));
+
         Ok(Self {
             name: name.to_string(),
-            registered_forwarding_rules: Mutex::new(HashSet::new()),
-            transport_forwarders: TransportForwarders::new(message_queue_size as usize),
-            forwarding_listeners: ForwardingListeners::new(),
+            registered_forwarding_rules: Mutex::new(HashMap::new()),
+            next_rule_id: 0,
+            loop_detection: None,
+            in_flight_requests: None,
+            error_responses: false,
+            transport_forwarders: TransportForwarders::new(
+                message_queue_size as usize,
+                dead_letters.clone(),
+                ForwarderRuntime::Tasks(runtime),
+            ),
+            forwarding_listeners: Arc::new(ForwardingListeners::new()),
             subscription_cache: subscription_cache.clone(),
+            dead_letters,
+            observer,
+            access_control: Default::default(),
+            subscription_notifications,
         })
     }
 
-    #[inline(always)]
-    fn forwarding_id(r#in: &Endpoint, out: &Endpoint) -> String {
-        format!(
-            "[in.name: {}, in.authority: {:?} ; out.name: {}, out.authority: {:?}]",
-            r#in.name, r#in.authority, out.name, out.authority
-        )
+    /// Sets where the forwarders of out `UTransport`s added from now on run
+    ///
+    /// Use [`ForwarderRuntime::DedicatedThreads`][crate::ForwarderRuntime::DedicatedThreads] for
+    /// out `UTransport`s whose `send()` blocks, so that they don't stall the other tasks.
+    pub fn with_forwarder_runtime(mut self, forwarder_runtime: ForwarderRuntime) -> Self {
+        self.transport_forwarders.forwarder_runtime = forwarder_runtime;
+        // the timers of requests tracked from now on run alongside the forwarders
+        if let Some(in_flight_requests) = &self.in_flight_requests {
+            self.in_flight_requests = Some(Arc::new(InFlightRequests::new(
+                in_flight_requests.request_timeouts(),
+                self.runtime_handle(),
+            )));
+        }
+        self
     }
 
-    #[inline(always)]
-    fn fail_due_to_same_authority(&self, r#in: &Endpoint, out: &Endpoint) -> Result<(), UStatus> {
-        let err = Err(UStatus::fail_with_code(
-            UCode::INVALID_ARGUMENT,
-            format!(
-                "{} are the same. Unable to delete.",
-                Self::forwarding_id(r#in, out)
-            ),
-        ));
-        error!(
-            "{}:{}:{} Deleting forwarding rule failed: {:?}",
-            self.name, USTREAMER_TAG, USTREAMER_FN_ADD_FORWARDING_RULE_TAG, err
-        );
-        err
+    /// Drops messages which come back to a forwarding rule they already passed, for forwarding
+    /// rules added from now on
+    pub fn with_loop_detection(mut self, loop_detection: LoopDetection) -> Self {
+        self.loop_detection = Some(loop_detection);
+        self
     }
 
-    /// Adds a forwarding rule to the [`UStreamer`] based on an in [`Endpoint`][crate::Endpoint] and an
-    /// out [`Endpoint`][crate::Endpoint]
+    /// Responds with `DEADLINE_EXCEEDED` to requests which aren't answered before their
+    /// time-to-live elapses and drops their late responses, for forwarding rules added from now on
     ///
-    /// Works for any [`UMessage`][up_rust::UMessage] type which has a destination / sink contained
-    /// in its attributes, i.e.
-    /// * [`UMessageType::UMESSAGE_TYPE_NOTIFICATION`][up_rust::UMessageType::UMESSAGE_TYPE_NOTIFICATION]
-    /// * [`UMessageType::UMESSAGE_TYPE_REQUEST`][up_rust::UMessageType::UMESSAGE_TYPE_REQUEST]
-    /// * [`UMessageType::UMESSAGE_TYPE_RESPONSE`][up_rust::UMessageType::UMESSAGE_TYPE_RESPONSE]
-    /// * [`UMessageType::UMESSAGE_TYPE_PUBLISH`][up_rust::UMessageType::UMESSAGE_TYPE_PUBLISH]
+    /// See [`RequestTimeouts`][crate::RequestTimeouts]. Requests are tracked across all forwarding
+    /// rules, so the response to a request may pass any of them.
+    pub fn with_request_timeouts(mut self, request_timeouts: RequestTimeouts) -> Self {
+        self.in_flight_requests = Some(Arc::new(InFlightRequests::new(
+            request_timeouts,
+            self.runtime_handle(),
+        )));
+        self
+    }
+
+    /// Answers requests which can't be forwarded with an error response, sent back over the in
+    /// [`Endpoint`][crate::Endpoint] of their forwarding rule, for forwarding rules added from now
+    /// on
     ///
-    /// # Parameters
+    /// Lets clients fail fast instead of waiting for the time-to-live of their requests. The
+    /// `commstatus` of the response tells why the request was dropped:
+    /// * `PERMISSION_DENIED` when it didn't pass the [`MessageFilter`][crate::MessageFilter] or
+    ///   was dropped by the [`MessageTransformer`][crate::MessageTransformer] of the rule, or the
+    ///   [`AccessPolicy`][crate::AccessPolicy] denied it
+    /// * `RESOURCE_EXHAUSTED` when it didn't fit into the queue of the out `UTransport` or
+    ///   exceeded a [`RateLimit`][crate::RateLimit]
+    /// * `UNAVAILABLE` when sending it failed, its rule was paused or the [`UStreamer`] shut
+    ///   down
+    /// * `INVALID_ARGUMENT` when its payload refers to shared memory
+    ///
+    /// Expired requests are not answered.
+    pub fn with_error_responses(mut self) -> Self {
+        self.error_responses = true;
+        self
+    }
+
+    /// Returns a [`UListener`][up_rust::UListener] which applies the uSubscription
+    /// [`Update`][up_rust::core::usubscription::Update]s it receives to this [`UStreamer`]
+    ///
+    /// Register it on the `UTransport` over which the uSubscription service publishes its
+    /// subscription change topic, see
+    /// [`RESOURCE_ID_SUBSCRIPTION_CHANGE`][up_rust::core::usubscription::RESOURCE_ID_SUBSCRIPTION_CHANGE].
+    pub fn subscription_update_listener(&self) -> Arc<dyn UListener> {
+        Arc::new(SubscriptionUpdateListener {
+            name: self.name.clone(),
+            forwarding_listeners: self.forwarding_listeners.clone(),
+            subscription_cache: self.subscription_cache.clone(),
+        })
+    }
+
+    /// Sets the [`DeadLetterSink`][crate::DeadLetterSink] receiving every message this
+    /// [`UStreamer`] is unable to forward, replacing any previous one
+    ///
+    /// Applies to existing forwarding rules as well as to those added later. Without a sink,
+    /// such messages are only counted as dropped or failed in [`UStreamer::stats`].
+    pub fn set_dead_letter_sink(&self, dead_letter_sink: Arc<dyn DeadLetterSink>) {
+        self.dead_letters.set_sink(dead_letter_sink);
+    }
+
+    /// Sets the [`StreamerObserver`][crate::StreamerObserver] receiving the lifecycle and
+    /// forwarding events of this [`UStreamer`], replacing any previous one
+    ///
+    /// Applies to existing forwarding rules as well as to those added later.
+    pub fn set_observer(&self, observer: Arc<dyn StreamerObserver>) {
+        self.observer.set(observer);
+    }
+
+    /// Sets the [`AccessPolicy`][crate::AccessPolicy] deciding which messages may cross between
+    /// the [`Endpoint`][crate::Endpoint]s of this [`UStreamer`], replacing any previous one
+    ///
+    /// Applies to existing forwarding rules as well as to those added later, so a policy is
+    /// reloaded by setting it again. Until a policy is set, all messages are permitted.
+    pub fn set_access_policy(&self, access_policy: AccessPolicy) {
+        self.access_control.set_policy(access_policy);
+    }
+
+    /// Applies a uSubscription [`Update`][up_rust::core::usubscription::Update] to this
+    /// [`UStreamer`]
+    ///
+    /// The subscription is added to or removed from the subscription cache and publish listeners
+    /// are registered or unregistered on every in [`Endpoint`][crate::Endpoint] whose forwarding
+    /// rules lead to the subscriber's authority.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`UStatus`][up_rust::UStatus] if the update lacks its topic or subscriber
+    pub async fn apply_subscription_update(&self, update: Update) -> Result<(), UStatus> {
+        debug!(
+            "{}:{}:{} Applying subscription update: {:?}",
+            self.name, USTREAMER_TAG, USTREAMER_FN_APPLY_SUBSCRIPTION_UPDATE_TAG, update
+        );
+
+        self.forwarding_listeners
+            .apply_subscription_update(update, self.subscription_cache.clone())
+            .await
+    }
+
+    #[inline(always)]
+    // the runtime the forwarders run on, or the internal one if they run on dedicated threads
+    fn runtime_handle(&self) -> Handle {
+        match &self.transport_forwarders.forwarder_runtime {
+            ForwarderRuntime::Tasks(handle) => handle.clone(),
+            ForwarderRuntime::DedicatedThreads => CB_RUNTIME.handle().clone(),
+        }
+    }
+
+    fn forwarding_id(r#in: &Endpoint, out: &Endpoint) -> String {
+        format!(
+            "[in.name: {}, in.authority: {:?} ; out.name: {}, out.authority: {:?}]",
+            r#in.name, r#in.authority, out.name, out.authority
+        )
+    }
+
+    fn forwarding_rule_key(r#in: &Endpoint, out: &Endpoint) -> ForwardingRuleKey {
+        (
+            r#in.authority.clone(),
+            out.authority.clone(),
+            ComparableTransport::new(r#in.transport.clone()),
+            ComparableTransport::new(out.transport.clone()),
+        )
+    }
+
+    #[inline(always)]
+    fn fail_due_to_same_authority<T>(&self, r#in: &Endpoint, out: &Endpoint) -> Result<T, UStatus> {
+        let err = UStatus::fail_with_code(
+            UCode::INVALID_ARGUMENT,
+            format!(
+                "{} are the same. Unable to delete.",
+                Self::forwarding_id(r#in, out)
+            ),
+        );
+        error!(
+            "{}:{}:{} Deleting forwarding rule failed: {:?}",
+            self.name, USTREAMER_TAG, USTREAMER_FN_ADD_FORWARDING_RULE_TAG, err
+        );
+        Err(err)
+    }
+
+    /// Adds a forwarding rule to the [`UStreamer`] based on an in [`Endpoint`][crate::Endpoint] and an
+    /// out [`Endpoint`][crate::Endpoint]
+    ///
+    /// Works for any [`UMessage`][up_rust::UMessage] type which has a destination / sink contained
+    /// in its attributes, i.e.
+    /// * [`UMessageType::UMESSAGE_TYPE_NOTIFICATION`][up_rust::UMessageType::UMESSAGE_TYPE_NOTIFICATION]
+    /// * [`UMessageType::UMESSAGE_TYPE_REQUEST`][up_rust::UMessageType::UMESSAGE_TYPE_REQUEST]
+    /// * [`UMessageType::UMESSAGE_TYPE_RESPONSE`][up_rust::UMessageType::UMESSAGE_TYPE_RESPONSE]
+    /// * [`UMessageType::UMESSAGE_TYPE_PUBLISH`][up_rust::UMessageType::UMESSAGE_TYPE_PUBLISH]
+    ///
+    /// # Parameters
     ///
     /// * `in` - [`Endpoint`][crate::Endpoint] we will bridge _from_
     /// * `out` - [`Endpoint`][crate::Endpoint] we will bridge _onto_
     ///
+    /// Returns the [`RuleId`][crate::RuleId] of the new rule.
+    ///
     /// # Errors
     ///
     /// If unable to add this forwarding rule, we return a [`UStatus`][up_rust::UStatus] noting
@@ -820,7 +1534,92 @@ impl UStreamer {
         &mut self,
         r#in: Endpoint,
         out: Endpoint,
+    ) -> Result<RuleId, UStatus> {
+        self.add_forwarding_rule_with_options(r#in, out, ForwardingRuleOptions::default())
+            .await
+    }
+
+    /// Adds a forwarding rule to the [`UStreamer`] which only forwards the messages passing
+    /// `filter`
+    ///
+    /// Shorthand for [`UStreamer::add_forwarding_rule_with_options`] with only a filter set.
+    ///
+    /// # Parameters
+    ///
+    /// * `in` - [`Endpoint`][crate::Endpoint] we will bridge _from_
+    /// * `out` - [`Endpoint`][crate::Endpoint] we will bridge _onto_
+    /// * `filter` - [`MessageFilter`][crate::MessageFilter] a message has to pass to be forwarded
+    ///
+    /// # Errors
+    ///
+    /// Same as for [`UStreamer::add_forwarding_rule`]
+    pub async fn add_filtered_forwarding_rule(
+        &mut self,
+        r#in: Endpoint,
+        out: Endpoint,
+        filter: MessageFilter,
+    ) -> Result<RuleId, UStatus> {
+        self.add_forwarding_rule_with_options(
+            r#in,
+            out,
+            ForwardingRuleOptions::default().with_filter(filter),
+        )
+        .await
+    }
+
+    /// Adds a forwarding rule to the [`UStreamer`] with the given
+    /// [`ForwardingRuleOptions`][crate::ForwardingRuleOptions]
+    ///
+    /// Behaves like [`UStreamer::add_forwarding_rule`] apart from that. Messages rejected by the
+    /// [`MessageFilter`][crate::MessageFilter] or dropped by the
+    /// [`MessageTransformer`][crate::MessageTransformer] of the options are counted as `filtered`
+    /// in the rule's [`ForwardingCounts`][crate::ForwardingCounts].
+    ///
+    /// Rules from the same in `UTransport` and in authority to the same out authority share a
+    /// single listener on the in `UTransport`, which hands every message to each of them, so
+    /// each rule applies its own options to it.
+    ///
+    /// # Parameters
+    ///
+    /// * `in` - [`Endpoint`][crate::Endpoint] we will bridge _from_
+    /// * `out` - [`Endpoint`][crate::Endpoint] we will bridge _onto_
+    /// * `options` - [`ForwardingRuleOptions`][crate::ForwardingRuleOptions] of the rule
+    ///
+    /// # Errors
+    ///
+    /// Same as for [`UStreamer::add_forwarding_rule`]
+    pub async fn add_forwarding_rule_with_options(
+        &mut self,
+        r#in: Endpoint,
+        out: Endpoint,
+        options: ForwardingRuleOptions,
+    ) -> Result<RuleId, UStatus> {
+        let rule_id = RuleId(self.next_rule_id);
+        self.register_forwarding_rule(RegisteredForwardingRule {
+            id: rule_id,
+            in_endpoint: r#in,
+            out_endpoint: out,
+            created_at: SystemTime::now(),
+            options,
+            stats: Arc::new(ForwardingCounters::default()),
+            pause: Default::default(),
+        })
+        .await?;
+        self.next_rule_id += 1;
+        Ok(rule_id)
+    }
+
+    // registers the forwarding rule and its listener, undoing everything if that fails
+    async fn register_forwarding_rule(
+        &mut self,
+        rule: RegisteredForwardingRule,
     ) -> Result<(), UStatus> {
+        let rule_id = rule.id;
+        let r#in = rule.in_endpoint.clone();
+        let out = rule.out_endpoint.clone();
+        let options = rule.options.clone();
+        let rule_stats = rule.stats.clone();
+        let rule_pause = rule.pause.clone();
         debug!(
             "{}:{}:{} Adding forwarding rule for {}",
             self.name,
@@ -833,19 +1632,16 @@ impl UStreamer {
             return self.fail_due_to_same_authority(&r#in, &out);
         }
 
-        let in_comparable_transport = ComparableTransport::new(r#in.transport.clone());
-        let out_comparable_transport = ComparableTransport::new(out.transport.clone());
-
-        let forwarding_rule = (
-            r#in.authority.clone(),
-            out.authority.clone(),
-            in_comparable_transport,
-            out_comparable_transport,
-        );
+        let forwarding_rule = Self::forwarding_rule_key(&r#in, &out);
 
         let inserted = {
             let mut registered_forwarding_rules = self.registered_forwarding_rules.lock().await;
-            registered_forwarding_rules.insert(forwarding_rule.clone())
+            if registered_forwarding_rules.contains_key(&forwarding_rule) {
+                false
+            } else {
+                registered_forwarding_rules.insert(forwarding_rule.clone(), rule);
+                true
+            }
         };
 
         if !inserted {
@@ -856,10 +1652,35 @@ impl UStreamer {
             ));
         }
 
-        let out_sender = self
-            .transport_forwarders
-            .insert(out.transport.clone())
-            .await;
+        let out_queue = self.transport_forwarders.insert(&out).await;
+        let mut forwarding_listener = ForwardingListener::new(
+            rule_id,
+            &Self::forwarding_id(&r#in, &out),
+            out_queue,
+            rule_stats,
+        )
+        .with_options(options)
+        .with_pause(rule_pause)
+        .with_observer(self.observer.clone());
+        if let Some(loop_detection) = self.loop_detection {
+            forwarding_listener = forwarding_listener.with_loop_detection(loop_detection);
+        }
+        if let Some(in_flight_requests) = &self.in_flight_requests {
+            forwarding_listener = forwarding_listener
+                .with_request_timeouts(in_flight_requests.clone(), r#in.transport.clone());
+        }
+        forwarding_listener =
+            forwarding_listener.with_access_control(self.access_control.clone(), &out);
+        if self.error_responses {
+            let mut error_responder =
+                ErrorResponder::new(r#in.transport.clone(), self.runtime_handle());
+            if let Some(in_flight_requests) = &self.in_flight_requests {
+                error_responder =
+                    error_responder.with_in_flight_requests(in_flight_requests.clone());
+            }
+            forwarding_listener =
+                forwarding_listener.with_error_responder(Arc::new(error_responder));
+        }
 
         if let Err(err) = self
             .forwarding_listeners
@@ -867,8 +1688,7 @@ impl UStreamer {
                 r#in.transport.clone(),
                 &r#in.authority,
                 &out.authority,
-                &Self::forwarding_id(&r#in, &out),
-                out_sender,
+                forwarding_listener,
                 self.subscription_cache.clone(),
             )
             .await
@@ -888,6 +1708,17 @@ impl UStreamer {
             ));
         }
 
+        if let Some(rule) = self
+            .registered_forwarding_rules
+            .lock()
+            .await
+            .get(&forwarding_rule)
+        {
+            let rule = rule.descriptor();
+            self.observer
+                .notify(|observer| observer.on_rule_added(&rule));
+        }
+
         Ok(())
     }
 
@@ -931,1130 +1762,4416 @@ impl UStreamer {
             return self.fail_due_to_same_authority(&r#in, &out);
         }
 
-        let in_comparable_transport = ComparableTransport::new(r#in.transport.clone());
-        let out_comparable_transport = ComparableTransport::new(out.transport.clone());
+        let forwarding_rule = Self::forwarding_rule_key(&r#in, &out);
 
         let remove_res = {
             let mut registered_forwarding_rules = self.registered_forwarding_rules.lock().await;
-            registered_forwarding_rules.remove(&(
-                r#in.authority.clone(),
-                out.authority.clone(),
-                in_comparable_transport.clone(),
-                out_comparable_transport.clone(),
-            ))
+            registered_forwarding_rules.remove(&forwarding_rule)
         };
 
         match remove_res {
-            true => {
-                self.transport_forwarders
-                    .remove(out.transport.clone())
-                    .await;
-                self.forwarding_listeners
-                    .remove(
-                        r#in.transport.clone(),
-                        &r#in.authority,
-                        &out.authority,
-                        self.subscription_cache.clone(),
-                    )
+            Some(rule) => {
+                self.release_forwarding_rule(&forwarding_rule, rule.id)
                     .await;
+                self.observer
+                    .notify(|observer| observer.on_rule_removed(&rule.descriptor()));
                 Ok(())
             }
-            false => Err(UStatus::fail_with_code(UCode::NOT_FOUND, "not found")),
+            None => Err(UStatus::fail_with_code(UCode::NOT_FOUND, "not found")),
         }
     }
-}
 
-#[derive(Clone)]
-pub(crate) struct ComparableTransport {
-    transport: Arc<dyn UTransport>,
-}
+    /// Deletes the forwarding rule with the given [`RuleId`][crate::RuleId] from the
+    /// [`UStreamer`]
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`UStatus`][up_rust::UStatus] with [`UCode::NOT_FOUND`][up_rust::UCode::NOT_FOUND]
+    /// if there is no such rule
+    pub async fn delete_forwarding_rule_by_id(&mut self, rule_id: RuleId) -> Result<(), UStatus> {
+        debug!(
+            "{}:{}:{} Deleting forwarding rule {}",
+            self.name, USTREAMER_TAG, USTREAMER_FN_DELETE_FORWARDING_RULE_BY_ID_TAG, rule_id
+        );
 
-impl ComparableTransport {
-    pub fn new(transport: Arc<dyn UTransport>) -> Self {
-        Self { transport }
+        self.unregister_forwarding_rule(rule_id).await.map(|_| ())
     }
-}
 
-impl Hash for ComparableTransport {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        Arc::as_ptr(&self.transport).hash(state);
-    }
-}
+    // unregisters the forwarding rule with `rule_id` and releases what it holds, returning it so
+    // that it can be registered again
+    async fn unregister_forwarding_rule(
+        &mut self,
+        rule_id: RuleId,
+    ) -> Result<RegisteredForwardingRule, UStatus> {
+        let removed = {
+            let mut registered_forwarding_rules = self.registered_forwarding_rules.lock().await;
+            let forwarding_rule = registered_forwarding_rules
+                .iter()
+                .find(|(_, rule)| rule.id == rule_id)
+                .map(|(forwarding_rule, _)| forwarding_rule.clone());
+            forwarding_rule.and_then(|forwarding_rule| {
+                registered_forwarding_rules.remove_entry(&forwarding_rule)
+            })
+        };
 
-impl PartialEq for ComparableTransport {
-    fn eq(&self, other: &Self) -> bool {
-        Arc::ptr_eq(&self.transport, &other.transport)
+        let Some((forwarding_rule, rule)) = removed else {
+            return Err(UStatus::fail_with_code(
+                UCode::NOT_FOUND,
+                format!("{rule_id} not found"),
+            ));
+        };
+        self.release_forwarding_rule(&forwarding_rule, rule.id)
+            .await;
+        self.observer
+            .notify(|observer| observer.on_rule_removed(&rule.descriptor()));
+        Ok(rule)
     }
-}
-
-impl Eq for ComparableTransport {}
-
-const TRANSPORT_FORWARDER_TAG: &str = "TransportForwarder:";
-const TRANSPORT_FORWARDER_FN_MESSAGE_FORWARDING_LOOP_TAG: &str = "message_forwarding_loop():";
-pub(crate) struct TransportForwarder {}
-
-impl TransportForwarder {
-    fn new(out_transport: Arc<dyn UTransport>, message_receiver: Receiver<Arc<UMessage>>) -> Self {
-        let out_transport_clone = out_transport.clone();
-        let message_receiver_clone = message_receiver.resubscribe();
 
-        thread::spawn(|| {
-            // Create a new single-threaded runtime
-            let runtime = Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .expect("Failed to create Tokio runtime");
-
-            runtime.block_on(async move {
-                trace!("Within blocked runtime");
-                Self::message_forwarding_loop(
-                    UUID::build().to_hyphenated_string(),
-                    out_transport_clone,
-                    message_receiver_clone,
-                )
-                .await;
-                info!("Broke out of loop! You probably dropped the UPClientVsomeip");
-            });
-        });
+    /// Applies a batch of [`RuleChange`][crate::RuleChange]s to the [`UStreamer`], all or nothing
+    ///
+    /// The changes are applied in order. If one of them fails, those applied before it are undone
+    /// in reverse order: added rules are deleted again and deleted rules are added back with
+    /// their previous [`RuleId`][crate::RuleId] and counters.
+    ///
+    /// Returns the [`RuleId`][crate::RuleId]s of the added rules, in the order of their
+    /// [`RuleChange::Add`][crate::RuleChange::Add]s.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`UStatus`][up_rust::UStatus] of the change which failed, as
+    /// [`UStreamer::add_forwarding_rule`] or [`UStreamer::delete_forwarding_rule_by_id`] would
+    pub async fn apply_rules(
+        &mut self,
+        changes: impl IntoIterator<Item = RuleChange>,
+    ) -> Result<Vec<RuleId>, UStatus> {
+        let mut applied = Vec::new();
+        for change in changes {
+            let res = match change {
+                RuleChange::Add { r#in, out, options } => self
+                    .add_forwarding_rule_with_options(r#in, out, options)
+                    .await
+                    .map(AppliedRuleChange::Added),
+                RuleChange::Delete(rule_id) => self
+                    .unregister_forwarding_rule(rule_id)
+                    .await
+                    .map(|rule| AppliedRuleChange::Deleted(Box::new(rule))),
+            };
+            match res {
+                Ok(applied_change) => applied.push(applied_change),
+                Err(err) => {
+                    warn!(
+                        "{}:{}:{} Rolling back {} applied changes: {:?}",
+                        self.name,
+                        USTREAMER_TAG,
+                        USTREAMER_FN_APPLY_RULES_TAG,
+                        applied.len(),
+                        err
+                    );
+                    self.roll_back(applied).await;
+                    return Err(err);
+                }
+            }
+        }
 
-        Self {}
+        Ok(applied
+            .into_iter()
+            .filter_map(|applied_change| match applied_change {
+                AppliedRuleChange::Added(rule_id) => Some(rule_id),
+                AppliedRuleChange::Deleted(_) => None,
+            })
+            .collect())
     }
 
-    async fn message_forwarding_loop(
-        id: String,
-        out_transport: Arc<dyn UTransport>,
-        mut message_receiver: Receiver<Arc<UMessage>>,
-    ) {
-        while let Ok(msg) = message_receiver.recv().await {
-            debug!(
-                "{}:{}:{} Attempting send of message: {:?}",
-                id,
-                TRANSPORT_FORWARDER_TAG,
-                TRANSPORT_FORWARDER_FN_MESSAGE_FORWARDING_LOOP_TAG,
-                msg
-            );
-            let send_res = out_transport.send(msg.deref().clone()).await;
-            if let Err(err) = send_res {
-                warn!(
-                    "{}:{}:{} Sending on out_transport failed: {:?}",
-                    id,
-                    TRANSPORT_FORWARDER_TAG,
-                    TRANSPORT_FORWARDER_FN_MESSAGE_FORWARDING_LOOP_TAG,
-                    err
-                );
-            } else {
-                debug!(
-                    "{}:{}:{} Sending on out_transport succeeded",
-                    id, TRANSPORT_FORWARDER_TAG, TRANSPORT_FORWARDER_FN_MESSAGE_FORWARDING_LOOP_TAG
+    async fn roll_back(&mut self, applied: Vec<AppliedRuleChange>) {
+        for applied_change in applied.into_iter().rev() {
+            let res = match applied_change {
+                AppliedRuleChange::Added(rule_id) => {
+                    self.unregister_forwarding_rule(rule_id).await.map(|_| ())
+                }
+                AppliedRuleChange::Deleted(rule) => self.register_forwarding_rule(*rule).await,
+            };
+            if let Err(err) = res {
+                error!(
+                    "{}:{}:{} Unable to roll back a change: {:?}",
+                    self.name, USTREAMER_TAG, USTREAMER_FN_APPLY_RULES_TAG, err
                 );
             }
         }
     }
-}
-
-const FORWARDING_LISTENER_TAG: &str = "ForwardingListener:";
-const FORWARDING_LISTENER_FN_ON_RECEIVE_TAG: &str = "on_receive():";
 
-#[derive(Clone)]
-pub(crate) struct ForwardingListener {
-    forwarding_id: String,
-    sender: Sender<Arc<UMessage>>,
-}
+    /// Pauses the forwarding rule with the given [`RuleId`][crate::RuleId] until it is resumed
+    /// with [`UStreamer::resume_rule`]
+    ///
+    /// The rule's listener stays registered and its counters are kept, but the messages it
+    /// receives are buffered or dropped as per `action` and counted as `paused`. Pausing a rule
+    /// which is already paused changes its [`PauseAction`][crate::PauseAction], keeping what it
+    /// buffered so far. Messages still buffered when the rule is deleted are discarded.
+    ///
+    /// Other rules are not affected, also those sharing the listener on the in `UTransport`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`UStatus`][up_rust::UStatus] with [`UCode::NOT_FOUND`][up_rust::UCode::NOT_FOUND]
+    /// if there is no such rule
+    pub async fn pause_rule(&self, rule_id: RuleId, action: PauseAction) -> Result<(), UStatus> {
+        debug!(
+            "{}:{}:{} Pausing forwarding rule {} with {:?}",
+            self.name, USTREAMER_TAG, USTREAMER_FN_PAUSE_RULE_TAG, rule_id, action
+        );
 
-impl ForwardingListener {
-    pub(crate) fn new(forwarding_id: &str, sender: Sender<Arc<UMessage>>) -> Self {
-        Self {
-            forwarding_id: forwarding_id.to_string(),
-            sender,
-        }
+        let registered_forwarding_rules = self.registered_forwarding_rules.lock().await;
+        let Some(rule) = registered_forwarding_rules
+            .values()
+            .find(|rule| rule.id == rule_id)
+        else {
+            return Err(UStatus::fail_with_code(
+                UCode::NOT_FOUND,
+                format!("{rule_id} not found"),
+            ));
+        };
+        rule.pause.pause(action);
+        Ok(())
     }
-}
 
-#[async_trait]
-impl UListener for ForwardingListener {
-    async fn on_receive(&self, msg: UMessage) {
+    /// Resumes the forwarding rule with the given [`RuleId`][crate::RuleId] after
+    /// [`UStreamer::pause_rule`]
+    ///
+    /// The messages buffered while the rule was paused are forwarded first, oldest first, as if
+    /// they had just been received. Messages the rule receives meanwhile are buffered behind
+    /// them, so that they keep their order. Resuming a rule which isn't paused does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`UStatus`][up_rust::UStatus] with [`UCode::NOT_FOUND`][up_rust::UCode::NOT_FOUND]
+    /// if there is no such rule
+    pub async fn resume_rule(&self, rule_id: RuleId) -> Result<(), UStatus> {
         debug!(
-            "{}:{}:{} Received message: {:?}",
-            self.forwarding_id,
-            FORWARDING_LISTENER_TAG,
-            FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
-            &msg
+            "{}:{}:{} Resuming forwarding rule {}",
+            self.name, USTREAMER_TAG, USTREAMER_FN_RESUME_RULE_TAG, rule_id
         );
 
-        if msg.attributes.payload_format.enum_value_or_default()
-            == UPayloadFormat::UPAYLOAD_FORMAT_SHM
-        {
-            debug!(
-                "{}:{}:{} Received message with type UPAYLOAD_FORMAT_SHM, \
-                which is not supported. A pointer to shared memory will not \
-                be usable on another device. UAttributes: {:#?}",
-                self.forwarding_id,
-                FORWARDING_LISTENER_TAG,
-                FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
-                &msg.attributes
-            );
-            return;
+        let (forwarding_rule, pause) = {
+            let registered_forwarding_rules = self.registered_forwarding_rules.lock().await;
+            let Some((forwarding_rule, rule)) = registered_forwarding_rules
+                .iter()
+                .find(|(_, rule)| rule.id == rule_id)
+            else {
+                return Err(UStatus::fail_with_code(
+                    UCode::NOT_FOUND,
+                    format!("{rule_id} not found"),
+                ));
+            };
+            (forwarding_rule.clone(), rule.pause.clone())
+        };
+        if !pause.resume() {
+            return Ok(());
         }
-        if let Err(e) = self.sender.send(Arc::new(msg)) {
-            error!(
-                "{}:{}:{} Unable to send message to worker pool: {e:?}",
-                self.forwarding_id, FORWARDING_LISTENER_TAG, FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+
+        let (in_authority, out_authority, in_comparable_transport, _) = forwarding_rule;
+        let Some(forwarding_listener) = self
+            .forwarding_listeners
+            .listener(
+                in_comparable_transport.transport,
+                &in_authority,
+                &out_authority,
+                rule_id,
+            )
+            .await
+        else {
+            let discarded = std::iter::from_fn(|| pause.next_buffered()).count();
+            warn!(
+                "{}:{}:{} No listener for {}, discarding {} buffered messages",
+                self.name, USTREAMER_TAG, USTREAMER_FN_RESUME_RULE_TAG, rule_id, discarded
             );
+            return Ok(());
+        };
+        // one at a time, so that messages received meanwhile are buffered behind the remaining
+        // ones until the buffer is empty
+        let mut forwarded = 0;
+        while let Some(msg) = pause.next_buffered() {
+            forwarding_listener.forward(msg).await;
+            forwarded += 1;
         }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::ustreamer::{uauthority_to_uuri, ForwardingListeners, TransportForwarders};
-    use crate::{Endpoint, UStreamer};
-    use async_trait::async_trait;
-    use std::collections::{HashMap, HashSet};
-    use std::str::FromStr;
-    use std::sync::{Arc, Mutex as StdMutex};
-    use subscription_cache::SubscriptionCache;
-    use tokio::sync::Mutex as TokioMutex;
-    use up_rust::core::usubscription::{FetchSubscriptionsResponse, SubscriberInfo, Subscription};
-    use up_rust::{UCode, UListener, UMessage, UStatus, UTransport, UUri};
-    use usubscription_static_file::USubscriptionStaticFile;
-
-    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-    struct ListenerRegistration {
-        source_filter: UUri,
-        sink_filter: Option<UUri>,
+        debug!(
+            "{}:{}:{} Forwarded {} messages buffered by {}",
+            self.name, USTREAMER_TAG, USTREAMER_FN_RESUME_RULE_TAG, forwarded, rule_id
+        );
+        Ok(())
     }
 
-    fn listener_registration(
-        source_filter: &UUri,
-        sink_filter: Option<&UUri>,
-    ) -> ListenerRegistration {
-        ListenerRegistration {
-            source_filter: source_filter.clone(),
-            sink_filter: sink_filter.cloned(),
-        }
-    }
+    /// Refreshes the forwarding rules using an [`Endpoint`][crate::Endpoint], e.g. after the
+    /// connection of its `UTransport` was lost and re-established
+    ///
+    /// The rules are found by the name of `endpoint`, without having to delete them:
+    /// * if `endpoint` has the `UTransport` the rules already use, the request and notification
+    ///   listener and the publish listeners of the rules bridging from it are registered on it
+    ///   again, registrations it reports as [`UCode::ALREADY_EXISTS`][up_rust::UCode::ALREADY_EXISTS]
+    ///   count as registered
+    /// * if `endpoint` has another `UTransport`, e.g. a fresh session, the rules are moved onto
+    ///   `endpoint`, keeping their [`RuleId`][crate::RuleId]s, options and counters. Their
+    ///   listeners are unregistered from the previous `UTransport` and messages still queued for
+    ///   it are sent before its forwarder ends.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`UStatus`][up_rust::UStatus] with
+    /// * [`UCode::NOT_FOUND`][up_rust::UCode::NOT_FOUND] if no rule uses an endpoint of that name
+    /// * [`UCode::INVALID_ARGUMENT`][up_rust::UCode::INVALID_ARGUMENT] if the rules use an
+    ///   endpoint of that name with another authority
+    /// * the error of registering a listener again, or of moving a rule, in which case all rules
+    ///   are moved back onto the previous `UTransport`
+    pub async fn refresh_endpoint(&mut self, endpoint: Endpoint) -> Result<(), UStatus> {
+        debug!(
+            "{}:{}:{} Refreshing endpoint {} with authority {:?}",
+            self.name,
+            USTREAMER_TAG,
+            USTREAMER_FN_REFRESH_ENDPOINT_TAG,
+            endpoint.name,
+            endpoint.authority
+        );
 
-    #[derive(Default)]
-    struct RecordingTransport {
-        register_calls: StdMutex<Vec<ListenerRegistration>>,
-        unregister_calls: StdMutex<Vec<ListenerRegistration>>,
-        register_call_counts: StdMutex<HashMap<ListenerRegistration, usize>>,
-        unregister_call_counts: StdMutex<HashMap<ListenerRegistration, usize>>,
-        forced_register_failures: StdMutex<HashMap<ListenerRegistration, UStatus>>,
-        duplicate_register_failure: StdMutex<Option<UStatus>>,
-    }
+        let (mut rule_ids, same_transport) = {
+            let registered_forwarding_rules = self.registered_forwarding_rules.lock().await;
+            let mut rule_ids = Vec::new();
+            let mut same_transport = true;
+            for rule in registered_forwarding_rules.values() {
+                for rule_endpoint in [&rule.in_endpoint, &rule.out_endpoint] {
+                    if rule_endpoint.name != endpoint.name {
+                        continue;
+                    }
+                    if rule_endpoint.authority != endpoint.authority {
+                        return Err(UStatus::fail_with_code(
+                            UCode::INVALID_ARGUMENT,
+                            format!(
+                                "Endpoint {} is used with authority {:?}, not {:?}",
+                                endpoint.name, rule_endpoint.authority, endpoint.authority
+                            ),
+                        ));
+                    }
+                    same_transport &= Arc::ptr_eq(&rule_endpoint.transport, &endpoint.transport);
+                    rule_ids.push(rule.id);
+                }
+            }
+            (rule_ids, same_transport)
+        };
 
-    impl RecordingTransport {
-        fn register_calls(&self) -> Vec<ListenerRegistration> {
-            self.register_calls.lock().unwrap().clone()
+        if rule_ids.is_empty() {
+            return Err(UStatus::fail_with_code(
+                UCode::NOT_FOUND,
+                format!("No forwarding rule uses endpoint {}", endpoint.name),
+            ));
         }
 
-        fn unregister_calls(&self) -> Vec<ListenerRegistration> {
-            self.unregister_calls.lock().unwrap().clone()
+        if same_transport {
+            return self
+                .forwarding_listeners
+                .refresh(
+                    endpoint.transport.clone(),
+                    &endpoint.authority,
+                    self.subscription_cache.clone(),
+                )
+                .await;
         }
 
-        fn register_call_count(&self, source_filter: &UUri, sink_filter: Option<&UUri>) -> usize {
-            self.register_call_counts
-                .lock()
-                .unwrap()
-                .get(&listener_registration(source_filter, sink_filter))
-                .copied()
-                .unwrap_or(0)
-        }
+        rule_ids.sort();
+        info!(
+            "{}:{}:{} Moving {} forwarding rules onto the new transport of endpoint {}",
+            self.name,
+            USTREAMER_TAG,
+            USTREAMER_FN_REFRESH_ENDPOINT_TAG,
+            rule_ids.len(),
+            endpoint.name
+        );
 
-        fn unregister_call_count(&self, source_filter: &UUri, sink_filter: Option<&UUri>) -> usize {
-            self.unregister_call_counts
-                .lock()
-                .unwrap()
-                .get(&listener_registration(source_filter, sink_filter))
-                .copied()
-                .unwrap_or(0)
+        let mut previous_rules = Vec::new();
+        for rule_id in rule_ids {
+            previous_rules.push(self.unregister_forwarding_rule(rule_id).await?);
         }
+        let moved_rules: Vec<_> = previous_rules
+            .iter()
+            .map(|rule| rule.with_endpoint(&endpoint))
+            .collect();
 
-        fn set_register_failure(
-            &self,
-            source_filter: &UUri,
-            sink_filter: Option<&UUri>,
-            status: UStatus,
-        ) {
-            self.forced_register_failures
-                .lock()
-                .unwrap()
-                .insert(listener_registration(source_filter, sink_filter), status);
+        // undone like a batch of rule changes should moving one of the rules fail
+        let mut applied: Vec<_> = previous_rules
+            .into_iter()
+            .map(|rule| AppliedRuleChange::Deleted(Box::new(rule)))
+            .collect();
+        for rule in moved_rules {
+            let rule_id = rule.id;
+            if let Err(err) = self.register_forwarding_rule(rule).await {
+                warn!(
+                    "{}:{}:{} Moving {} back onto the previous transport: {:?}",
+                    self.name, USTREAMER_TAG, USTREAMER_FN_REFRESH_ENDPOINT_TAG, rule_id, err
+                );
+                self.roll_back(applied).await;
+                return Err(err);
+            }
+            applied.push(AppliedRuleChange::Added(rule_id));
         }
 
-        fn clear_register_failure(&self, source_filter: &UUri, sink_filter: Option<&UUri>) {
-            self.forced_register_failures
-                .lock()
-                .unwrap()
-                .remove(&listener_registration(source_filter, sink_filter));
-        }
+        Ok(())
+    }
 
-        fn fail_duplicate_registers_with(&self, status: UStatus) {
-            *self.duplicate_register_failure.lock().unwrap() = Some(status);
-        }
+    // releases the out transport's forwarder and the in transport's listener held by a
+    // forwarding rule which was just unregistered
+    async fn release_forwarding_rule(
+        &mut self,
+        forwarding_rule: &ForwardingRuleKey,
+        rule_id: RuleId,
+    ) {
+        let (in_authority, out_authority, in_comparable_transport, out_comparable_transport) =
+            forwarding_rule;
+        self.transport_forwarders
+            .remove(out_comparable_transport.transport.clone())
+            .await;
+        self.forwarding_listeners
+            .remove(
+                in_comparable_transport.transport.clone(),
+                in_authority,
+                out_authority,
+                rule_id,
+                self.subscription_cache.clone(),
+            )
+            .await;
     }
 
-    #[async_trait]
-    impl UTransport for RecordingTransport {
-        async fn send(&self, _message: UMessage) -> Result<(), UStatus> {
-            Ok(())
-        }
+    /// Returns a description of every forwarding rule of this [`UStreamer`], ordered by
+    /// [`RuleId`][crate::RuleId]
+    pub async fn rules(&self) -> Vec<ForwardingRuleDescriptor> {
+        let registered_forwarding_rules = self.registered_forwarding_rules.lock().await;
 
-        async fn receive(
-            &self,
-            _source_filter: &UUri,
-            _sink_filter: Option<&UUri>,
-        ) -> Result<UMessage, UStatus> {
-            Err(UStatus::fail_with_code(
-                UCode::UNIMPLEMENTED,
-                "not implemented",
-            ))
-        }
+        let mut rules: Vec<_> = registered_forwarding_rules
+            .values()
+            .map(RegisteredForwardingRule::descriptor)
+            .collect();
+        rules.sort_by_key(|rule| rule.id);
+        rules
+    }
 
-        async fn register_listener(
-            &self,
-            source_filter: &UUri,
-            sink_filter: Option<&UUri>,
-            _listener: Arc<dyn UListener>,
-        ) -> Result<(), UStatus> {
-            let registration = listener_registration(source_filter, sink_filter);
+    /// Returns a snapshot of the message counters of every forwarding rule and of every out
+    /// `UTransport` of this [`UStreamer`]
+    ///
+    /// Counters of a forwarding rule start at zero when it is added and are discarded when it is
+    /// deleted.
+    pub async fn stats(&self) -> UStreamerStats {
+        let registered_forwarding_rules = self.registered_forwarding_rules.lock().await;
 
-            self.register_calls
-                .lock()
-                .unwrap()
-                .push(registration.clone());
+        let mut rules: Vec<_> = registered_forwarding_rules
+            .iter()
+            .map(
+                |((in_authority, out_authority, _, _), rule)| ForwardingRuleStats {
+                    in_endpoint: rule.in_endpoint.name.clone(),
+                    in_authority: in_authority.clone(),
+                    out_endpoint: rule.out_endpoint.name.clone(),
+                    out_authority: out_authority.clone(),
+                    registered_listeners: rule.stats.registered_listeners(),
+                    counts: rule.stats.counts(),
+                },
+            )
+            .collect();
+        rules.sort_by(|a, b| {
+            (&a.in_endpoint, &a.out_endpoint).cmp(&(&b.in_endpoint, &b.out_endpoint))
+        });
 
-            let register_count = {
-                let mut counts = self.register_call_counts.lock().unwrap();
-                let entry = counts.entry(registration.clone()).or_insert(0);
-                *entry += 1;
-                *entry
-            };
+        let out_transports = self
+            .transport_forwarders
+            .stats(&registered_forwarding_rules)
+            .await;
 
-            if let Some(status) = self
-                .forced_register_failures
-                .lock()
-                .unwrap()
-                .get(&registration)
-                .cloned()
+        UStreamerStats {
+            rules,
+            out_transports,
+        }
+    }
+
+    /// Shuts down the [`UStreamer`] by deleting all of its forwarding rules
+    ///
+    /// Unregisters from the subscription change notifications of the uSubscription service and
+    /// every listener from the in `UTransport`s, then gives the forwarder of each out
+    /// `UTransport` until `drain_timeout` has passed to send the messages still queued and joins
+    /// it. Messages still queued after `drain_timeout` are discarded and counted as
+    /// dropped; a forwarder stuck in sending past that point is not joined, but ends once the send
+    /// returns.
+    ///
+    /// Forwarding rules can be added again afterwards, though subscription changes are no longer
+    /// notified of. Dropping a [`UStreamer`] without calling
+    /// `shutdown` discards queued messages right away and unregisters its listeners in the
+    /// background.
+    pub async fn shutdown(&mut self, drain_timeout: Duration) {
+        let deadline = Instant::now() + drain_timeout;
+
+        if let Some((usubscription, notifications_request)) = self.subscription_notifications.take()
+        {
+            if let Err(err) = usubscription
+                .unregister_for_notifications(notifications_request)
+                .await
             {
-                return Err(status);
+                warn!(
+                    "{}:{}:{} Unable to unregister from subscription change notifications: {:?}",
+                    self.name, USTREAMER_TAG, USTREAMER_FN_SHUTDOWN_TAG, err
+                );
             }
+        }
 
-            if register_count > 1 {
-                if let Some(status) = self.duplicate_register_failure.lock().unwrap().clone() {
-                    return Err(status);
-                }
-            }
+        let forwarding_rules: Vec<_> = self
+            .registered_forwarding_rules
+            .lock()
+            .await
+            .drain()
+            .collect();
+        info!(
+            "{}:{}:{} Shutting down, deleting {} forwarding rules",
+            self.name,
+            USTREAMER_TAG,
+            USTREAMER_FN_SHUTDOWN_TAG,
+            forwarding_rules.len()
+        );
 
-            Ok(())
+        for ((in_authority, out_authority, in_comparable_transport, _), rule) in forwarding_rules {
+            self.forwarding_listeners
+                .remove(
+                    in_comparable_transport.transport,
+                    &in_authority,
+                    &out_authority,
+                    rule.id,
+                    self.subscription_cache.clone(),
+                )
+                .await;
+            self.observer
+                .notify(|observer| observer.on_rule_removed(&rule.descriptor()));
         }
 
-        async fn unregister_listener(
-            &self,
-            source_filter: &UUri,
-            sink_filter: Option<&UUri>,
-            _listener: Arc<dyn UListener>,
-        ) -> Result<(), UStatus> {
-            let registration = listener_registration(source_filter, sink_filter);
-
-            self.unregister_calls
-                .lock()
-                .unwrap()
-                .push(registration.clone());
+        self.transport_forwarders.shutdown(deadline).await;
+    }
+}
 
-            let mut counts = self.unregister_call_counts.lock().unwrap();
-            let entry = counts.entry(registration).or_insert(0);
-            *entry += 1;
+impl Drop for UStreamer {
+    fn drop(&mut self) {
+        let forwarding_rules: Vec<_> = self
+            .registered_forwarding_rules
+            .get_mut()
+            .drain()
+            .map(|(forwarding_rule, rule)| (forwarding_rule, rule.id))
+            .collect();
+        self.transport_forwarders.discard_all();
 
-            Ok(())
+        if forwarding_rules.is_empty() {
+            return;
         }
-    }
 
-    fn make_test_streamer(entries: &[(&str, &str)]) -> UStreamer {
-        UStreamer {
-            name: "test-streamer".to_string(),
-            registered_forwarding_rules: TokioMutex::new(HashSet::new()),
-            transport_forwarders: TransportForwarders::new(16),
-            forwarding_listeners: ForwardingListeners::new(),
-            subscription_cache: make_subscription_cache(entries),
-        }
+        warn!(
+            "{}:{}:{} Dropped without shutdown(), unregistering listeners of {} forwarding rules in the background",
+            self.name,
+            USTREAMER_TAG,
+            USTREAMER_FN_DROP_TAG,
+            forwarding_rules.len()
+        );
+        let forwarding_listeners = self.forwarding_listeners.clone();
+        let subscription_cache = self.subscription_cache.clone();
+        let unregister_listeners = async move {
+            for ((in_authority, out_authority, in_comparable_transport, _), rule_id) in
+                forwarding_rules
+            {
+                forwarding_listeners
+                    .remove(
+                        in_comparable_transport.transport,
+                        &in_authority,
+                        &out_authority,
+                        rule_id,
+                        subscription_cache.clone(),
+                    )
+                    .await;
+            }
+        };
+        drop(self.runtime_handle().spawn(unregister_listeners));
     }
+}
 
-    fn make_subscription_cache(entries: &[(&str, &str)]) -> Arc<TokioMutex<SubscriptionCache>> {
-        let subscriptions = entries
-            .iter()
-            .map(|(topic, subscriber)| {
-                let topic_uri = UUri::from_str(topic).unwrap();
-                let subscriber_uri = UUri::from_str(subscriber).unwrap();
-                let subscriber_info = SubscriberInfo {
-                    uri: Some(subscriber_uri).into(),
-                    ..Default::default()
-                };
-
-                Subscription {
-                    topic: Some(topic_uri).into(),
-                    subscriber: Some(subscriber_info).into(),
-                    ..Default::default()
-                }
-            })
-            .collect();
+#[derive(Clone)]
+pub(crate) struct ComparableTransport {
+    transport: Arc<dyn UTransport>,
+}
 
-        Arc::new(TokioMutex::new(
-            SubscriptionCache::new(FetchSubscriptionsResponse {
-                subscriptions,
-                ..Default::default()
+impl ComparableTransport {
+    pub fn new(transport: Arc<dyn UTransport>) -> Self {
+        Self { transport }
+    }
+}
+
+impl Hash for ComparableTransport {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.transport).hash(state);
+    }
+}
+
+impl PartialEq for ComparableTransport {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.transport, &other.transport)
+    }
+}
+
+impl Eq for ComparableTransport {}
+
+const TRANSPORT_FORWARDER_TAG: &str = "TransportForwarder:";
+const TRANSPORT_FORWARDER_FN_MESSAGE_FORWARDING_LOOP_TAG: &str = "message_forwarding_loop():";
+const TRANSPORT_FORWARDER_FN_JOIN_TAG: &str = "join():";
+
+// the task or thread running the message_forwarding_loop, along with a way to await its end
+// from async code without blocking
+struct RunningForwarder {
+    worker: ForwarderWorker,
+    finished: oneshot::Receiver<()>,
+}
+
+pub(crate) struct TransportForwarder {
+    stats: Arc<ForwardingCounters>,
+    retry_policy: RetryPolicy,
+    rate_limit: Option<RateLimit>,
+    running: StdMutex<Option<RunningForwarder>>,
+}
+
+impl TransportForwarder {
+    fn new(
+        out_transport: Arc<dyn UTransport>,
+        message_queue: Arc<ForwardingQueue>,
+        stats: Arc<ForwardingCounters>,
+        retry_policy: RetryPolicy,
+        rate_limit: Option<RateLimit>,
+        forwarder_runtime: &ForwarderRuntime,
+    ) -> Self {
+        let out_transport_clone = out_transport.clone();
+        let stats_clone = stats.clone();
+        let retry_policy_clone = retry_policy.clone();
+        let (finished_tx, finished_rx) = oneshot::channel();
+
+        let worker = forwarder_runtime.spawn(async move {
+            Self::message_forwarding_loop(
+                UUID::build().to_hyphenated_string(),
+                out_transport_clone,
+                message_queue,
+                stats_clone,
+                retry_policy_clone,
+                rate_limit.map(RateLimiter::new),
+            )
+            .await;
+            info!("Broke out of loop! The forwarding queue of this out transport was closed");
+            let _ = finished_tx.send(());
+        });
+
+        Self {
+            stats,
+            retry_policy,
+            rate_limit,
+            running: StdMutex::new(Some(RunningForwarder {
+                worker,
+                finished: finished_rx,
+            })),
+        }
+    }
+
+    // waits until `deadline` for the forwarder to drain the closed `message_queue` and end, after
+    // which the remaining messages are discarded and the forwarder is left to end on its own
+    async fn join(&self, message_queue: &ForwardingQueue, deadline: Instant) {
+        let Some(RunningForwarder {
+            worker,
+            mut finished,
+        }) = self.running.lock().unwrap().take()
+        else {
+            return;
+        };
+
+        if tokio::time::timeout_at(deadline, &mut finished)
+            .await
+            .is_err()
+        {
+            warn!(
+                "{TRANSPORT_FORWARDER_TAG}:{TRANSPORT_FORWARDER_FN_JOIN_TAG} deadline passed, discarding {} queued messages",
+                message_queue.len()
+            );
+            message_queue.discard_queued();
+            return;
+        }
+
+        if !worker.join().await {
+            warn!("{TRANSPORT_FORWARDER_TAG}:{TRANSPORT_FORWARDER_FN_JOIN_TAG} forwarder panicked");
+        }
+    }
+
+    async fn message_forwarding_loop(
+        id: String,
+        out_transport: Arc<dyn UTransport>,
+        message_queue: Arc<ForwardingQueue>,
+        stats: Arc<ForwardingCounters>,
+        retry_policy: RetryPolicy,
+        rate_limiter: Option<RateLimiter>,
+    ) {
+        while let Some(QueuedMessage {
+            message: msg,
+            rule_id,
+            rule_stats,
+            error_responder,
+            in_flight_requests,
+            span,
+        }) = message_queue.pop().await
+        {
+            stats.record_received();
+            debug!(
+                "{}:{}:{} Attempting send of message: {:?}",
+                id,
+                TRANSPORT_FORWARDER_TAG,
+                TRANSPORT_FORWARDER_FN_MESSAGE_FORWARDING_LOOP_TAG,
+                msg
+            );
+            if let Some(rate_limiter) = &rate_limiter {
+                match rate_limiter.admit(&msg).await {
+                    Admission::Passed => {}
+                    Admission::Delayed => {
+                        stats.record_throttled();
+                        rule_stats.record_throttled();
+                    }
+                    Admission::Rejected => {
+                        stats.record_throttled();
+                        rule_stats.record_throttled();
+                        stats.record_dropped();
+                        rule_stats.record_dropped();
+                        debug!(
+                            "{}:{}:{} Dropping message exceeding the rate limit of the out transport: {:?}",
+                            id,
+                            TRANSPORT_FORWARDER_TAG,
+                            TRANSPORT_FORWARDER_FN_MESSAGE_FORWARDING_LOOP_TAG,
+                            msg.attributes
+                        );
+                        let reason = DeadLetterReason::RateLimited;
+                        if let Some(error_responder) = &error_responder {
+                            error_responder.respond_to_dead_letter(rule_id, &reason, &msg);
+                        }
+                        message_queue.dead_letters().post(rule_id, reason, &msg);
+                        continue;
+                    }
+                }
+            }
+            let send_span = tracing::info_span!(
+                parent: &span,
+                "send",
+                out_transport = %id,
+                attempts = tracing::field::Empty
+            );
+            // the next hop continues the trace from this send
+            let mut outgoing = msg.deref().clone();
+            trace_context::inject(&send_span, &mut outgoing);
+            let mut attempt = 1;
+            let send_res = loop {
+                // the message may have aged past its TTL while queued or backing off
+                if is_expired(&msg) {
+                    break None;
+                }
+                match out_transport
+                    .send(outgoing.clone())
+                    .instrument(send_span.clone())
+                    .await
+                {
+                    Err(err) if retry_policy.should_retry(attempt, &err) => {
+                        stats.record_retry();
+                        rule_stats.record_retry();
+                        let backoff = retry_policy.backoff(attempt);
+                        debug!(
+                            "{}:{}:{} Sending on out_transport failed on attempt {}, retrying in {:?}: {:?}",
+                            id,
+                            TRANSPORT_FORWARDER_TAG,
+                            TRANSPORT_FORWARDER_FN_MESSAGE_FORWARDING_LOOP_TAG,
+                            attempt,
+                            backoff,
+                            err
+                        );
+                        tokio::time::sleep(backoff).await;
+                        attempt += 1;
+                    }
+                    send_res => break Some(send_res),
+                }
+            };
+            send_span.record("attempts", attempt);
+            let Some(send_res) = send_res else {
+                stats.record_expired();
+                rule_stats.record_expired();
+                debug!(
+                    "{}:{}:{} Dropping expired message: {:?}",
+                    id,
+                    TRANSPORT_FORWARDER_TAG,
+                    TRANSPORT_FORWARDER_FN_MESSAGE_FORWARDING_LOOP_TAG,
+                    msg.attributes
+                );
+                message_queue
+                    .dead_letters()
+                    .post(rule_id, DeadLetterReason::Expired, &msg);
+                continue;
+            };
+            if let Err(err) = send_res {
+                stats.record_send_failure();
+                rule_stats.record_send_failure();
+                warn!(
+                    "{}:{}:{} Sending on out_transport failed after {} attempts: {:?}",
+                    id,
+                    TRANSPORT_FORWARDER_TAG,
+                    TRANSPORT_FORWARDER_FN_MESSAGE_FORWARDING_LOOP_TAG,
+                    attempt,
+                    err
+                );
+                let reason = DeadLetterReason::SendFailed(err);
+                if let Some(error_responder) = &error_responder {
+                    error_responder.respond_to_dead_letter(rule_id, &reason, &msg);
+                }
+                message_queue.dead_letters().post(rule_id, reason, &msg);
+            } else {
+                stats.record_forwarded();
+                rule_stats.record_forwarded();
+                // only a response which was actually sent answers its request
+                if let Some(in_flight_requests) = &in_flight_requests {
+                    in_flight_requests.answered(&msg);
+                }
+                message_queue
+                    .observer()
+                    .notify(|observer| observer.on_message_forwarded(rule_id, &msg));
+                debug!(
+                    "{}:{}:{} Sending on out_transport succeeded",
+                    id, TRANSPORT_FORWARDER_TAG, TRANSPORT_FORWARDER_FN_MESSAGE_FORWARDING_LOOP_TAG
+                );
+            }
+        }
+    }
+}
+
+const FORWARDING_LISTENER_TAG: &str = "ForwardingListener:";
+const FORWARDING_LISTENER_FN_ON_RECEIVE_TAG: &str = "on_receive():";
+
+#[derive(Clone)]
+pub(crate) struct ForwardingListener {
+    rule_id: RuleId,
+    forwarding_id: Arc<str>,
+    queue: Arc<ForwardingQueue>,
+    stats: Arc<ForwardingCounters>,
+    options: Arc<ForwardingRuleOptions>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    loop_detection: Option<(LoopDetection, Arc<RecentlySeen>)>,
+    // along with the in UTransport, which timeout responses are sent over
+    request_timeouts: Option<(Arc<InFlightRequests>, Arc<dyn UTransport>)>,
+    error_responder: Option<Arc<ErrorResponder>>,
+    // along with the name and authority of the out Endpoint
+    access_control: Option<(Arc<AccessControl>, Arc<str>, Arc<str>)>,
+    pause: Arc<RulePause>,
+    observer: Arc<SharedObserver>,
+}
+
+impl ForwardingListener {
+    pub(crate) fn new(
+        rule_id: RuleId,
+        forwarding_id: &str,
+        queue: Arc<ForwardingQueue>,
+        stats: Arc<ForwardingCounters>,
+    ) -> Self {
+        Self {
+            rule_id,
+            forwarding_id: Arc::from(forwarding_id),
+            queue,
+            stats,
+            options: Default::default(),
+            rate_limiter: None,
+            loop_detection: None,
+            request_timeouts: None,
+            error_responder: None,
+            access_control: None,
+            pause: Default::default(),
+            observer: Default::default(),
+        }
+    }
+
+    pub(crate) fn with_options(mut self, options: ForwardingRuleOptions) -> Self {
+        self.rate_limiter = options
+            .rate_limit
+            .map(|rate_limit| Arc::new(RateLimiter::new(rate_limit)));
+        self.options = Arc::new(options);
+        self
+    }
+
+    pub(crate) fn with_loop_detection(mut self, loop_detection: LoopDetection) -> Self {
+        self.loop_detection = Some((
+            loop_detection,
+            Arc::new(RecentlySeen::new(loop_detection.capacity)),
+        ));
+        self
+    }
+
+    pub(crate) fn with_request_timeouts(
+        mut self,
+        in_flight_requests: Arc<InFlightRequests>,
+        in_transport: Arc<dyn UTransport>,
+    ) -> Self {
+        self.request_timeouts = Some((in_flight_requests, in_transport));
+        self
+    }
+
+    pub(crate) fn with_error_responder(mut self, error_responder: Arc<ErrorResponder>) -> Self {
+        self.error_responder = Some(error_responder);
+        self
+    }
+
+    pub(crate) fn with_access_control(
+        mut self,
+        access_control: Arc<AccessControl>,
+        out: &Endpoint,
+    ) -> Self {
+        self.access_control = Some((
+            access_control,
+            Arc::from(out.name.as_str()),
+            Arc::from(out.authority.as_str()),
+        ));
+        self
+    }
+
+    pub(crate) fn with_pause(mut self, pause: Arc<RulePause>) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    pub(crate) fn with_observer(mut self, observer: Arc<SharedObserver>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    fn notify_listener_registration(
+        &self,
+        source_filter: &UUri,
+        sink_filter: Option<&UUri>,
+        res: Result<(), &UStatus>,
+    ) {
+        self.observer.notify(|observer| match res {
+            Ok(()) => observer.on_listener_registered(source_filter, sink_filter),
+            Err(status) => {
+                observer.on_listener_registration_failed(source_filter, sink_filter, status)
+            }
+        });
+    }
+
+    // hands a message which can't be forwarded to the DeadLetterSink, answering it if it is a
+    // request
+    fn discard(&self, reason: DeadLetterReason, msg: &UMessage) {
+        if let Some(error_responder) = &self.error_responder {
+            error_responder.respond_to_dead_letter(self.rule_id, &reason, msg);
+        }
+        self.queue.dead_letters().post(self.rule_id, reason, msg);
+    }
+
+    fn respond_with_error(&self, msg: &UMessage, commstatus: UCode) {
+        if let Some(error_responder) = &self.error_responder {
+            error_responder.respond(self.rule_id, msg, commstatus);
+        }
+    }
+
+    // for the messages which are dropped without becoming dead letters
+    fn notify_dropped(&self, reason: DropReason, msg: &UMessage) {
+        self.observer
+            .notify(|observer| observer.on_message_dropped(self.rule_id, &reason, msg));
+    }
+
+    // everything on_receive() does once a message got past the pause of the rule, which is also
+    // how buffered messages are forwarded when the rule is resumed
+    pub(crate) async fn forward(&self, msg: UMessage) {
+        let span = tracing::info_span!("forward", rule = %self.forwarding_id);
+        trace_context::continue_trace(&span, &msg);
+        self.forward_in_span(msg).instrument(span).await
+    }
+
+    async fn forward_in_span(&self, msg: UMessage) {
+        if let Some((access_control, out_endpoint, out_authority)) = &self.access_control {
+            if !access_control.permits(&msg, out_endpoint, out_authority) {
+                self.stats.record_denied();
+                debug!(
+                    "{}:{}:{} Access policy denies message, not forwarding it. UAttributes: {:?}",
+                    self.forwarding_id,
+                    FORWARDING_LISTENER_TAG,
+                    FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+                    &msg.attributes
+                );
+                self.discard(DeadLetterReason::Denied, &msg);
+                return;
+            }
+        }
+        // the id of the message is only remembered once it is queued, see below
+        if let Some((loop_detection, recently_seen)) = &self.loop_detection {
+            if msg
+                .attributes
+                .id
+                .as_ref()
+                .is_some_and(|id| recently_seen.contains(id))
+            {
+                self.stats.record_looped();
+                let attributes = msg.attributes.get_or_default();
+                if loop_detection.log_loops {
+                    warn!(
+                        "{}:{}:{} Dropping message {} which came back to this forwarding rule, from {} to {}",
+                        self.forwarding_id,
+                        FORWARDING_LISTENER_TAG,
+                        FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+                        attributes.id.get_or_default().to_hyphenated_string(),
+                        attributes.source.get_or_default().to_uri(false),
+                        attributes.sink.get_or_default().to_uri(false)
+                    );
+                } else {
+                    debug!(
+                        "{}:{}:{} Dropping message which came back to this forwarding rule. UAttributes: {:?}",
+                        self.forwarding_id,
+                        FORWARDING_LISTENER_TAG,
+                        FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+                        attributes
+                    );
+                }
+                self.notify_dropped(DropReason::Looped, &msg);
+                return;
+            }
+        }
+        if let Some((in_flight_requests, _)) = &self.request_timeouts {
+            if msg.is_response() && in_flight_requests.is_late(&msg) {
+                self.stats.record_dropped();
+                debug!(
+                    "{}:{}:{} Dropping response to a request which timed out already. UAttributes: {:?}",
+                    self.forwarding_id,
+                    FORWARDING_LISTENER_TAG,
+                    FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+                    &msg.attributes
+                );
+                self.discard(DeadLetterReason::LateResponse, &msg);
+                return;
+            }
+        }
+        if !self.options.filter.matches(&msg) {
+            self.stats.record_filtered();
+            debug!(
+                "{}:{}:{} Message doesn't pass the filter of the forwarding rule, not forwarding it. UAttributes: {:?}",
+                self.forwarding_id,
+                FORWARDING_LISTENER_TAG,
+                FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+                &msg.attributes
+            );
+            self.notify_dropped(DropReason::Filtered, &msg);
+            self.respond_with_error(&msg, UCode::PERMISSION_DENIED);
+            return;
+        }
+        if msg.attributes.payload_format.enum_value_or_default()
+            == UPayloadFormat::UPAYLOAD_FORMAT_SHM
+        {
+            self.stats.record_dropped();
+            debug!(
+                "{}:{}:{} Received message with type UPAYLOAD_FORMAT_SHM, \
+                which is not supported. A pointer to shared memory will not \
+                be usable on another device. UAttributes: {:#?}",
+                self.forwarding_id,
+                FORWARDING_LISTENER_TAG,
+                FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+                &msg.attributes
+            );
+            self.discard(DeadLetterReason::SharedMemoryPayload, &msg);
+            return;
+        }
+        if is_expired(&msg) {
+            self.stats.record_expired();
+            debug!(
+                "{}:{}:{} Received expired message, not forwarding it. UAttributes: {:?}",
+                self.forwarding_id,
+                FORWARDING_LISTENER_TAG,
+                FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+                &msg.attributes
+            );
+            self.discard(DeadLetterReason::Expired, &msg);
+            return;
+        }
+        let msg = match &self.options.transformer {
+            Some(transformer) => {
+                // the transformer consumes the message, keep it for the observer and error response
+                let original = (self.observer.is_set()
+                    || (self.error_responder.is_some() && msg.is_request()))
+                .then(|| msg.clone());
+                match transformer.transform(msg) {
+                    Some(msg) => msg,
+                    None => {
+                        self.stats.record_filtered();
+                        debug!(
+                            "{}:{}:{} Message dropped by the transformer of the forwarding rule",
+                            self.forwarding_id,
+                            FORWARDING_LISTENER_TAG,
+                            FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+                        );
+                        if let Some(original) = original {
+                            self.notify_dropped(DropReason::Filtered, &original);
+                            self.respond_with_error(&original, UCode::PERMISSION_DENIED);
+                        }
+                        return;
+                    }
+                }
+            }
+            None => msg,
+        };
+        if let Some(rate_limiter) = &self.rate_limiter {
+            match rate_limiter.admit(&msg).await {
+                Admission::Passed => {}
+                Admission::Delayed => self.stats.record_throttled(),
+                Admission::Rejected => {
+                    self.stats.record_throttled();
+                    self.stats.record_dropped();
+                    debug!(
+                        "{}:{}:{} Dropping message exceeding the rate limit of the forwarding rule. UAttributes: {:?}",
+                        self.forwarding_id,
+                        FORWARDING_LISTENER_TAG,
+                        FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+                        &msg.attributes
+                    );
+                    self.discard(DeadLetterReason::RateLimited, &msg);
+                    return;
+                }
+            }
+        }
+        if let Some((in_flight_requests, in_transport)) = &self.request_timeouts {
+            in_flight_requests.track(self.rule_id, &msg, in_transport.clone());
+        }
+        // a response answers its request once the forwarder sent it
+        let in_flight_requests = self
+            .request_timeouts
+            .as_ref()
+            .filter(|_| msg.is_response())
+            .map(|(in_flight_requests, _)| in_flight_requests.clone());
+        // remembered before pushing so that a loop can't outrun it, forgotten again if the message
+        // doesn't make it into the queue, so that its retransmission isn't taken for a loop
+        let remembered_id = self.loop_detection.as_ref().and_then(|(_, recently_seen)| {
+            msg.attributes
+                .id
+                .as_ref()
+                .filter(|id| !recently_seen.check_and_insert_id(id))
+                .cloned()
+        });
+        // the queue applies its OverflowPolicy and accounts for messages it has to drop
+        let queued = self
+            .queue
+            .push(QueuedMessage {
+                message: Arc::new(msg),
+                rule_id: self.rule_id,
+                rule_stats: self.stats.clone(),
+                error_responder: self.error_responder.clone(),
+                in_flight_requests,
+                span: Span::current(),
             })
-            .unwrap(),
-        ))
+            .await;
+        if let (false, Some((_, recently_seen)), Some(id)) =
+            (queued, &self.loop_detection, remembered_id)
+        {
+            recently_seen.remove_id(&id);
+        }
+    }
+}
+
+#[async_trait]
+impl UListener for ForwardingListener {
+    async fn on_receive(&self, msg: UMessage) {
+        debug!(
+            "{}:{}:{} Received message: {:?}",
+            self.forwarding_id,
+            FORWARDING_LISTENER_TAG,
+            FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+            &msg
+        );
+        self.stats.record_received();
+
+        match self.pause.intercept(msg) {
+            Paused::No(msg) => self.forward(msg).await,
+            Paused::Buffered => {
+                self.stats.record_paused();
+                debug!(
+                    "{}:{}:{} Buffering message while the forwarding rule is paused",
+                    self.forwarding_id,
+                    FORWARDING_LISTENER_TAG,
+                    FORWARDING_LISTENER_FN_ON_RECEIVE_TAG
+                );
+            }
+            Paused::Dropped(msg) => {
+                self.stats.record_paused();
+                self.stats.record_dropped();
+                debug!(
+                    "{}:{}:{} Dropping message while the forwarding rule is paused. UAttributes: {:?}",
+                    self.forwarding_id,
+                    FORWARDING_LISTENER_TAG,
+                    FORWARDING_LISTENER_FN_ON_RECEIVE_TAG,
+                    &msg.attributes
+                );
+                self.discard(DeadLetterReason::Paused, &msg);
+            }
+        }
+    }
+}
+
+// the ForwardingListeners of the forwarding rules from the same in UTransport and in authority
+// to the same out authority, registered on the in UTransport as a single listener which hands
+// every message to each of them, so that every rule applies its own options, pause and queue
+pub(crate) struct ForwardingListenerGroup {
+    forwarding_listeners: StdMutex<Vec<Arc<ForwardingListener>>>,
+}
+
+impl ForwardingListenerGroup {
+    fn new(forwarding_listener: Arc<ForwardingListener>) -> Self {
+        Self {
+            forwarding_listeners: StdMutex::new(vec![forwarding_listener]),
+        }
+    }
+
+    fn forwarding_listeners(&self) -> Vec<Arc<ForwardingListener>> {
+        self.forwarding_listeners.lock().unwrap().clone()
+    }
+
+    fn add(&self, forwarding_listener: Arc<ForwardingListener>) {
+        let mut forwarding_listeners = self.forwarding_listeners.lock().unwrap();
+        // the listeners registered for the group so far are registered for the new rule as well
+        if let Some(first) = forwarding_listeners.first() {
+            forwarding_listener
+                .stats
+                .set_registered_listeners(first.stats.registered_listeners());
+        }
+        forwarding_listeners.push(forwarding_listener);
+    }
+
+    fn remove(&self, rule_id: RuleId) -> Option<Arc<ForwardingListener>> {
+        let mut forwarding_listeners = self.forwarding_listeners.lock().unwrap();
+        let position = forwarding_listeners
+            .iter()
+            .position(|forwarding_listener| forwarding_listener.rule_id == rule_id)?;
+        Some(forwarding_listeners.remove(position))
+    }
+
+    fn get(&self, rule_id: RuleId) -> Option<Arc<ForwardingListener>> {
+        self.forwarding_listeners
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|forwarding_listener| forwarding_listener.rule_id == rule_id)
+            .cloned()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.forwarding_listeners.lock().unwrap().is_empty()
+    }
+
+    fn set_registered_listeners(&self, registered_listeners: u64) {
+        for forwarding_listener in self.forwarding_listeners() {
+            forwarding_listener
+                .stats
+                .set_registered_listeners(registered_listeners);
+        }
+    }
+
+    fn record_listener_registered(&self) {
+        for forwarding_listener in self.forwarding_listeners() {
+            forwarding_listener.stats.record_listener_registered();
+        }
+    }
+
+    fn record_listener_unregistered(&self) {
+        for forwarding_listener in self.forwarding_listeners() {
+            forwarding_listener.stats.record_listener_unregistered();
+        }
+    }
+
+    // the rules of the group share one StreamerObserver, so it is notified only once
+    fn notify_listener_registration(
+        &self,
+        source_filter: &UUri,
+        sink_filter: Option<&UUri>,
+        res: Result<(), &UStatus>,
+    ) {
+        if let Some(forwarding_listener) = self.forwarding_listeners().first() {
+            forwarding_listener.notify_listener_registration(source_filter, sink_filter, res);
+        }
+    }
+}
+
+#[async_trait]
+impl UListener for ForwardingListenerGroup {
+    async fn on_receive(&self, msg: UMessage) {
+        // concurrently, so that a rule holding a message back, e.g. to keep to its rate limit,
+        // doesn't hold up the other rules
+        join_all(
+            self.forwarding_listeners()
+                .iter()
+                .map(|forwarding_listener| forwarding_listener.on_receive(msg.clone())),
+        )
+        .await;
+    }
+}
+
+const SUBSCRIPTION_UPDATE_LISTENER_TAG: &str = "SubscriptionUpdateListener:";
+const SUBSCRIPTION_UPDATE_LISTENER_FN_ON_RECEIVE_TAG: &str = "on_receive():";
+
+pub(crate) struct SubscriptionUpdateListener {
+    name: String,
+    forwarding_listeners: Arc<ForwardingListeners>,
+    subscription_cache: Arc<Mutex<SubscriptionCache>>,
+}
+
+#[async_trait]
+impl UListener for SubscriptionUpdateListener {
+    async fn on_receive(&self, msg: UMessage) {
+        let update = match msg.extract_protobuf::<Update>() {
+            Ok(update) => update,
+            Err(err) => {
+                warn!(
+                    "{}:{}:{} Unable to parse subscription update: {err:?}",
+                    self.name,
+                    SUBSCRIPTION_UPDATE_LISTENER_TAG,
+                    SUBSCRIPTION_UPDATE_LISTENER_FN_ON_RECEIVE_TAG
+                );
+                return;
+            }
+        };
+
+        if let Err(err) = self
+            .forwarding_listeners
+            .apply_subscription_update(update, self.subscription_cache.clone())
+            .await
+        {
+            warn!(
+                "{}:{}:{} Unable to apply subscription update: {err:?}",
+                self.name,
+                SUBSCRIPTION_UPDATE_LISTENER_TAG,
+                SUBSCRIPTION_UPDATE_LISTENER_FN_ON_RECEIVE_TAG
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dead_letter::{DeadLetterReason, DeadLetters, RingBufferDeadLetterSink};
+    use crate::observer::SharedObserver;
+    use crate::queue::{ForwardingQueue, QueueConfig};
+    use crate::rate_limit::{RateLimit, RateLimitAction};
+    use crate::retry::RetryPolicy;
+    use crate::ustreamer::{
+        uauthority_to_uuri, ForwardingListener, ForwardingListeners, TransportForwarders,
+    };
+    use crate::{
+        AccessPolicy, AccessRule, Deduplication, DropReason, Endpoint, ForwarderRuntime,
+        ForwardingCounts, ForwardingRuleDescriptor, ForwardingRuleOptions, LoopDetection,
+        MessageFilter, PauseAction, RequestTimeouts, RuleChange, RuleId, RuleState,
+        StreamerObserver, UStreamer, UStreamerStats,
+    };
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex as StdMutex};
+    use std::time::Duration;
+    use subscription_cache::SubscriptionCache;
+    use tokio::runtime::Handle;
+    use tokio::sync::Mutex as TokioMutex;
+    use up_rust::core::usubscription::{
+        FetchSubscribersRequest, FetchSubscribersResponse, FetchSubscriptionsRequest,
+        FetchSubscriptionsResponse, NotificationsRequest, ResetRequest, ResetResponse, State,
+        SubscriberInfo, Subscription, SubscriptionRequest, SubscriptionResponse,
+        SubscriptionStatus, USubscription, UnsubscribeRequest, Update,
+    };
+    use up_rust::{
+        UCode, UListener, UMessage, UMessageBuilder, UMessageType, UPayloadFormat, UPriority,
+        UStatus, UTransport, UUri,
+    };
+    use usubscription_static_file::USubscriptionStaticFile;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct ListenerRegistration {
+        source_filter: UUri,
+        sink_filter: Option<UUri>,
+    }
+
+    fn listener_registration(
+        source_filter: &UUri,
+        sink_filter: Option<&UUri>,
+    ) -> ListenerRegistration {
+        ListenerRegistration {
+            source_filter: source_filter.clone(),
+            sink_filter: sink_filter.cloned(),
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingTransport {
+        register_calls: StdMutex<Vec<ListenerRegistration>>,
+        unregister_calls: StdMutex<Vec<ListenerRegistration>>,
+        register_call_counts: StdMutex<HashMap<ListenerRegistration, usize>>,
+        unregister_call_counts: StdMutex<HashMap<ListenerRegistration, usize>>,
+        forced_register_failures: StdMutex<HashMap<ListenerRegistration, UStatus>>,
+        duplicate_register_failure: StdMutex<Option<UStatus>>,
+        registered_listeners: StdMutex<Vec<Arc<dyn UListener>>>,
+        sent_messages: StdMutex<Vec<UMessage>>,
+        forced_send_failure: StdMutex<Option<UStatus>>,
+        remaining_forced_send_failures: StdMutex<Option<usize>>,
+        send_delay: StdMutex<Option<Duration>>,
+    }
+
+    impl RecordingTransport {
+        fn register_calls(&self) -> Vec<ListenerRegistration> {
+            self.register_calls.lock().unwrap().clone()
+        }
+
+        fn unregister_calls(&self) -> Vec<ListenerRegistration> {
+            self.unregister_calls.lock().unwrap().clone()
+        }
+
+        fn register_call_count(&self, source_filter: &UUri, sink_filter: Option<&UUri>) -> usize {
+            self.register_call_counts
+                .lock()
+                .unwrap()
+                .get(&listener_registration(source_filter, sink_filter))
+                .copied()
+                .unwrap_or(0)
+        }
+
+        fn unregister_call_count(&self, source_filter: &UUri, sink_filter: Option<&UUri>) -> usize {
+            self.unregister_call_counts
+                .lock()
+                .unwrap()
+                .get(&listener_registration(source_filter, sink_filter))
+                .copied()
+                .unwrap_or(0)
+        }
+
+        fn set_register_failure(
+            &self,
+            source_filter: &UUri,
+            sink_filter: Option<&UUri>,
+            status: UStatus,
+        ) {
+            self.forced_register_failures
+                .lock()
+                .unwrap()
+                .insert(listener_registration(source_filter, sink_filter), status);
+        }
+
+        fn clear_register_failure(&self, source_filter: &UUri, sink_filter: Option<&UUri>) {
+            self.forced_register_failures
+                .lock()
+                .unwrap()
+                .remove(&listener_registration(source_filter, sink_filter));
+        }
+
+        fn fail_duplicate_registers_with(&self, status: UStatus) {
+            *self.duplicate_register_failure.lock().unwrap() = Some(status);
+        }
+
+        fn registered_listener(&self) -> Arc<dyn UListener> {
+            self.registered_listeners.lock().unwrap()[0].clone()
+        }
+
+        fn sent_messages(&self) -> Vec<UMessage> {
+            self.sent_messages.lock().unwrap().clone()
+        }
+
+        fn fail_sends_with(&self, status: Option<UStatus>) {
+            *self.forced_send_failure.lock().unwrap() = status;
+        }
+
+        fn fail_next_sends_with(&self, count: usize, status: UStatus) {
+            *self.remaining_forced_send_failures.lock().unwrap() = Some(count);
+            *self.forced_send_failure.lock().unwrap() = Some(status);
+        }
+
+        fn delay_sends_by(&self, delay: Duration) {
+            *self.send_delay.lock().unwrap() = Some(delay);
+        }
+    }
+
+    #[async_trait]
+    impl UTransport for RecordingTransport {
+        async fn send(&self, message: UMessage) -> Result<(), UStatus> {
+            let send_delay = *self.send_delay.lock().unwrap();
+            if let Some(delay) = send_delay {
+                tokio::time::sleep(delay).await;
+            }
+            if let Some(status) = self.forced_send_failure.lock().unwrap().clone() {
+                let mut remaining = self.remaining_forced_send_failures.lock().unwrap();
+                match remaining.as_mut() {
+                    Some(0) => {}
+                    Some(count) => {
+                        *count -= 1;
+                        return Err(status);
+                    }
+                    None => return Err(status),
+                }
+            }
+            self.sent_messages.lock().unwrap().push(message);
+            Ok(())
+        }
+
+        async fn receive(
+            &self,
+            _source_filter: &UUri,
+            _sink_filter: Option<&UUri>,
+        ) -> Result<UMessage, UStatus> {
+            Err(UStatus::fail_with_code(
+                UCode::UNIMPLEMENTED,
+                "not implemented",
+            ))
+        }
+
+        async fn register_listener(
+            &self,
+            source_filter: &UUri,
+            sink_filter: Option<&UUri>,
+            listener: Arc<dyn UListener>,
+        ) -> Result<(), UStatus> {
+            let registration = listener_registration(source_filter, sink_filter);
+
+            self.registered_listeners.lock().unwrap().push(listener);
+            self.register_calls
+                .lock()
+                .unwrap()
+                .push(registration.clone());
+
+            let register_count = {
+                let mut counts = self.register_call_counts.lock().unwrap();
+                let entry = counts.entry(registration.clone()).or_insert(0);
+                *entry += 1;
+                *entry
+            };
+
+            if let Some(status) = self
+                .forced_register_failures
+                .lock()
+                .unwrap()
+                .get(&registration)
+                .cloned()
+            {
+                return Err(status);
+            }
+
+            if register_count > 1 {
+                if let Some(status) = self.duplicate_register_failure.lock().unwrap().clone() {
+                    return Err(status);
+                }
+            }
+
+            Ok(())
+        }
+
+        async fn unregister_listener(
+            &self,
+            source_filter: &UUri,
+            sink_filter: Option<&UUri>,
+            _listener: Arc<dyn UListener>,
+        ) -> Result<(), UStatus> {
+            let registration = listener_registration(source_filter, sink_filter);
+
+            self.unregister_calls
+                .lock()
+                .unwrap()
+                .push(registration.clone());
+
+            let mut counts = self.unregister_call_counts.lock().unwrap();
+            let entry = counts.entry(registration).or_insert(0);
+            *entry += 1;
+
+            Ok(())
+        }
+    }
+
+    fn test_queue() -> Arc<ForwardingQueue> {
+        Arc::new(ForwardingQueue::new(
+            16,
+            QueueConfig::default(),
+            Default::default(),
+            Default::default(),
+        ))
+    }
+
+    fn make_test_streamer(entries: &[(&str, &str)]) -> UStreamer {
+        let observer = Arc::new(SharedObserver::default());
+        let dead_letters = Arc::new(DeadLetters::new(observer.clone()));
+        UStreamer {
+            name: "test-streamer".to_string(),
+            registered_forwarding_rules: TokioMutex::new(HashMap::new()),
+            next_rule_id: 0,
+            loop_detection: None,
+            in_flight_requests: None,
+            error_responses: false,
+            transport_forwarders: TransportForwarders::new(
+                16,
+                dead_letters.clone(),
+                ForwarderRuntime::Tasks(Handle::current()),
+            ),
+            forwarding_listeners: Arc::new(ForwardingListeners::new()),
+            subscription_cache: make_subscription_cache(entries),
+            dead_letters,
+            observer,
+            access_control: Default::default(),
+            subscription_notifications: None,
+        }
+    }
+
+    fn make_subscription_cache(entries: &[(&str, &str)]) -> Arc<TokioMutex<SubscriptionCache>> {
+        let subscriptions = entries
+            .iter()
+            .map(|(topic, subscriber)| {
+                let topic_uri = UUri::from_str(topic).unwrap();
+                let subscriber_uri = UUri::from_str(subscriber).unwrap();
+                let subscriber_info = SubscriberInfo {
+                    uri: Some(subscriber_uri).into(),
+                    ..Default::default()
+                };
+
+                Subscription {
+                    topic: Some(topic_uri).into(),
+                    subscriber: Some(subscriber_info).into(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        Arc::new(TokioMutex::new(
+            SubscriptionCache::new(FetchSubscriptionsResponse {
+                subscriptions,
+                ..Default::default()
+            })
+            .unwrap(),
+        ))
+    }
+
+    fn subscription_update(topic: &str, subscriber: &str, state: State) -> Update {
+        Update {
+            topic: Some(UUri::from_str(topic).unwrap()).into(),
+            subscriber: Some(SubscriberInfo {
+                uri: Some(UUri::from_str(subscriber).unwrap()).into(),
+                ..Default::default()
+            })
+            .into(),
+            status: Some(SubscriptionStatus {
+                state: state.into(),
+                ..Default::default()
+            })
+            .into(),
+            ..Default::default()
+        }
+    }
+
+    async fn wait_for_stats(
+        streamer: &UStreamer,
+        predicate: impl Fn(&UStreamerStats) -> bool,
+    ) -> UStreamerStats {
+        for _ in 0..200 {
+            let stats = streamer.stats().await;
+            if predicate(&stats) {
+                return stats;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("stats never matched: {:?}", streamer.stats().await);
+    }
+
+    fn has_listener_call(
+        calls: &[ListenerRegistration],
+        source_filter: &UUri,
+        sink_filter: Option<&UUri>,
+    ) -> bool {
+        calls.iter().any(|call| {
+            call.source_filter == *source_filter && call.sink_filter.as_ref() == sink_filter
+        })
+    }
+
+    pub struct UPClientFoo;
+
+    #[async_trait]
+    impl UTransport for UPClientFoo {
+        async fn send(&self, _message: UMessage) -> Result<(), UStatus> {
+            todo!()
+        }
+
+        async fn receive(
+            &self,
+            _source_filter: &UUri,
+            _sink_filter: Option<&UUri>,
+        ) -> Result<UMessage, UStatus> {
+            todo!()
+        }
+
+        async fn register_listener(
+            &self,
+            source_filter: &UUri,
+            _sink_filter: Option<&UUri>,
+            _listener: Arc<dyn UListener>,
+        ) -> Result<(), UStatus> {
+            println!(
+                "UPClientFoo: registering source_filter: {:?}",
+                source_filter
+            );
+            Ok(())
+        }
+
+        async fn unregister_listener(
+            &self,
+            source_filter: &UUri,
+            _sink_filter: Option<&UUri>,
+            _listener: Arc<dyn UListener>,
+        ) -> Result<(), UStatus> {
+            println!(
+                "UPClientFoo: unregistering source_filter: {:?}",
+                source_filter
+            );
+            Ok(())
+        }
+    }
+
+    pub struct UPClientBar;
+
+    #[async_trait]
+    impl UTransport for UPClientBar {
+        async fn send(&self, _message: UMessage) -> Result<(), UStatus> {
+            todo!()
+        }
+
+        async fn receive(
+            &self,
+            _source_filter: &UUri,
+            _sink_filter: Option<&UUri>,
+        ) -> Result<UMessage, UStatus> {
+            todo!()
+        }
+
+        async fn register_listener(
+            &self,
+            source_filter: &UUri,
+            _sink_filter: Option<&UUri>,
+            _listener: Arc<dyn UListener>,
+        ) -> Result<(), UStatus> {
+            println!(
+                "UPClientBar: registering source_filter: {:?}",
+                source_filter
+            );
+            Ok(())
+        }
+
+        async fn unregister_listener(
+            &self,
+            source_filter: &UUri,
+            _sink_filter: Option<&UUri>,
+            _listener: Arc<dyn UListener>,
+        ) -> Result<(), UStatus> {
+            println!(
+                "UPClientBar: unregistering source_filter: {:?}",
+                source_filter
+            );
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_simple_with_a_single_input_and_output_endpoint() {
+        // Local endpoint
+        let local_authority = "local";
+        let local_transport: Arc<dyn UTransport> = Arc::new(UPClientFoo);
+        let local_endpoint =
+            Endpoint::new("local_endpoint", local_authority, local_transport.clone());
+
+        // A remote endpoint
+        let remote_authority = "remote";
+        let remote_transport: Arc<dyn UTransport> = Arc::new(UPClientBar);
+        let remote_endpoint = Endpoint::new(
+            "remote_endpoint",
+            remote_authority,
+            remote_transport.clone(),
+        );
+
+        let subscription_path =
+            "../utils/usubscription-static-file/static-configs/testdata.json".to_string();
+        let usubscription = Arc::new(USubscriptionStaticFile::new(subscription_path));
+        let mut ustreamer = match UStreamer::new("foo_bar_streamer", 100, usubscription) {
+            Ok(streamer) => streamer,
+            Err(error) => panic!("Failed to create uStreamer: {}", error),
+        };
+        // Add forwarding rules to endpoint local<->remote
+        assert!(ustreamer
+            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint.clone())
+            .await
+            .is_ok());
+        assert!(ustreamer
+            .add_forwarding_rule(remote_endpoint.clone(), local_endpoint.clone())
+            .await
+            .is_ok());
+
+        // Add forwarding rules to endpoint local<->local, should report an error
+        assert!(ustreamer
+            .add_forwarding_rule(local_endpoint.clone(), local_endpoint.clone())
+            .await
+            .is_err());
+
+        // Rule already exists so it should report an error
+        assert!(ustreamer
+            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint.clone())
+            .await
+            .is_err());
+
+        // Add forwarding rules to endpoint remote<->remote, should report an error
+        assert!(ustreamer
+            .add_forwarding_rule(remote_endpoint.clone(), remote_endpoint.clone())
+            .await
+            .is_err());
+
+        // remove valid routing rules
+        assert!(ustreamer
+            .delete_forwarding_rule(local_endpoint.clone(), remote_endpoint.clone())
+            .await
+            .is_ok());
+        assert!(ustreamer
+            .delete_forwarding_rule(remote_endpoint.clone(), local_endpoint.clone())
+            .await
+            .is_ok());
+
+        // Try and remove a rule that doesn't exist, should report an error
+        assert!(ustreamer
+            .delete_forwarding_rule(local_endpoint.clone(), remote_endpoint.clone())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_advanced_where_there_is_a_local_endpoint_and_two_remote_endpoints() {
+        // Local endpoint
+        let local_authority = "local";
+        let local_transport: Arc<dyn UTransport> = Arc::new(UPClientFoo);
+        let local_endpoint =
+            Endpoint::new("local_endpoint", local_authority, local_transport.clone());
+
+        // Remote endpoint - A
+        let remote_authority_a = "remote_a";
+        let remote_transport_a: Arc<dyn UTransport> = Arc::new(UPClientBar);
+        let remote_endpoint_a = Endpoint::new(
+            "remote_endpoint_a",
+            remote_authority_a,
+            remote_transport_a.clone(),
+        );
+
+        // Remote endpoint - B
+        let remote_authority_b = "remote_b";
+        let remote_transport_b: Arc<dyn UTransport> = Arc::new(UPClientBar);
+        let remote_endpoint_b = Endpoint::new(
+            "remote_endpoint_b",
+            remote_authority_b,
+            remote_transport_b.clone(),
+        );
+
+        let subscription_path =
+            "../utils/usubscription-static-file/static-configs/testdata.json".to_string();
+        let usubscription = Arc::new(USubscriptionStaticFile::new(subscription_path));
+        let mut ustreamer = match UStreamer::new("foo_bar_streamer", 100, usubscription) {
+            Ok(streamer) => streamer,
+            Err(error) => panic!("Failed to create uStreamer: {}", error),
+        };
+
+        // Add forwarding rules to endpoint local<->remote_a
+        assert!(ustreamer
+            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint_a.clone())
+            .await
+            .is_ok());
+        assert!(ustreamer
+            .add_forwarding_rule(remote_endpoint_a.clone(), local_endpoint.clone())
+            .await
+            .is_ok());
+
+        // Add forwarding rules to endpoint local<->remote_b
+        assert!(ustreamer
+            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint_b.clone())
+            .await
+            .is_ok());
+        assert!(ustreamer
+            .add_forwarding_rule(remote_endpoint_b.clone(), local_endpoint.clone())
+            .await
+            .is_ok());
+
+        // Add forwarding rules to endpoint remote_a<->remote_b
+        assert!(ustreamer
+            .add_forwarding_rule(remote_endpoint_a.clone(), remote_endpoint_b.clone())
+            .await
+            .is_ok());
+        assert!(ustreamer
+            .add_forwarding_rule(remote_endpoint_b.clone(), remote_endpoint_a.clone())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_advanced_where_there_is_a_local_endpoint_and_two_remote_endpoints_but_the_remote_endpoints_have_the_same_instance_of_utransport(
+    ) {
+        // Local endpoint
+        let local_authority = "local";
+        let local_transport: Arc<dyn UTransport> = Arc::new(UPClientFoo);
+        let local_endpoint =
+            Endpoint::new("local_endpoint", local_authority, local_transport.clone());
+
+        let remote_transport: Arc<dyn UTransport> = Arc::new(UPClientBar);
+
+        // Remote endpoint - A
+        let remote_authority_a = "remote_a";
+        let remote_endpoint_a = Endpoint::new(
+            "remote_endpoint_a",
+            remote_authority_a,
+            remote_transport.clone(),
+        );
+
+        // Remote endpoint - B
+        let remote_authority_b = "remote_b";
+        let remote_endpoint_b = Endpoint::new(
+            "remote_endpoint_b",
+            remote_authority_b,
+            remote_transport.clone(),
+        );
+
+        let subscription_path =
+            "../utils/usubscription-static-file/static-configs/testdata.json".to_string();
+        let usubscription = Arc::new(USubscriptionStaticFile::new(subscription_path));
+        let mut ustreamer = match UStreamer::new("foo_bar_streamer", 100, usubscription) {
+            Ok(streamer) => streamer,
+            Err(error) => panic!("Failed to create uStreamer: {}", error),
+        };
+
+        // Add forwarding rules to endpoint local<->remote_a
+        assert!(ustreamer
+            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint_a.clone())
+            .await
+            .is_ok());
+        assert!(ustreamer
+            .add_forwarding_rule(remote_endpoint_a.clone(), local_endpoint.clone())
+            .await
+            .is_ok());
+
+        // Add forwarding rules to endpoint local<->remote_b
+        assert!(ustreamer
+            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint_b.clone())
+            .await
+            .is_ok());
+        assert!(ustreamer
+            .add_forwarding_rule(remote_endpoint_b.clone(), local_endpoint.clone())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn publish_filter_respects_topic_authority() {
+        let recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
+        let forwarding_listeners = ForwardingListeners::new();
+        let out_queue = test_queue();
+        let subscription_cache =
+            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+
+        let insert_result = forwarding_listeners
+            .insert(
+                in_transport,
+                "authority-a",
+                "authority-b",
+                ForwardingListener::new(
+                    RuleId(0),
+                    "test-forwarding",
+                    out_queue,
+                    Default::default(),
+                ),
+                subscription_cache,
+            )
+            .await;
+
+        assert!(insert_result.is_ok());
+
+        let register_calls = recording_transport.register_calls();
+        let request_source = uauthority_to_uuri("authority-a");
+        let request_sink = uauthority_to_uuri("authority-b");
+        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
+
+        assert!(has_listener_call(
+            &register_calls,
+            &request_source,
+            Some(&request_sink)
+        ));
+        assert!(has_listener_call(&register_calls, &publish_source, None));
+        assert_eq!(
+            register_calls
+                .iter()
+                .filter(|call| call.sink_filter.is_none())
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn publish_filter_allows_left_wildcard() {
+        let recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
+        let forwarding_listeners = ForwardingListeners::new();
+        let out_queue = test_queue();
+        let subscription_cache =
+            make_subscription_cache(&[("//*/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+
+        let insert_result = forwarding_listeners
+            .insert(
+                in_transport,
+                "authority-c",
+                "authority-b",
+                ForwardingListener::new(
+                    RuleId(0),
+                    "test-forwarding",
+                    out_queue,
+                    Default::default(),
+                ),
+                subscription_cache,
+            )
+            .await;
+
+        assert!(insert_result.is_ok());
+
+        let register_calls = recording_transport.register_calls();
+        let publish_source = UUri::try_from_parts("authority-c", 0x5BA0, 0x1, 0x8001).unwrap();
+
+        assert!(has_listener_call(&register_calls, &publish_source, None));
+        assert_eq!(
+            register_calls
+                .iter()
+                .filter(|call| call.sink_filter.is_none())
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn publish_filter_blocks_unmapped_authority() {
+        let recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
+        let forwarding_listeners = ForwardingListeners::new();
+        let out_queue = test_queue();
+        let subscription_cache =
+            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+
+        let insert_result = forwarding_listeners
+            .insert(
+                in_transport,
+                "authority-c",
+                "authority-b",
+                ForwardingListener::new(
+                    RuleId(0),
+                    "test-forwarding",
+                    out_queue,
+                    Default::default(),
+                ),
+                subscription_cache,
+            )
+            .await;
+
+        assert!(insert_result.is_ok());
+
+        let register_calls = recording_transport.register_calls();
+        let request_source = uauthority_to_uuri("authority-c");
+        let request_sink = uauthority_to_uuri("authority-b");
+        let disallowed_publish_source =
+            UUri::try_from_parts("authority-c", 0x5BA0, 0x1, 0x8001).unwrap();
+
+        assert!(has_listener_call(
+            &register_calls,
+            &request_source,
+            Some(&request_sink)
+        ));
+        assert!(!has_listener_call(
+            &register_calls,
+            &disallowed_publish_source,
+            None
+        ));
+        assert_eq!(
+            register_calls
+                .iter()
+                .filter(|call| call.sink_filter.is_none())
+                .count(),
+            0
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn right_side_wildcard_forwards_to_registered_authorities() {
+        let recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
+        let forwarding_listeners = ForwardingListeners::new();
+        let out_queue = test_queue();
+        let subscription_cache =
+            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//*/5678/1/1234")]);
+
+        assert!(forwarding_listeners
+            .insert(
+                in_transport.clone(),
+                "authority-a",
+                "authority-b",
+                ForwardingListener::new(
+                    RuleId(0),
+                    "test-forwarding-b",
+                    out_queue.clone(),
+                    Default::default()
+                ),
+                subscription_cache.clone(),
+            )
+            .await
+            .is_ok());
+        assert!(forwarding_listeners
+            .insert(
+                in_transport,
+                "authority-a",
+                "authority-d",
+                ForwardingListener::new(
+                    RuleId(1),
+                    "test-forwarding-d",
+                    out_queue,
+                    Default::default()
+                ),
+                subscription_cache,
+            )
+            .await
+            .is_ok());
+
+        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
+        let request_source = uauthority_to_uuri("authority-a");
+        let authority_b = uauthority_to_uuri("authority-b");
+        let authority_d = uauthority_to_uuri("authority-d");
+
+        assert_eq!(
+            recording_transport.register_call_count(&publish_source, None),
+            2
+        );
+        assert_eq!(
+            recording_transport.register_call_count(&request_source, Some(&authority_b)),
+            1
+        );
+        assert_eq!(
+            recording_transport.register_call_count(&request_source, Some(&authority_d)),
+            1
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn right_side_wildcard_does_not_forward_without_rule() {
+        let recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
+        let forwarding_listeners = ForwardingListeners::new();
+        let out_queue = test_queue();
+        let subscription_cache =
+            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//*/5678/1/1234")]);
+
+        assert!(forwarding_listeners
+            .insert(
+                in_transport.clone(),
+                "authority-a",
+                "authority-b",
+                ForwardingListener::new(
+                    RuleId(0),
+                    "test-forwarding-b",
+                    out_queue.clone(),
+                    Default::default()
+                ),
+                subscription_cache.clone(),
+            )
+            .await
+            .is_ok());
+        assert!(forwarding_listeners
+            .insert(
+                in_transport,
+                "authority-a",
+                "authority-d",
+                ForwardingListener::new(
+                    RuleId(1),
+                    "test-forwarding-d",
+                    out_queue,
+                    Default::default()
+                ),
+                subscription_cache,
+            )
+            .await
+            .is_ok());
+
+        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
+        let request_source = uauthority_to_uuri("authority-a");
+        let authority_e = uauthority_to_uuri("authority-e");
+
+        assert_eq!(
+            recording_transport.register_call_count(&publish_source, None),
+            2
+        );
+        assert_eq!(
+            recording_transport.register_call_count(&request_source, Some(&authority_e)),
+            0
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn publish_filter_blocks_mismatched_tuple() {
+        let recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
+        let forwarding_listeners = ForwardingListeners::new();
+        let out_queue = test_queue();
+        let subscription_cache =
+            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+
+        assert!(forwarding_listeners
+            .insert(
+                in_transport,
+                "authority-a",
+                "authority-b",
+                ForwardingListener::new(
+                    RuleId(0),
+                    "test-forwarding",
+                    out_queue,
+                    Default::default()
+                ),
+                subscription_cache,
+            )
+            .await
+            .is_ok());
+
+        let exact = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
+        let mismatched_ue_id = UUri::try_from_parts("authority-a", 0x5BA1, 0x1, 0x8001).unwrap();
+        let mismatched_version = UUri::try_from_parts("authority-a", 0x5BA0, 0x2, 0x8001).unwrap();
+        let mismatched_resource = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8002).unwrap();
+
+        assert_eq!(recording_transport.register_call_count(&exact, None), 1);
+        assert_eq!(
+            recording_transport.register_call_count(&mismatched_ue_id, None),
+            0
+        );
+        assert_eq!(
+            recording_transport.register_call_count(&mismatched_version, None),
+            0
+        );
+        assert_eq!(
+            recording_transport.register_call_count(&mismatched_resource, None),
+            0
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn multi_map_allows_both_sources() {
+        let recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
+        let forwarding_listeners = ForwardingListeners::new();
+        let out_queue = test_queue();
+        let subscription_cache = make_subscription_cache(&[
+            ("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234"),
+            ("//authority-c/5BA0/1/8001", "//authority-b/5679/1/1234"),
+        ]);
+
+        assert!(forwarding_listeners
+            .insert(
+                in_transport.clone(),
+                "authority-a",
+                "authority-b",
+                ForwardingListener::new(
+                    RuleId(0),
+                    "test-forwarding-a",
+                    out_queue.clone(),
+                    Default::default()
+                ),
+                subscription_cache.clone(),
+            )
+            .await
+            .is_ok());
+        assert!(forwarding_listeners
+            .insert(
+                in_transport,
+                "authority-c",
+                "authority-b",
+                ForwardingListener::new(
+                    RuleId(1),
+                    "test-forwarding-c",
+                    out_queue,
+                    Default::default()
+                ),
+                subscription_cache,
+            )
+            .await
+            .is_ok());
+
+        let source_a = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
+        let source_c = UUri::try_from_parts("authority-c", 0x5BA0, 0x1, 0x8001).unwrap();
+
+        assert_eq!(recording_transport.register_call_count(&source_a, None), 1);
+        assert_eq!(recording_transport.register_call_count(&source_c, None), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn publish_filter_dedupes_overlapping_rows() {
+        let recording_transport = Arc::new(RecordingTransport::default());
+        recording_transport.fail_duplicate_registers_with(UStatus::fail_with_code(
+            UCode::ALREADY_EXISTS,
+            "duplicate publish listener registration",
+        ));
+
+        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
+        let forwarding_listeners = ForwardingListeners::new();
+        let out_queue = test_queue();
+        let subscription_cache = make_subscription_cache(&[
+            ("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234"),
+            ("//authority-a/5BA0/1/8001", "//authority-b/5679/1/1234"),
+        ]);
+
+        assert!(forwarding_listeners
+            .insert(
+                in_transport.clone(),
+                "authority-a",
+                "authority-b",
+                ForwardingListener::new(
+                    RuleId(0),
+                    "test-forwarding",
+                    out_queue,
+                    Default::default()
+                ),
+                subscription_cache.clone(),
+            )
+            .await
+            .is_ok());
+
+        forwarding_listeners
+            .remove(
+                in_transport,
+                "authority-a",
+                "authority-b",
+                RuleId(0),
+                subscription_cache,
+            )
+            .await;
+
+        let request_source = uauthority_to_uuri("authority-a");
+        let request_sink = uauthority_to_uuri("authority-b");
+        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
+
+        assert_eq!(
+            recording_transport.register_call_count(&request_source, Some(&request_sink)),
+            1
+        );
+        assert_eq!(
+            recording_transport.unregister_call_count(&request_source, Some(&request_sink)),
+            1
+        );
+        assert_eq!(
+            recording_transport.register_call_count(&publish_source, None),
+            1
+        );
+        assert_eq!(
+            recording_transport.unregister_call_count(&publish_source, None),
+            1
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn add_forwarding_rule_rolls_back_on_listener_insert_failure() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport;
+
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport.clone());
+        let out_endpoint = Endpoint::new("out-endpoint", "authority-b", out_transport.clone());
+
+        let mut streamer =
+            make_test_streamer(&[("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+
+        let request_source = uauthority_to_uuri("authority-a");
+        let request_sink = uauthority_to_uuri("authority-b");
+
+        in_recording_transport.set_register_failure(
+            &request_source,
+            Some(&request_sink),
+            UStatus::fail_with_code(UCode::INTERNAL, "forced listener insertion failure"),
+        );
+
+        assert!(streamer
+            .add_forwarding_rule(in_endpoint.clone(), out_endpoint.clone())
+            .await
+            .is_err());
+        assert_eq!(streamer.registered_forwarding_rules.lock().await.len(), 0);
+        assert_eq!(
+            streamer.transport_forwarders.forwarders.lock().await.len(),
+            0
+        );
+
+        in_recording_transport.clear_register_failure(&request_source, Some(&request_sink));
+
+        assert!(streamer
+            .add_forwarding_rule(in_endpoint.clone(), out_endpoint.clone())
+            .await
+            .is_ok());
+        assert_eq!(streamer.registered_forwarding_rules.lock().await.len(), 1);
+        assert_eq!(
+            streamer.transport_forwarders.forwarders.lock().await.len(),
+            1
+        );
+
+        assert!(streamer
+            .delete_forwarding_rule(in_endpoint, out_endpoint)
+            .await
+            .is_ok());
+        assert_eq!(streamer.registered_forwarding_rules.lock().await.len(), 0);
+        assert_eq!(
+            streamer.transport_forwarders.forwarders.lock().await.len(),
+            0
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn apply_rules_rolls_back_all_changes_on_failure() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = Arc::new(RecordingTransport::default());
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+        let out_endpoint = |name, authority| Endpoint::new(name, authority, out_transport.clone());
+        let add = |out| RuleChange::Add {
+            r#in: in_endpoint.clone(),
+            out,
+            options: ForwardingRuleOptions::default(),
+        };
+
+        let mut streamer = make_test_streamer(&[]);
+        let rule_b = streamer
+            .add_forwarding_rule(in_endpoint.clone(), out_endpoint("out-b", "authority-b"))
+            .await
+            .unwrap();
+        let rules_before = streamer.rules().await;
+
+        in_recording_transport.set_register_failure(
+            &uauthority_to_uuri("authority-a"),
+            Some(&uauthority_to_uuri("authority-d")),
+            UStatus::fail_with_code(UCode::INTERNAL, "forced listener insertion failure"),
+        );
+        let res = streamer
+            .apply_rules([
+                RuleChange::Delete(rule_b),
+                add(out_endpoint("out-c", "authority-c")),
+                add(out_endpoint("out-d", "authority-d")),
+            ])
+            .await;
+
+        assert_eq!(res.unwrap_err().get_code(), UCode::INVALID_ARGUMENT);
+        assert_eq!(streamer.rules().await, rules_before);
+        assert_eq!(
+            in_recording_transport.register_call_count(
+                &uauthority_to_uuri("authority-a"),
+                Some(&uauthority_to_uuri("authority-b"))
+            ),
+            2
+        );
+        assert_eq!(
+            in_recording_transport.unregister_call_count(
+                &uauthority_to_uuri("authority-a"),
+                Some(&uauthority_to_uuri("authority-c"))
+            ),
+            1
+        );
+        assert_eq!(
+            streamer.transport_forwarders.forwarders.lock().await.len(),
+            1
+        );
+
+        in_recording_transport.clear_register_failure(
+            &uauthority_to_uuri("authority-a"),
+            Some(&uauthority_to_uuri("authority-d")),
+        );
+        let added = streamer
+            .apply_rules([
+                RuleChange::Delete(rule_b),
+                add(out_endpoint("out-c", "authority-c")),
+                add(out_endpoint("out-d", "authority-d")),
+            ])
+            .await
+            .unwrap();
+
+        let rules = streamer.rules().await;
+        assert_eq!(rules.iter().map(|rule| rule.id).collect::<Vec<_>>(), added);
+        assert_eq!(
+            rules
+                .iter()
+                .map(|rule| rule.out_endpoint.as_str())
+                .collect::<Vec<_>>(),
+            vec!["out-c", "out-d"]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unregistration_removes_publish_filters() {
+        let recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
+        let forwarding_listeners = ForwardingListeners::new();
+        let out_queue = test_queue();
+        let subscription_cache =
+            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+
+        let insert_result = forwarding_listeners
+            .insert(
+                in_transport.clone(),
+                "authority-a",
+                "authority-b",
+                ForwardingListener::new(
+                    RuleId(0),
+                    "test-forwarding",
+                    out_queue,
+                    Default::default(),
+                ),
+                subscription_cache.clone(),
+            )
+            .await;
+
+        assert!(insert_result.is_ok());
+
+        forwarding_listeners
+            .remove(
+                in_transport,
+                "authority-a",
+                "authority-b",
+                RuleId(0),
+                subscription_cache,
+            )
+            .await;
+
+        let unregister_calls = recording_transport.unregister_calls();
+        let request_source = uauthority_to_uuri("authority-a");
+        let request_sink = uauthority_to_uuri("authority-b");
+        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
+
+        assert!(has_listener_call(
+            &unregister_calls,
+            &request_source,
+            Some(&request_sink)
+        ));
+        assert!(has_listener_call(&unregister_calls, &publish_source, None));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn subscription_update_registers_and_unregisters_publish_filters() {
+        let recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = Arc::new(RecordingTransport::default());
+
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+        let out_endpoint = Endpoint::new("out-endpoint", "authority-b", out_transport);
+
+        let mut streamer = make_test_streamer(&[]);
+        assert!(streamer
+            .add_forwarding_rule(in_endpoint, out_endpoint)
+            .await
+            .is_ok());
+
+        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
+        assert_eq!(
+            recording_transport.register_call_count(&publish_source, None),
+            0
+        );
+
+        assert!(streamer
+            .apply_subscription_update(subscription_update(
+                "//authority-a/5BA0/1/8001",
+                "//authority-b/5678/1/1234",
+                State::SUBSCRIBED,
+            ))
+            .await
+            .is_ok());
+        assert_eq!(
+            recording_transport.register_call_count(&publish_source, None),
+            1
+        );
+        assert_eq!(streamer.stats().await.rules[0].registered_listeners, 2);
+
+        // a second subscriber of the same topic doesn't need another publish listener
+        assert!(streamer
+            .apply_subscription_update(subscription_update(
+                "//authority-a/5BA0/1/8001",
+                "//authority-b/5679/1/1234",
+                State::SUBSCRIBED,
+            ))
+            .await
+            .is_ok());
+        assert_eq!(
+            recording_transport.register_call_count(&publish_source, None),
+            1
+        );
+
+        assert!(streamer
+            .apply_subscription_update(subscription_update(
+                "//authority-a/5BA0/1/8001",
+                "//authority-b/5678/1/1234",
+                State::UNSUBSCRIBED,
+            ))
+            .await
+            .is_ok());
+        assert_eq!(
+            recording_transport.unregister_call_count(&publish_source, None),
+            0
+        );
+
+        assert!(streamer
+            .apply_subscription_update(subscription_update(
+                "//authority-a/5BA0/1/8001",
+                "//authority-b/5679/1/1234",
+                State::UNSUBSCRIBED,
+            ))
+            .await
+            .is_ok());
+        assert_eq!(
+            recording_transport.unregister_call_count(&publish_source, None),
+            1
+        );
+        assert_eq!(streamer.stats().await.rules[0].registered_listeners, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn subscription_update_ignores_unrelated_authorities() {
+        let recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = Arc::new(RecordingTransport::default());
+
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+        let out_endpoint = Endpoint::new("out-endpoint", "authority-b", out_transport);
+
+        let mut streamer = make_test_streamer(&[]);
+        assert!(streamer
+            .add_forwarding_rule(in_endpoint, out_endpoint)
+            .await
+            .is_ok());
+        let registrations_after_rule = recording_transport.register_calls().len();
+
+        // subscriber lives behind an authority we don't forward to
+        assert!(streamer
+            .apply_subscription_update(subscription_update(
+                "//authority-a/5BA0/1/8001",
+                "//authority-c/5678/1/1234",
+                State::SUBSCRIBED,
+            ))
+            .await
+            .is_ok());
+        // topic is published by an authority we don't forward from
+        assert!(streamer
+            .apply_subscription_update(subscription_update(
+                "//authority-d/5BA0/1/8001",
+                "//authority-b/5678/1/1234",
+                State::SUBSCRIBED,
+            ))
+            .await
+            .is_ok());
+
+        assert_eq!(
+            recording_transport.register_calls().len(),
+            registrations_after_rule
+        );
+        assert!(streamer
+            .apply_subscription_update(Update::default())
+            .await
+            .is_err());
     }
 
-    fn has_listener_call(
-        calls: &[ListenerRegistration],
-        source_filter: &UUri,
-        sink_filter: Option<&UUri>,
-    ) -> bool {
-        calls.iter().any(|call| {
-            call.source_filter == *source_filter && call.sink_filter.as_ref() == sink_filter
-        })
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stats_count_forwarded_dropped_and_failed_messages() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+        let out_endpoint = Endpoint::new("out-endpoint", "authority-b", out_transport);
+
+        let mut streamer = make_test_streamer(&[]);
+        assert!(streamer
+            .add_forwarding_rule(in_endpoint, out_endpoint)
+            .await
+            .is_ok());
+
+        let stats = streamer.stats().await;
+        assert_eq!(stats.rules.len(), 1);
+        assert_eq!(stats.rules[0].in_endpoint, "in-endpoint");
+        assert_eq!(stats.rules[0].out_authority, "authority-b");
+        assert_eq!(stats.rules[0].registered_listeners, 1);
+        assert_eq!(stats.rules[0].counts, ForwardingCounts::default());
+        assert_eq!(stats.out_transports.len(), 1);
+        assert_eq!(stats.out_transports[0].out_endpoints, vec!["out-endpoint"]);
+
+        let listener = in_recording_transport.registered_listener();
+        listener.on_receive(UMessage::default()).await;
+        listener.on_receive(UMessage::default()).await;
+
+        let mut shm_message = UMessage::default();
+        shm_message
+            .attributes
+            .mut_or_insert_default()
+            .payload_format = UPayloadFormat::UPAYLOAD_FORMAT_SHM.into();
+        listener.on_receive(shm_message).await;
+
+        wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 2).await;
+
+        out_recording_transport.fail_sends_with(Some(UStatus::fail_with_code(
+            UCode::UNAVAILABLE,
+            "forced send failure",
+        )));
+        listener.on_receive(UMessage::default()).await;
+
+        let stats =
+            wait_for_stats(&streamer, |stats| stats.rules[0].counts.send_failures == 1).await;
+        assert_eq!(
+            stats.rules[0].counts,
+            ForwardingCounts {
+                received: 4,
+                forwarded: 2,
+                dropped: 1,
+                filtered: 0,
+                denied: 0,
+                expired: 0,
+                looped: 0,
+                duplicates: 0,
+                paused: 0,
+                throttled: 0,
+                send_failures: 1,
+                retries: 0,
+            }
+        );
+        assert_eq!(
+            stats.out_transports[0].counts,
+            ForwardingCounts {
+                received: 3,
+                forwarded: 2,
+                dropped: 0,
+                filtered: 0,
+                denied: 0,
+                expired: 0,
+                looped: 0,
+                duplicates: 0,
+                paused: 0,
+                throttled: 0,
+                send_failures: 1,
+                retries: 0,
+            }
+        );
+        assert_eq!(out_recording_transport.sent_messages().len(), 2);
     }
 
-    pub struct UPClientFoo;
+    // a uSubscription service without subscriptions, recording the notification registrations
+    #[derive(Default)]
+    struct RecordingUSubscription {
+        registered: StdMutex<Vec<NotificationsRequest>>,
+        unregistered: StdMutex<Vec<NotificationsRequest>>,
+    }
 
     #[async_trait]
-    impl UTransport for UPClientFoo {
-        async fn send(&self, _message: UMessage) -> Result<(), UStatus> {
+    impl USubscription for RecordingUSubscription {
+        async fn subscribe(
+            &self,
+            _subscription_request: SubscriptionRequest,
+        ) -> Result<SubscriptionResponse, UStatus> {
             todo!()
         }
 
-        async fn receive(
+        async fn unsubscribe(
             &self,
-            _source_filter: &UUri,
-            _sink_filter: Option<&UUri>,
-        ) -> Result<UMessage, UStatus> {
+            _unsubscribe_request: UnsubscribeRequest,
+        ) -> Result<(), UStatus> {
             todo!()
         }
 
-        async fn register_listener(
+        async fn fetch_subscriptions(
             &self,
-            source_filter: &UUri,
-            _sink_filter: Option<&UUri>,
-            _listener: Arc<dyn UListener>,
+            _fetch_subscriptions_request: FetchSubscriptionsRequest,
+        ) -> Result<FetchSubscriptionsResponse, UStatus> {
+            Ok(FetchSubscriptionsResponse::default())
+        }
+
+        async fn register_for_notifications(
+            &self,
+            notifications_register_request: NotificationsRequest,
         ) -> Result<(), UStatus> {
-            println!(
-                "UPClientFoo: registering source_filter: {:?}",
-                source_filter
-            );
+            self.registered
+                .lock()
+                .unwrap()
+                .push(notifications_register_request);
             Ok(())
         }
 
-        async fn unregister_listener(
+        async fn unregister_for_notifications(
             &self,
-            source_filter: &UUri,
-            _sink_filter: Option<&UUri>,
-            _listener: Arc<dyn UListener>,
+            notifications_unregister_request: NotificationsRequest,
         ) -> Result<(), UStatus> {
-            println!(
-                "UPClientFoo: unregistering source_filter: {:?}",
-                source_filter
-            );
+            self.unregistered
+                .lock()
+                .unwrap()
+                .push(notifications_unregister_request);
             Ok(())
         }
-    }
 
-    pub struct UPClientBar;
-
-    #[async_trait]
-    impl UTransport for UPClientBar {
-        async fn send(&self, _message: UMessage) -> Result<(), UStatus> {
+        async fn fetch_subscribers(
+            &self,
+            _fetch_subscribers_request: FetchSubscribersRequest,
+        ) -> Result<FetchSubscribersResponse, UStatus> {
             todo!()
         }
 
-        async fn receive(
-            &self,
-            _source_filter: &UUri,
-            _sink_filter: Option<&UUri>,
-        ) -> Result<UMessage, UStatus> {
+        async fn reset(&self, _reset_request: ResetRequest) -> Result<ResetResponse, UStatus> {
             todo!()
         }
+    }
 
-        async fn register_listener(
-            &self,
-            source_filter: &UUri,
-            _sink_filter: Option<&UUri>,
-            _listener: Arc<dyn UListener>,
-        ) -> Result<(), UStatus> {
-            println!(
-                "UPClientBar: registering source_filter: {:?}",
-                source_filter
-            );
-            Ok(())
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shutdown_unregisters_from_subscription_change_notifications() {
+        let usubscription = Arc::new(RecordingUSubscription::default());
+        let mut streamer = UStreamer::new_async("test-streamer", 16, usubscription.clone())
+            .await
+            .unwrap();
+        assert_eq!(usubscription.registered.lock().unwrap().len(), 1);
+        assert!(usubscription.unregistered.lock().unwrap().is_empty());
+
+        streamer.shutdown(Duration::from_secs(1)).await;
+
+        assert_eq!(
+            *usubscription.unregistered.lock().unwrap(),
+            *usubscription.registered.lock().unwrap()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shutdown_unregisters_listeners_and_drains_queues() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+        let out_endpoint = Endpoint::new("out-endpoint", "authority-b", out_transport);
+
+        let mut streamer = make_test_streamer(&[]);
+        assert!(streamer
+            .add_forwarding_rule(in_endpoint.clone(), out_endpoint.clone())
+            .await
+            .is_ok());
+
+        out_recording_transport.delay_sends_by(Duration::from_millis(10));
+        let listener = in_recording_transport.registered_listener();
+        for _ in 0..3 {
+            listener.on_receive(UMessage::default()).await;
         }
 
-        async fn unregister_listener(
-            &self,
-            source_filter: &UUri,
-            _sink_filter: Option<&UUri>,
-            _listener: Arc<dyn UListener>,
-        ) -> Result<(), UStatus> {
-            println!(
-                "UPClientBar: unregistering source_filter: {:?}",
-                source_filter
-            );
-            Ok(())
+        streamer.shutdown(Duration::from_secs(5)).await;
+
+        assert_eq!(out_recording_transport.sent_messages().len(), 3);
+        assert_eq!(
+            in_recording_transport.unregister_calls(),
+            in_recording_transport.register_calls()
+        );
+        assert_eq!(streamer.stats().await, UStreamerStats::default());
+        assert!(streamer
+            .transport_forwarders
+            .forwarders
+            .lock()
+            .await
+            .is_empty());
+
+        // the streamer can be used again after shutdown
+        assert!(streamer
+            .add_forwarding_rule(in_endpoint, out_endpoint)
+            .await
+            .is_ok());
+        streamer.shutdown(Duration::from_secs(5)).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shutdown_discards_messages_left_after_drain_timeout() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let mut streamer = make_test_streamer(&[]);
+        assert!(streamer
+            .add_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
+            )
+            .await
+            .is_ok());
+
+        out_recording_transport.delay_sends_by(Duration::from_millis(200));
+        let listener = in_recording_transport.registered_listener();
+        for _ in 0..5 {
+            listener.on_receive(UMessage::default()).await;
+        }
+
+        let started = std::time::Instant::now();
+        streamer.shutdown(Duration::from_millis(50)).await;
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        // only the send in flight at the deadline completes
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(out_recording_transport.sent_messages().len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn drop_without_shutdown_unregisters_listeners() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = Arc::new(RecordingTransport::default());
+
+        let mut streamer = make_test_streamer(&[]);
+        assert!(streamer
+            .add_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
+            )
+            .await
+            .is_ok());
+        drop(streamer);
+
+        for _ in 0..200 {
+            if !in_recording_transport.unregister_calls().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
         }
+        assert_eq!(
+            in_recording_transport.unregister_calls(),
+            in_recording_transport.register_calls()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retry_policy_retries_retryable_send_failures() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let retry_policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let mut streamer = make_test_streamer(&[]);
+        assert!(streamer
+            .add_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport)
+                    .with_retry_policy(retry_policy),
+            )
+            .await
+            .is_ok());
+        let listener = in_recording_transport.registered_listener();
+
+        // recovers within max_attempts
+        out_recording_transport.fail_next_sends_with(
+            2,
+            UStatus::fail_with_code(UCode::UNAVAILABLE, "broker hiccup"),
+        );
+        listener.on_receive(UMessage::default()).await;
+        let stats = wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 1).await;
+        assert_eq!(stats.rules[0].counts.retries, 2);
+        assert_eq!(stats.rules[0].counts.send_failures, 0);
+
+        // not retryable
+        out_recording_transport.fail_next_sends_with(
+            1,
+            UStatus::fail_with_code(UCode::INVALID_ARGUMENT, "rejected"),
+        );
+        listener.on_receive(UMessage::default()).await;
+        let stats =
+            wait_for_stats(&streamer, |stats| stats.rules[0].counts.send_failures == 1).await;
+        assert_eq!(stats.rules[0].counts.retries, 2);
+
+        // exhausts max_attempts
+        out_recording_transport.fail_next_sends_with(
+            3,
+            UStatus::fail_with_code(UCode::UNAVAILABLE, "broker down"),
+        );
+        listener.on_receive(UMessage::default()).await;
+        let stats =
+            wait_for_stats(&streamer, |stats| stats.rules[0].counts.send_failures == 2).await;
+        assert_eq!(stats.rules[0].counts.retries, 4);
+        assert_eq!(stats.out_transports[0].counts.retries, 4);
+        assert_eq!(out_recording_transport.sent_messages().len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dead_letter_sink_receives_undeliverable_messages() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+        let out_endpoint = Endpoint::new("out-endpoint", "authority-b", out_transport);
+
+        let mut streamer = make_test_streamer(&[]);
+        let dead_letter_sink = Arc::new(RingBufferDeadLetterSink::new(10));
+        streamer.set_dead_letter_sink(dead_letter_sink.clone());
+        let rule_id = streamer
+            .add_forwarding_rule(in_endpoint, out_endpoint)
+            .await
+            .unwrap();
+        let listener = in_recording_transport.registered_listener();
+
+        let mut shm_message = UMessage::default();
+        shm_message
+            .attributes
+            .mut_or_insert_default()
+            .payload_format = UPayloadFormat::UPAYLOAD_FORMAT_SHM.into();
+        listener.on_receive(shm_message.clone()).await;
+
+        let send_failure = UStatus::fail_with_code(UCode::INTERNAL, "forced send failure");
+        out_recording_transport.fail_sends_with(Some(send_failure.clone()));
+        listener.on_receive(UMessage::default()).await;
+        wait_for_stats(&streamer, |stats| stats.rules[0].counts.send_failures == 1).await;
+
+        let dead_letters = dead_letter_sink.dead_letters();
+        assert_eq!(dead_letters.len(), 2);
+        assert!(dead_letters
+            .iter()
+            .all(|dead_letter| dead_letter.rule_id == rule_id));
+        assert_eq!(
+            dead_letters[0].reason,
+            DeadLetterReason::SharedMemoryPayload
+        );
+        assert_eq!(dead_letters[0].message, shm_message);
+        assert_eq!(
+            dead_letters[1].reason,
+            DeadLetterReason::SendFailed(send_failure)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn expired_messages_are_not_forwarded() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let mut streamer = make_test_streamer(&[]);
+        assert!(streamer
+            .add_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
+            )
+            .await
+            .is_ok());
+        let listener = in_recording_transport.registered_listener();
+        let topic = UUri::from_str("//authority-a/5BA0/1/8001").unwrap();
+        let message_with_ttl = |ttl| {
+            UMessageBuilder::publish(topic.clone())
+                .with_ttl(ttl)
+                .build()
+                .unwrap()
+        };
+
+        // expired on receive
+        let expired_message = message_with_ttl(20);
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        listener.on_receive(expired_message).await;
+
+        // expires while waiting in the queue behind a slow send
+        out_recording_transport.delay_sends_by(Duration::from_millis(200));
+        listener.on_receive(message_with_ttl(10_000)).await;
+        listener.on_receive(message_with_ttl(100)).await;
+
+        let stats = wait_for_stats(&streamer, |stats| {
+            stats.rules[0].counts.forwarded == 1 && stats.rules[0].counts.expired == 2
+        })
+        .await;
+        assert_eq!(stats.out_transports[0].counts.expired, 1);
+        assert_eq!(stats.rules[0].counts.dropped, 0);
+        assert_eq!(out_recording_transport.sent_messages().len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rules_are_listed_and_deleted_by_id() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = Arc::new(RecordingTransport::default());
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+        let out_endpoint_b = Endpoint::new("out-endpoint-b", "authority-b", out_transport.clone());
+        let out_endpoint_c = Endpoint::new("out-endpoint-c", "authority-c", out_transport);
+        let filter = MessageFilter {
+            message_types: vec![UMessageType::UMESSAGE_TYPE_PUBLISH],
+            ..Default::default()
+        };
+
+        let mut streamer = make_test_streamer(&[]);
+        let rule_b = streamer
+            .add_forwarding_rule(in_endpoint.clone(), out_endpoint_b.clone())
+            .await
+            .unwrap();
+        let rule_c = streamer
+            .add_filtered_forwarding_rule(
+                in_endpoint.clone(),
+                out_endpoint_c.clone(),
+                filter.clone(),
+            )
+            .await
+            .unwrap();
+        assert_ne!(rule_b, rule_c);
+
+        let rules = streamer.rules().await;
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].id, rule_b);
+        assert_eq!(
+            (
+                rules[0].in_endpoint.as_str(),
+                rules[0].in_authority.as_str()
+            ),
+            ("in-endpoint", "authority-a")
+        );
+        assert_eq!(
+            (
+                rules[0].out_endpoint.as_str(),
+                rules[0].out_authority.as_str()
+            ),
+            ("out-endpoint-b", "authority-b")
+        );
+        assert_eq!(rules[0].filter, MessageFilter::default());
+        assert_eq!(rules[0].state, RuleState::Active);
+        assert_eq!(rules[1].id, rule_c);
+        assert_eq!(rules[1].filter, filter);
+        assert!(rules[0].created_at <= rules[1].created_at);
+
+        assert_eq!(streamer.delete_forwarding_rule_by_id(rule_b).await, Ok(()));
+        assert_eq!(
+            in_recording_transport.unregister_call_count(
+                &uauthority_to_uuri("authority-a"),
+                Some(&uauthority_to_uuri("authority-b"))
+            ),
+            1
+        );
+        let rules = streamer.rules().await;
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].id, rule_c);
+        assert_eq!(
+            streamer
+                .delete_forwarding_rule_by_id(rule_b)
+                .await
+                .unwrap_err()
+                .get_code(),
+            UCode::NOT_FOUND
+        );
+
+        // IDs are not reused
+        let rule_b_again = streamer
+            .add_forwarding_rule(in_endpoint, out_endpoint_b)
+            .await
+            .unwrap();
+        assert_ne!(rule_b_again, rule_b);
+        assert_ne!(rule_b_again, rule_c);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn messages_coming_back_to_a_rule_are_dropped() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let mut streamer = make_test_streamer(&[]).with_loop_detection(LoopDetection {
+            capacity: 16,
+            log_loops: true,
+        });
+        assert!(streamer
+            .add_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
+            )
+            .await
+            .is_ok());
+        let listener = in_recording_transport.registered_listener();
+        let topic = UUri::from_str("//authority-a/5BA0/1/8001").unwrap();
+        let message = UMessageBuilder::publish(topic.clone()).build().unwrap();
+
+        listener.on_receive(message.clone()).await;
+        listener.on_receive(message).await;
+        listener
+            .on_receive(UMessageBuilder::publish(topic).build().unwrap())
+            .await;
+
+        let stats = wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 2).await;
+        assert_eq!(stats.rules[0].counts.received, 3);
+        assert_eq!(stats.rules[0].counts.looped, 1);
+        assert_eq!(out_recording_transport.sent_messages().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn messages_dropped_by_a_rule_are_not_taken_for_loops() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let mut streamer = make_test_streamer(&[]).with_loop_detection(LoopDetection {
+            capacity: 16,
+            log_loops: true,
+        });
+        streamer
+            .add_forwarding_rule_with_options(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
+                ForwardingRuleOptions::default().with_rate_limit(RateLimit {
+                    messages_per_second: Some(1),
+                    action: RateLimitAction::Drop,
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap();
+        let listener = in_recording_transport.registered_listener();
+        let topic = UUri::from_str("//authority-a/5BA0/1/8001").unwrap();
+        let rate_limited = UMessageBuilder::publish(topic.clone()).build().unwrap();
+
+        listener
+            .on_receive(UMessageBuilder::publish(topic).build().unwrap())
+            .await;
+        listener.on_receive(rate_limited.clone()).await;
+        wait_for_stats(&streamer, |stats| stats.rules[0].counts.dropped == 1).await;
+
+        // resent once the rate limit admits it again
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        listener.on_receive(rate_limited.clone()).await;
+        let stats = wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 2).await;
+        assert_eq!(stats.rules[0].counts.looped, 0);
+        assert_eq!(out_recording_transport.sent_messages()[1], rate_limited);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_simple_with_a_single_input_and_output_endpoint() {
-        // Local endpoint
-        let local_authority = "local";
-        let local_transport: Arc<dyn UTransport> = Arc::new(UPClientFoo);
-        let local_endpoint =
-            Endpoint::new("local_endpoint", local_authority, local_transport.clone());
+    async fn duplicates_over_redundant_in_endpoints_are_suppressed() {
+        let primary_recording_transport = Arc::new(RecordingTransport::default());
+        let backup_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let primary_transport: Arc<dyn UTransport> = primary_recording_transport.clone();
+        let backup_transport: Arc<dyn UTransport> = backup_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+        let out_endpoint = Endpoint::new("out-endpoint", "authority-b", out_transport)
+            .with_deduplication(Deduplication::default());
+
+        let mut streamer = make_test_streamer(&[]);
+        for in_endpoint in [
+            Endpoint::new("primary-endpoint", "authority-a", primary_transport),
+            Endpoint::new("backup-endpoint", "authority-a", backup_transport),
+        ] {
+            assert!(streamer
+                .add_forwarding_rule(in_endpoint, out_endpoint.clone())
+                .await
+                .is_ok());
+        }
+        let topic = UUri::from_str("//authority-a/5BA0/1/8001").unwrap();
+        let message = UMessageBuilder::publish(topic.clone()).build().unwrap();
 
-        // A remote endpoint
-        let remote_authority = "remote";
-        let remote_transport: Arc<dyn UTransport> = Arc::new(UPClientBar);
-        let remote_endpoint = Endpoint::new(
-            "remote_endpoint",
-            remote_authority,
-            remote_transport.clone(),
+        primary_recording_transport
+            .registered_listener()
+            .on_receive(message.clone())
+            .await;
+        backup_recording_transport
+            .registered_listener()
+            .on_receive(message)
+            .await;
+        backup_recording_transport
+            .registered_listener()
+            .on_receive(UMessageBuilder::publish(topic).build().unwrap())
+            .await;
+
+        let stats = wait_for_stats(&streamer, |stats| {
+            stats.out_transports[0].counts.forwarded == 2
+        })
+        .await;
+        assert_eq!(stats.out_transports[0].counts.duplicates, 1);
+        let duplicates: Vec<_> = stats
+            .rules
+            .iter()
+            .map(|rule| (rule.in_endpoint.as_str(), rule.counts.duplicates))
+            .collect();
+        assert_eq!(
+            duplicates,
+            vec![("backup-endpoint", 1), ("primary-endpoint", 0)]
         );
+        assert_eq!(out_recording_transport.sent_messages().len(), 2);
+    }
 
-        let subscription_path =
-            "../utils/usubscription-static-file/static-configs/testdata.json".to_string();
-        let usubscription = Arc::new(USubscriptionStaticFile::new(subscription_path));
-        let mut ustreamer = match UStreamer::new("foo_bar_streamer", 100, usubscription) {
-            Ok(streamer) => streamer,
-            Err(error) => panic!("Failed to create uStreamer: {}", error),
-        };
-        // Add forwarding rules to endpoint local<->remote
-        assert!(ustreamer
-            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint.clone())
-            .await
-            .is_ok());
-        assert!(ustreamer
-            .add_forwarding_rule(remote_endpoint.clone(), local_endpoint.clone())
+    #[tokio::test(flavor = "multi_thread")]
+    async fn refreshing_an_endpoint_registers_its_listeners_again() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        in_recording_transport.fail_duplicate_registers_with(UStatus::fail_with_code(
+            UCode::ALREADY_EXISTS,
+            "duplicate listener registration",
+        ));
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = Arc::new(RecordingTransport::default());
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+        let out_endpoint = Endpoint::new("out-endpoint", "authority-b", out_transport);
+
+        let mut streamer =
+            make_test_streamer(&[("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+        assert!(streamer
+            .add_forwarding_rule(in_endpoint.clone(), out_endpoint.clone())
             .await
             .is_ok());
 
-        // Add forwarding rules to endpoint local<->local, should report an error
-        assert!(ustreamer
-            .add_forwarding_rule(local_endpoint.clone(), local_endpoint.clone())
-            .await
-            .is_err());
+        assert_eq!(streamer.refresh_endpoint(in_endpoint.clone()).await, Ok(()));
+        assert_eq!(streamer.refresh_endpoint(out_endpoint).await, Ok(()));
 
-        // Rule already exists so it should report an error
-        assert!(ustreamer
-            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint.clone())
-            .await
-            .is_err());
+        let request_source = uauthority_to_uuri("authority-a");
+        let request_sink = uauthority_to_uuri("authority-b");
+        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
+        assert_eq!(
+            in_recording_transport.register_call_count(&request_source, Some(&request_sink)),
+            2
+        );
+        assert_eq!(
+            in_recording_transport.register_call_count(&publish_source, None),
+            2
+        );
+        assert_eq!(streamer.stats().await.rules[0].registered_listeners, 2);
 
-        // Add forwarding rules to endpoint remote<->remote, should report an error
-        assert!(ustreamer
-            .add_forwarding_rule(remote_endpoint.clone(), remote_endpoint.clone())
-            .await
-            .is_err());
+        in_recording_transport.set_register_failure(
+            &publish_source,
+            None,
+            UStatus::fail_with_code(UCode::UNAVAILABLE, "disconnected"),
+        );
+        let err = streamer.refresh_endpoint(in_endpoint).await.unwrap_err();
+        assert_eq!(err.get_code(), UCode::UNAVAILABLE);
+        assert_eq!(streamer.stats().await.rules[0].registered_listeners, 1);
 
-        // remove valid routing rules
-        assert!(ustreamer
-            .delete_forwarding_rule(local_endpoint.clone(), remote_endpoint.clone())
-            .await
-            .is_ok());
-        assert!(ustreamer
-            .delete_forwarding_rule(remote_endpoint.clone(), local_endpoint.clone())
+        let err = streamer
+            .refresh_endpoint(Endpoint::new(
+                "other-endpoint",
+                "authority-a",
+                Arc::new(RecordingTransport::default()),
+            ))
             .await
-            .is_ok());
-
-        // Try and remove a rule that doesn't exist, should report an error
-        assert!(ustreamer
-            .delete_forwarding_rule(local_endpoint.clone(), remote_endpoint.clone())
+            .unwrap_err();
+        assert_eq!(err.get_code(), UCode::NOT_FOUND);
+        let err = streamer
+            .refresh_endpoint(Endpoint::new(
+                "in-endpoint",
+                "authority-c",
+                Arc::new(RecordingTransport::default()),
+            ))
             .await
-            .is_err());
+            .unwrap_err();
+        assert_eq!(err.get_code(), UCode::INVALID_ARGUMENT);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_advanced_where_there_is_a_local_endpoint_and_two_remote_endpoints() {
-        // Local endpoint
-        let local_authority = "local";
-        let local_transport: Arc<dyn UTransport> = Arc::new(UPClientFoo);
-        let local_endpoint =
-            Endpoint::new("local_endpoint", local_authority, local_transport.clone());
+    async fn refreshing_an_endpoint_with_a_new_transport_moves_its_rules() {
+        let old_recording_transport = Arc::new(RecordingTransport::default());
+        let new_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let old_transport: Arc<dyn UTransport> = old_recording_transport.clone();
+        let new_transport: Arc<dyn UTransport> = new_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+        let out_endpoint = Endpoint::new("out-endpoint", "authority-b", out_transport);
+
+        let mut streamer = make_test_streamer(&[]);
+        let rule_id = streamer
+            .add_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", old_transport),
+                out_endpoint,
+            )
+            .await
+            .unwrap();
+        let topic = UUri::from_str("//authority-a/5BA0/1/8001").unwrap();
+        old_recording_transport
+            .registered_listener()
+            .on_receive(UMessageBuilder::publish(topic.clone()).build().unwrap())
+            .await;
+        wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 1).await;
 
-        // Remote endpoint - A
-        let remote_authority_a = "remote_a";
-        let remote_transport_a: Arc<dyn UTransport> = Arc::new(UPClientBar);
-        let remote_endpoint_a = Endpoint::new(
-            "remote_endpoint_a",
-            remote_authority_a,
-            remote_transport_a.clone(),
+        assert_eq!(
+            streamer
+                .refresh_endpoint(Endpoint::new("in-endpoint", "authority-a", new_transport))
+                .await,
+            Ok(())
         );
 
-        // Remote endpoint - B
-        let remote_authority_b = "remote_b";
-        let remote_transport_b: Arc<dyn UTransport> = Arc::new(UPClientBar);
-        let remote_endpoint_b = Endpoint::new(
-            "remote_endpoint_b",
-            remote_authority_b,
-            remote_transport_b.clone(),
+        let request_source = uauthority_to_uuri("authority-a");
+        let request_sink = uauthority_to_uuri("authority-b");
+        assert_eq!(
+            old_recording_transport.unregister_call_count(&request_source, Some(&request_sink)),
+            1
         );
+        assert_eq!(
+            new_recording_transport.register_call_count(&request_source, Some(&request_sink)),
+            1
+        );
+        let rules = streamer.rules().await;
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].id, rule_id);
 
-        let subscription_path =
-            "../utils/usubscription-static-file/static-configs/testdata.json".to_string();
-        let usubscription = Arc::new(USubscriptionStaticFile::new(subscription_path));
-        let mut ustreamer = match UStreamer::new("foo_bar_streamer", 100, usubscription) {
-            Ok(streamer) => streamer,
-            Err(error) => panic!("Failed to create uStreamer: {}", error),
-        };
+        new_recording_transport
+            .registered_listener()
+            .on_receive(UMessageBuilder::publish(topic).build().unwrap())
+            .await;
+        let stats = wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 2).await;
+        assert_eq!(stats.rules[0].counts.received, 2);
+        assert_eq!(out_recording_transport.sent_messages().len(), 2);
+    }
 
-        // Add forwarding rules to endpoint local<->remote_a
-        assert!(ustreamer
-            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint_a.clone())
-            .await
-            .is_ok());
-        assert!(ustreamer
-            .add_forwarding_rule(remote_endpoint_a.clone(), local_endpoint.clone())
+    #[tokio::test(flavor = "multi_thread")]
+    async fn paused_rules_buffer_messages_until_resumed() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let mut streamer = make_test_streamer(&[]);
+        let dead_letter_sink = Arc::new(RingBufferDeadLetterSink::new(10));
+        streamer.set_dead_letter_sink(dead_letter_sink.clone());
+        let rule_id = streamer
+            .add_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
+            )
             .await
-            .is_ok());
+            .unwrap();
+        let listener = in_recording_transport.registered_listener();
+        let topic = UUri::from_str("//authority-a/5BA0/1/8001").unwrap();
+        let messages: Vec<_> = (0..4)
+            .map(|_| UMessageBuilder::publish(topic.clone()).build().unwrap())
+            .collect();
 
-        // Add forwarding rules to endpoint local<->remote_b
-        assert!(ustreamer
-            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint_b.clone())
+        assert_eq!(
+            streamer
+                .pause_rule(rule_id, PauseAction::Buffer { max_messages: 2 })
+                .await,
+            Ok(())
+        );
+        assert_eq!(streamer.rules().await[0].state, RuleState::Paused);
+        for message in &messages[..3] {
+            listener.on_receive(message.clone()).await;
+        }
+        let counts = streamer.stats().await.rules[0].counts;
+        assert_eq!((counts.received, counts.paused, counts.dropped), (3, 3, 1));
+        assert!(out_recording_transport.sent_messages().is_empty());
+        assert_eq!(
+            dead_letter_sink
+                .dead_letters()
+                .into_iter()
+                .map(|dead_letter| dead_letter.reason)
+                .collect::<Vec<_>>(),
+            vec![DeadLetterReason::Paused]
+        );
+
+        assert_eq!(streamer.resume_rule(rule_id).await, Ok(()));
+        assert_eq!(streamer.rules().await[0].state, RuleState::Active);
+        listener.on_receive(messages[3].clone()).await;
+
+        let stats = wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 3).await;
+        assert_eq!(stats.rules[0].counts.received, 4);
+        assert_eq!(stats.rules[0].counts.paused, 3);
+        let sent_ids: Vec<_> = out_recording_transport
+            .sent_messages()
+            .iter()
+            .map(|message| message.attributes.id.clone())
+            .collect();
+        let expected_ids: Vec<_> = [&messages[0], &messages[1], &messages[3]]
+            .iter()
+            .map(|message| message.attributes.id.clone())
+            .collect();
+        assert_eq!(sent_ids, expected_ids);
+
+        assert_eq!(
+            streamer
+                .pause_rule(RuleId(42), PauseAction::Drop)
+                .await
+                .unwrap_err()
+                .get_code(),
+            UCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pausing_a_rule_leaves_rules_sharing_its_listener_running() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport_a = Arc::new(RecordingTransport::default());
+        let out_recording_transport_b = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+
+        let mut streamer = make_test_streamer(&[]);
+        let rule_a = streamer
+            .add_forwarding_rule(
+                in_endpoint.clone(),
+                Endpoint::new(
+                    "out-endpoint-a",
+                    "authority-b",
+                    out_recording_transport_a.clone(),
+                ),
+            )
             .await
-            .is_ok());
-        assert!(ustreamer
-            .add_forwarding_rule(remote_endpoint_b.clone(), local_endpoint.clone())
+            .unwrap();
+        let rule_b = streamer
+            .add_forwarding_rule(
+                in_endpoint,
+                Endpoint::new(
+                    "out-endpoint-b",
+                    "authority-b",
+                    out_recording_transport_b.clone(),
+                ),
+            )
             .await
-            .is_ok());
+            .unwrap();
+        let listener = in_recording_transport.registered_listener();
+        let topic = UUri::from_str("//authority-a/5BA0/1/8001").unwrap();
+        let message = UMessageBuilder::publish(topic).build().unwrap();
 
-        // Add forwarding rules to endpoint remote_a<->remote_b
-        assert!(ustreamer
-            .add_forwarding_rule(remote_endpoint_a.clone(), remote_endpoint_b.clone())
+        streamer
+            .pause_rule(rule_a, PauseAction::Buffer { max_messages: 10 })
             .await
-            .is_ok());
-        assert!(ustreamer
-            .add_forwarding_rule(remote_endpoint_b.clone(), remote_endpoint_a.clone())
+            .unwrap();
+        let states: Vec<_> = streamer
+            .rules()
             .await
-            .is_ok());
+            .iter()
+            .map(|rule| (rule.id, rule.state))
+            .collect();
+        assert_eq!(
+            states,
+            vec![(rule_a, RuleState::Paused), (rule_b, RuleState::Active)]
+        );
+        listener.on_receive(message.clone()).await;
+
+        wait_for_stats(&streamer, |stats| {
+            stats
+                .rules
+                .iter()
+                .map(|rule| rule.counts.forwarded)
+                .sum::<u64>()
+                == 1
+        })
+        .await;
+        assert!(out_recording_transport_a.sent_messages().is_empty());
+        assert_eq!(
+            out_recording_transport_b.sent_messages(),
+            vec![message.clone()]
+        );
+
+        // resuming rule B, which isn't paused, leaves rule A paused
+        streamer.resume_rule(rule_b).await.unwrap();
+        assert_eq!(streamer.rules().await[0].state, RuleState::Paused);
+        streamer.resume_rule(rule_a).await.unwrap();
+        wait_for_stats(&streamer, |stats| {
+            stats.rules.iter().all(|rule| rule.counts.forwarded == 1)
+        })
+        .await;
+        assert_eq!(out_recording_transport_a.sent_messages(), vec![message]);
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_advanced_where_there_is_a_local_endpoint_and_two_remote_endpoints_but_the_remote_endpoints_have_the_same_instance_of_utransport(
-    ) {
-        // Local endpoint
-        let local_authority = "local";
-        let local_transport: Arc<dyn UTransport> = Arc::new(UPClientFoo);
-        let local_endpoint =
-            Endpoint::new("local_endpoint", local_authority, local_transport.clone());
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: StdMutex<Vec<String>>,
+    }
 
-        let remote_transport: Arc<dyn UTransport> = Arc::new(UPClientBar);
+    impl RecordingObserver {
+        fn record(&self, event: String) {
+            self.events.lock().unwrap().push(event);
+        }
 
-        // Remote endpoint - A
-        let remote_authority_a = "remote_a";
-        let remote_endpoint_a = Endpoint::new(
-            "remote_endpoint_a",
-            remote_authority_a,
-            remote_transport.clone(),
-        );
+        fn events(&self) -> Vec<String> {
+            self.events.lock().unwrap().clone()
+        }
+    }
 
-        // Remote endpoint - B
-        let remote_authority_b = "remote_b";
-        let remote_endpoint_b = Endpoint::new(
-            "remote_endpoint_b",
-            remote_authority_b,
-            remote_transport.clone(),
-        );
+    impl StreamerObserver for RecordingObserver {
+        fn on_rule_added(&self, rule: &ForwardingRuleDescriptor) {
+            self.record(format!("rule added: {}", rule.id));
+        }
 
-        let subscription_path =
-            "../utils/usubscription-static-file/static-configs/testdata.json".to_string();
-        let usubscription = Arc::new(USubscriptionStaticFile::new(subscription_path));
-        let mut ustreamer = match UStreamer::new("foo_bar_streamer", 100, usubscription) {
-            Ok(streamer) => streamer,
-            Err(error) => panic!("Failed to create uStreamer: {}", error),
-        };
+        fn on_rule_removed(&self, rule: &ForwardingRuleDescriptor) {
+            self.record(format!("rule removed: {}", rule.id));
+        }
 
-        // Add forwarding rules to endpoint local<->remote_a
-        assert!(ustreamer
-            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint_a.clone())
+        fn on_listener_registered(&self, source_filter: &UUri, sink_filter: Option<&UUri>) {
+            self.record(format!(
+                "listener registered: {} -> {}",
+                source_filter.to_uri(false),
+                sink_filter
+                    .map(|sink| sink.to_uri(false))
+                    .unwrap_or_default()
+            ));
+        }
+
+        fn on_listener_registration_failed(
+            &self,
+            source_filter: &UUri,
+            _sink_filter: Option<&UUri>,
+            status: &UStatus,
+        ) {
+            self.record(format!(
+                "listener registration failed: {}, {:?}",
+                source_filter.to_uri(false),
+                status.get_code()
+            ));
+        }
+
+        fn on_message_forwarded(&self, rule_id: RuleId, _message: &UMessage) {
+            self.record(format!("message forwarded: {rule_id}"));
+        }
+
+        fn on_message_dropped(&self, rule_id: RuleId, reason: &DropReason, _message: &UMessage) {
+            self.record(format!("message dropped: {rule_id}, {reason:?}"));
+        }
+
+        fn on_send_failed(&self, rule_id: RuleId, status: &UStatus, _message: &UMessage) {
+            self.record(format!("send failed: {rule_id}, {:?}", status.get_code()));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn observer_receives_lifecycle_and_forwarding_events() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+        let observer = Arc::new(RecordingObserver::default());
+
+        let mut streamer = make_test_streamer(&[]);
+        streamer.set_observer(observer.clone());
+        let rule_id = streamer
+            .add_filtered_forwarding_rule(
+                in_endpoint.clone(),
+                Endpoint::new("out-endpoint", "authority-b", out_transport.clone()),
+                MessageFilter {
+                    message_types: vec![UMessageType::UMESSAGE_TYPE_PUBLISH],
+                    ..Default::default()
+                },
+            )
             .await
-            .is_ok());
-        assert!(ustreamer
-            .add_forwarding_rule(remote_endpoint_a.clone(), local_endpoint.clone())
+            .unwrap();
+        in_recording_transport.set_register_failure(
+            &uauthority_to_uuri("authority-a"),
+            Some(&uauthority_to_uuri("authority-c")),
+            UStatus::fail_with_code(UCode::UNAVAILABLE, "disconnected"),
+        );
+        assert!(streamer
+            .add_forwarding_rule(
+                in_endpoint,
+                Endpoint::new("out-endpoint-c", "authority-c", out_transport),
+            )
             .await
-            .is_ok());
+            .is_err());
 
-        // Add forwarding rules to endpoint local<->remote_b
-        assert!(ustreamer
-            .add_forwarding_rule(local_endpoint.clone(), remote_endpoint_b.clone())
+        let listener = in_recording_transport.registered_listener();
+        let topic = UUri::from_str("//authority-a/5BA0/1/8001").unwrap();
+        listener
+            .on_receive(UMessageBuilder::publish(topic.clone()).build().unwrap())
+            .await;
+        wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 1).await;
+        listener.on_receive(UMessage::default()).await;
+        out_recording_transport.fail_sends_with(Some(UStatus::fail_with_code(
+            UCode::INTERNAL,
+            "forced send failure",
+        )));
+        listener
+            .on_receive(UMessageBuilder::publish(topic).build().unwrap())
+            .await;
+        wait_for_stats(&streamer, |stats| stats.rules[0].counts.send_failures == 1).await;
+        streamer
+            .delete_forwarding_rule_by_id(rule_id)
             .await
-            .is_ok());
-        assert!(ustreamer
-            .add_forwarding_rule(remote_endpoint_b.clone(), local_endpoint.clone())
+            .unwrap();
+
+        assert_eq!(
+            observer.events(),
+            vec![
+                "listener registered: //authority-a/FFFFFFFF/FF/FFFF -> //authority-b/FFFFFFFF/FF/FFFF"
+                    .to_string(),
+                format!("rule added: {rule_id}"),
+                "listener registration failed: //authority-a/FFFFFFFF/FF/FFFF, UNAVAILABLE".to_string(),
+                format!("message forwarded: {rule_id}"),
+                format!("message dropped: {rule_id}, Filtered"),
+                format!("send failed: {rule_id}, INTERNAL"),
+                format!("rule removed: {rule_id}"),
+            ]
+        );
+    }
+
+    // a client on authority-a calling a service on authority-b through a pair of forwarding rules
+    async fn make_rpc_test_streamer(
+        client_transport: Arc<dyn UTransport>,
+        service_transport: Arc<dyn UTransport>,
+    ) -> UStreamer {
+        let client_endpoint = Endpoint::new("client-endpoint", "authority-a", client_transport);
+        let service_endpoint = Endpoint::new("service-endpoint", "authority-b", service_transport);
+        let mut streamer =
+            make_test_streamer(&[]).with_request_timeouts(RequestTimeouts::default());
+        streamer
+            .add_forwarding_rule(client_endpoint.clone(), service_endpoint.clone())
             .await
-            .is_ok());
+            .unwrap();
+        streamer
+            .add_forwarding_rule(service_endpoint, client_endpoint)
+            .await
+            .unwrap();
+        streamer
+    }
+
+    fn rpc_request(ttl: u32) -> UMessage {
+        UMessageBuilder::request(
+            UUri::from_str("//authority-b/D1A6/1/3").unwrap(),
+            UUri::from_str("//authority-a/5678/1/0").unwrap(),
+            ttl,
+        )
+        .build()
+        .unwrap()
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn publish_filter_respects_topic_authority() {
-        let recording_transport = Arc::new(RecordingTransport::default());
-        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
-        let forwarding_listeners = ForwardingListeners::new();
-        let (out_sender, _) = tokio::sync::broadcast::channel(16);
-        let subscription_cache =
-            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+    async fn unanswered_requests_get_a_deadline_exceeded_response() {
+        let client_recording_transport = Arc::new(RecordingTransport::default());
+        let service_recording_transport = Arc::new(RecordingTransport::default());
+        let streamer = make_rpc_test_streamer(
+            client_recording_transport.clone(),
+            service_recording_transport.clone(),
+        )
+        .await;
 
-        let insert_result = forwarding_listeners
-            .insert(
-                in_transport,
-                "authority-a",
-                "authority-b",
-                "test-forwarding",
-                out_sender,
-                subscription_cache,
-            )
+        let request = rpc_request(200);
+        client_recording_transport
+            .registered_listener()
+            .on_receive(request.clone())
             .await;
 
-        assert!(insert_result.is_ok());
-
-        let register_calls = recording_transport.register_calls();
-        let request_source = uauthority_to_uuri("authority-a");
-        let request_sink = uauthority_to_uuri("authority-b");
-        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
-
-        assert!(has_listener_call(
-            &register_calls,
-            &request_source,
-            Some(&request_sink)
-        ));
-        assert!(has_listener_call(&register_calls, &publish_source, None));
+        let mut sent_to_client = Vec::new();
+        for _ in 0..100 {
+            sent_to_client = client_recording_transport.sent_messages();
+            if !sent_to_client.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
         assert_eq!(
-            register_calls
-                .iter()
-                .filter(|call| call.sink_filter.is_none())
-                .count(),
-            1
+            service_recording_transport.sent_messages(),
+            vec![request.clone()]
+        );
+        assert_eq!(sent_to_client.len(), 1);
+        let timeout_response = &sent_to_client[0];
+        assert_eq!(
+            timeout_response.type_unchecked(),
+            UMessageType::UMESSAGE_TYPE_RESPONSE
+        );
+        assert_eq!(
+            timeout_response.request_id_unchecked(),
+            request.id_unchecked()
         );
+        assert_eq!(
+            timeout_response.commstatus(),
+            Some(UCode::DEADLINE_EXCEEDED)
+        );
+
+        // the service answers too late
+        service_recording_transport
+            .registered_listener()
+            .on_receive(
+                UMessageBuilder::response_for_request(&request.attributes)
+                    .build()
+                    .unwrap(),
+            )
+            .await;
+        let stats = wait_for_stats(&streamer, |stats| {
+            stats
+                .rules
+                .iter()
+                .any(|rule| rule.in_endpoint == "service-endpoint" && rule.counts.dropped == 1)
+        })
+        .await;
+        let response_rule = stats
+            .rules
+            .iter()
+            .find(|rule| rule.in_endpoint == "service-endpoint")
+            .unwrap();
+        assert_eq!(response_rule.counts.forwarded, 0);
+        assert_eq!(client_recording_transport.sent_messages().len(), 1);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn publish_filter_allows_left_wildcard() {
-        let recording_transport = Arc::new(RecordingTransport::default());
-        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
-        let forwarding_listeners = ForwardingListeners::new();
-        let (out_sender, _) = tokio::sync::broadcast::channel(16);
-        let subscription_cache =
-            make_subscription_cache(&[("//*/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+    async fn answered_requests_get_no_deadline_exceeded_response() {
+        let client_recording_transport = Arc::new(RecordingTransport::default());
+        let service_recording_transport = Arc::new(RecordingTransport::default());
+        let streamer = make_rpc_test_streamer(
+            client_recording_transport.clone(),
+            service_recording_transport.clone(),
+        )
+        .await;
+
+        let request = rpc_request(200);
+        let response = UMessageBuilder::response_for_request(&request.attributes)
+            .build()
+            .unwrap();
+        client_recording_transport
+            .registered_listener()
+            .on_receive(request)
+            .await;
+        service_recording_transport
+            .registered_listener()
+            .on_receive(response.clone())
+            .await;
+        wait_for_stats(&streamer, |stats| {
+            stats.rules.iter().all(|rule| rule.counts.forwarded == 1)
+        })
+        .await;
 
-        let insert_result = forwarding_listeners
-            .insert(
-                in_transport,
-                "authority-c",
-                "authority-b",
-                "test-forwarding",
-                out_sender,
-                subscription_cache,
+        // past the deadline of the request
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(client_recording_transport.sent_messages(), vec![response]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn requests_whose_response_is_dropped_get_a_deadline_exceeded_response() {
+        let client_recording_transport = Arc::new(RecordingTransport::default());
+        let service_recording_transport = Arc::new(RecordingTransport::default());
+        let client_transport: Arc<dyn UTransport> = client_recording_transport.clone();
+        let service_transport: Arc<dyn UTransport> = service_recording_transport.clone();
+        let client_endpoint = Endpoint::new("client-endpoint", "authority-a", client_transport);
+        let service_endpoint = Endpoint::new("service-endpoint", "authority-b", service_transport);
+
+        let mut streamer =
+            make_test_streamer(&[]).with_request_timeouts(RequestTimeouts::default());
+        streamer
+            .add_forwarding_rule(client_endpoint.clone(), service_endpoint.clone())
+            .await
+            .unwrap();
+        // the response doesn't pass the filter on its way back
+        streamer
+            .add_filtered_forwarding_rule(
+                service_endpoint,
+                client_endpoint,
+                MessageFilter {
+                    message_types: vec![UMessageType::UMESSAGE_TYPE_REQUEST],
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let request = rpc_request(200);
+        client_recording_transport
+            .registered_listener()
+            .on_receive(request.clone())
+            .await;
+        service_recording_transport
+            .registered_listener()
+            .on_receive(
+                UMessageBuilder::response_for_request(&request.attributes)
+                    .build()
+                    .unwrap(),
             )
             .await;
 
-        assert!(insert_result.is_ok());
+        let mut sent_to_client = Vec::new();
+        for _ in 0..100 {
+            sent_to_client = client_recording_transport.sent_messages();
+            if !sent_to_client.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(sent_to_client.len(), 1);
+        assert_eq!(
+            sent_to_client[0].request_id_unchecked(),
+            request.id_unchecked()
+        );
+        assert_eq!(
+            sent_to_client[0].commstatus(),
+            Some(UCode::DEADLINE_EXCEEDED)
+        );
+    }
 
-        let register_calls = recording_transport.register_calls();
-        let publish_source = UUri::try_from_parts("authority-c", 0x5BA0, 0x1, 0x8001).unwrap();
+    #[tokio::test(start_paused = true)]
+    async fn request_timeouts_run_on_the_forwarder_runtime() {
+        let client_recording_transport = Arc::new(RecordingTransport::default());
+        let service_recording_transport = Arc::new(RecordingTransport::default());
+        let _streamer = make_rpc_test_streamer(
+            client_recording_transport.clone(),
+            service_recording_transport.clone(),
+        )
+        .await;
 
-        assert!(has_listener_call(&register_calls, &publish_source, None));
+        let request = rpc_request(60_000);
+        client_recording_transport
+            .registered_listener()
+            .on_receive(request.clone())
+            .await;
+
+        // only a timer on this runtime follows its paused clock
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        let sent_to_client = client_recording_transport.sent_messages();
+        assert_eq!(sent_to_client.len(), 1);
         assert_eq!(
-            register_calls
-                .iter()
-                .filter(|call| call.sink_filter.is_none())
-                .count(),
-            1
+            sent_to_client[0].commstatus(),
+            Some(UCode::DEADLINE_EXCEEDED)
         );
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn publish_filter_blocks_unmapped_authority() {
-        let recording_transport = Arc::new(RecordingTransport::default());
-        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
-        let forwarding_listeners = ForwardingListeners::new();
-        let (out_sender, _) = tokio::sync::broadcast::channel(16);
-        let subscription_cache =
-            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234")]);
-
-        let insert_result = forwarding_listeners
-            .insert(
-                in_transport,
-                "authority-c",
-                "authority-b",
-                "test-forwarding",
-                out_sender,
-                subscription_cache,
+    async fn undeliverable_requests_get_an_error_response() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let mut streamer = make_test_streamer(&[])
+            .with_request_timeouts(RequestTimeouts::default())
+            .with_error_responses();
+        streamer
+            .add_filtered_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
+                MessageFilter {
+                    ue_ids: vec![0xD1A6..=0xD1A6],
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        out_recording_transport.fail_sends_with(Some(UStatus::fail_with_code(
+            UCode::INTERNAL,
+            "forced send failure",
+        )));
+        let listener = in_recording_transport.registered_listener();
+
+        let filtered_request = UMessageBuilder::request(
+            UUri::from_str("//authority-b/1234/1/3").unwrap(),
+            UUri::from_str("//authority-a/5678/1/0").unwrap(),
+            200,
+        )
+        .build()
+        .unwrap();
+        listener.on_receive(filtered_request.clone()).await;
+        let failing_request = rpc_request(200);
+        listener.on_receive(failing_request.clone()).await;
+        listener
+            .on_receive(
+                UMessageBuilder::publish(UUri::from_str("//authority-a/1234/1/8001").unwrap())
+                    .build()
+                    .unwrap(),
             )
             .await;
+        wait_for_stats(&streamer, |stats| stats.rules[0].counts.send_failures == 1).await;
 
-        assert!(insert_result.is_ok());
+        // past the deadline of the requests, which are answered once only
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        let mut error_responses = in_recording_transport.sent_messages();
+        error_responses.sort_by_key(|response| response.commstatus().map(|code| code as i32));
+        assert_eq!(error_responses.len(), 2);
+        assert_eq!(
+            error_responses[0].request_id_unchecked(),
+            filtered_request.id_unchecked()
+        );
+        assert_eq!(
+            error_responses[0].commstatus(),
+            Some(UCode::PERMISSION_DENIED)
+        );
+        assert_eq!(
+            error_responses[1].request_id_unchecked(),
+            failing_request.id_unchecked()
+        );
+        assert_eq!(error_responses[1].commstatus(), Some(UCode::UNAVAILABLE));
+    }
 
-        let register_calls = recording_transport.register_calls();
-        let request_source = uauthority_to_uuri("authority-c");
-        let request_sink = uauthority_to_uuri("authority-b");
-        let disallowed_publish_source =
-            UUri::try_from_parts("authority-c", 0x5BA0, 0x1, 0x8001).unwrap();
+    #[tokio::test(start_paused = true)]
+    async fn error_responses_are_sent_on_the_forwarder_runtime() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = Arc::new(RecordingTransport::default());
+
+        let mut streamer = make_test_streamer(&[]).with_error_responses();
+        streamer
+            .add_filtered_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
+                MessageFilter {
+                    ue_ids: vec![0x1234..=0x1234],
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        in_recording_transport.delay_sends_by(Duration::from_secs(60));
 
-        assert!(has_listener_call(
-            &register_calls,
-            &request_source,
-            Some(&request_sink)
-        ));
-        assert!(!has_listener_call(
-            &register_calls,
-            &disallowed_publish_source,
-            None
-        ));
+        let request = rpc_request(200);
+        in_recording_transport
+            .registered_listener()
+            .on_receive(request.clone())
+            .await;
+
+        // only a send on this runtime follows its paused clock
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        let error_responses = in_recording_transport.sent_messages();
+        assert_eq!(error_responses.len(), 1);
         assert_eq!(
-            register_calls
-                .iter()
-                .filter(|call| call.sink_filter.is_none())
-                .count(),
-            0
+            error_responses[0].request_id_unchecked(),
+            request.id_unchecked()
+        );
+        assert_eq!(
+            error_responses[0].commstatus(),
+            Some(UCode::PERMISSION_DENIED)
         );
     }
-
     #[tokio::test(flavor = "multi_thread")]
-    async fn right_side_wildcard_forwards_to_registered_authorities() {
-        let recording_transport = Arc::new(RecordingTransport::default());
-        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
-        let forwarding_listeners = ForwardingListeners::new();
-        let (out_sender, _) = tokio::sync::broadcast::channel(16);
-        let subscription_cache =
-            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//*/5678/1/1234")]);
-
-        assert!(forwarding_listeners
-            .insert(
-                in_transport.clone(),
-                "authority-a",
-                "authority-b",
-                "test-forwarding-b",
-                out_sender.clone(),
-                subscription_cache.clone(),
+    async fn access_policy_denies_messages_until_replaced() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let mut streamer = make_test_streamer(&[]);
+        let dead_letter_sink = Arc::new(RingBufferDeadLetterSink::new(10));
+        streamer.set_dead_letter_sink(dead_letter_sink.clone());
+        let rule_id = streamer
+            .add_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
             )
             .await
-            .is_ok());
-        assert!(forwarding_listeners
-            .insert(
-                in_transport,
-                "authority-a",
-                "authority-d",
-                "test-forwarding-d",
-                out_sender,
-                subscription_cache,
+            .unwrap();
+        streamer.set_access_policy(AccessPolicy {
+            allow: vec![AccessRule {
+                source_authority: Some("authority-a".to_string()),
+                sink_authority: Some("authority-b".to_string()),
+                out_endpoint: Some("out-endpoint".to_string()),
+                filter: MessageFilter {
+                    message_types: vec![UMessageType::UMESSAGE_TYPE_REQUEST],
+                    ..Default::default()
+                },
+            }],
+            deny: vec![],
+        });
+        let listener = in_recording_transport.registered_listener();
+        let request = rpc_request(10_000);
+        let publish =
+            UMessageBuilder::publish(UUri::from_str("//authority-a/5BA0/1/8001").unwrap())
+                .build()
+                .unwrap();
+
+        listener.on_receive(publish.clone()).await;
+        listener.on_receive(request.clone()).await;
+        let stats = wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 1).await;
+        assert_eq!(stats.rules[0].counts.denied, 1);
+        assert_eq!(out_recording_transport.sent_messages(), vec![request]);
+        let dead_letters = dead_letter_sink.dead_letters();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].rule_id, rule_id);
+        assert_eq!(dead_letters[0].reason, DeadLetterReason::Denied);
+        assert_eq!(dead_letters[0].message, publish);
+
+        streamer.set_access_policy(AccessPolicy::default());
+        listener.on_receive(publish.clone()).await;
+        let stats = wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 2).await;
+        assert_eq!(stats.rules[0].counts.denied, 1);
+        assert_eq!(out_recording_transport.sent_messages()[1], publish);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn filtered_forwarding_rule_forwards_only_matching_messages() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        let mut streamer = make_test_streamer(&[]);
+        assert!(streamer
+            .add_filtered_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
+                MessageFilter {
+                    message_types: vec![UMessageType::UMESSAGE_TYPE_REQUEST],
+                    ue_ids: vec![0xD1A6..=0xD1A6],
+                    ..Default::default()
+                },
             )
             .await
             .is_ok());
+        let listener = in_recording_transport.registered_listener();
+        let client = UUri::from_str("//authority-a/5678/1/0").unwrap();
 
-        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
-        let request_source = uauthority_to_uuri("authority-a");
-        let authority_b = uauthority_to_uuri("authority-b");
-        let authority_d = uauthority_to_uuri("authority-d");
+        let diagnostics_request = UMessageBuilder::request(
+            UUri::from_str("//authority-b/D1A6/1/3").unwrap(),
+            client.clone(),
+            10_000,
+        )
+        .build()
+        .unwrap();
+        listener.on_receive(diagnostics_request.clone()).await;
+        listener
+            .on_receive(
+                UMessageBuilder::request(
+                    UUri::from_str("//authority-b/1234/1/3").unwrap(),
+                    client,
+                    10_000,
+                )
+                .build()
+                .unwrap(),
+            )
+            .await;
+        listener
+            .on_receive(
+                UMessageBuilder::publish(UUri::from_str("//authority-a/D1A6/1/8001").unwrap())
+                    .build()
+                    .unwrap(),
+            )
+            .await;
 
+        let stats = wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 1).await;
+        assert_eq!(stats.rules[0].counts.received, 3);
+        assert_eq!(stats.rules[0].counts.filtered, 2);
+        assert_eq!(stats.out_transports[0].counts.received, 1);
         assert_eq!(
-            recording_transport.register_call_count(&publish_source, None),
-            2
-        );
-        assert_eq!(
-            recording_transport.register_call_count(&request_source, Some(&authority_b)),
-            1
-        );
-        assert_eq!(
-            recording_transport.register_call_count(&request_source, Some(&authority_d)),
-            1
+            out_recording_transport.sent_messages(),
+            vec![diagnostics_request]
         );
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn right_side_wildcard_does_not_forward_without_rule() {
-        let recording_transport = Arc::new(RecordingTransport::default());
-        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
-        let forwarding_listeners = ForwardingListeners::new();
-        let (out_sender, _) = tokio::sync::broadcast::channel(16);
-        let subscription_cache =
-            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//*/5678/1/1234")]);
-
-        assert!(forwarding_listeners
-            .insert(
-                in_transport.clone(),
-                "authority-a",
-                "authority-b",
-                "test-forwarding-b",
-                out_sender.clone(),
-                subscription_cache.clone(),
+    async fn rules_sharing_a_listener_apply_their_own_options() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport_a = Arc::new(RecordingTransport::default());
+        let out_recording_transport_b = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport_a: Arc<dyn UTransport> = out_recording_transport_a.clone();
+        let out_transport_b: Arc<dyn UTransport> = out_recording_transport_b.clone();
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+
+        let mut streamer = make_test_streamer(&[]);
+        let rule_a = streamer
+            .add_filtered_forwarding_rule(
+                in_endpoint.clone(),
+                Endpoint::new("out-endpoint-a", "authority-b", out_transport_a),
+                MessageFilter {
+                    ue_ids: vec![0xD1A6..=0xD1A6],
+                    ..Default::default()
+                },
             )
             .await
-            .is_ok());
-        assert!(forwarding_listeners
-            .insert(
-                in_transport,
-                "authority-a",
-                "authority-d",
-                "test-forwarding-d",
-                out_sender,
-                subscription_cache,
+            .unwrap();
+        streamer
+            .add_filtered_forwarding_rule(
+                in_endpoint,
+                Endpoint::new("out-endpoint-b", "authority-b", out_transport_b),
+                MessageFilter {
+                    ue_ids: vec![0x1234..=0x1234],
+                    ..Default::default()
+                },
             )
             .await
-            .is_ok());
+            .unwrap();
+        // the second rule reuses the listener registered for the first one
+        assert_eq!(in_recording_transport.register_calls().len(), 1);
+        let listener = in_recording_transport.registered_listener();
+        let client = UUri::from_str("//authority-a/5678/1/0").unwrap();
+        let request = |ue_id: u32| {
+            UMessageBuilder::request(
+                UUri::try_from_parts("authority-b", ue_id, 1, 3).unwrap(),
+                client.clone(),
+                10_000,
+            )
+            .build()
+            .unwrap()
+        };
 
-        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
-        let request_source = uauthority_to_uuri("authority-a");
-        let authority_e = uauthority_to_uuri("authority-e");
+        let (diagnostics_request, other_request) = (request(0xD1A6), request(0x1234));
+        listener.on_receive(diagnostics_request.clone()).await;
+        listener.on_receive(other_request.clone()).await;
 
+        let stats = wait_for_stats(&streamer, |stats| {
+            stats.rules.iter().all(|rule| rule.counts.forwarded == 1)
+        })
+        .await;
+        assert!(stats
+            .rules
+            .iter()
+            .all(|rule| rule.counts.received == 2 && rule.counts.filtered == 1));
         assert_eq!(
-            recording_transport.register_call_count(&publish_source, None),
-            2
+            out_recording_transport_a.sent_messages(),
+            vec![diagnostics_request]
         );
         assert_eq!(
-            recording_transport.register_call_count(&request_source, Some(&authority_e)),
-            0
+            out_recording_transport_b.sent_messages(),
+            vec![other_request]
         );
+
+        // the listener stays registered for the remaining rule
+        streamer.delete_forwarding_rule_by_id(rule_a).await.unwrap();
+        assert!(in_recording_transport.unregister_calls().is_empty());
+        let later_request = request(0x1234);
+        listener.on_receive(later_request.clone()).await;
+        wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 2).await;
+        assert_eq!(out_recording_transport_b.sent_messages()[1], later_request);
+        assert_eq!(out_recording_transport_a.sent_messages().len(), 1);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn publish_filter_blocks_mismatched_tuple() {
-        let recording_transport = Arc::new(RecordingTransport::default());
-        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
-        let forwarding_listeners = ForwardingListeners::new();
-        let (out_sender, _) = tokio::sync::broadcast::channel(16);
-        let subscription_cache =
-            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+    async fn transformer_rewrites_or_drops_messages_before_forwarding() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
 
-        assert!(forwarding_listeners
-            .insert(
-                in_transport,
-                "authority-a",
-                "authority-b",
-                "test-forwarding",
-                out_sender,
-                subscription_cache,
+        // raises the priority of text messages and drops all others
+        let transformer = |mut msg: UMessage| {
+            let attributes = msg.attributes.mut_or_insert_default();
+            if attributes.payload_format.enum_value_or_default()
+                != UPayloadFormat::UPAYLOAD_FORMAT_TEXT
+            {
+                return None;
+            }
+            attributes.priority = UPriority::UPRIORITY_CS5.into();
+            Some(msg)
+        };
+
+        let mut streamer = make_test_streamer(&[]);
+        assert!(streamer
+            .add_forwarding_rule_with_options(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
+                ForwardingRuleOptions::default().with_transformer(Arc::new(transformer)),
             )
             .await
             .is_ok());
+        let listener = in_recording_transport.registered_listener();
+        let topic = UUri::from_str("//authority-a/5BA0/1/8001").unwrap();
+
+        listener
+            .on_receive(
+                UMessageBuilder::publish(topic.clone())
+                    .build_with_payload("{}", UPayloadFormat::UPAYLOAD_FORMAT_JSON)
+                    .unwrap(),
+            )
+            .await;
+        listener
+            .on_receive(
+                UMessageBuilder::publish(topic)
+                    .build_with_payload("text", UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+                    .unwrap(),
+            )
+            .await;
 
-        let exact = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
-        let mismatched_ue_id = UUri::try_from_parts("authority-a", 0x5BA1, 0x1, 0x8001).unwrap();
-        let mismatched_version = UUri::try_from_parts("authority-a", 0x5BA0, 0x2, 0x8001).unwrap();
-        let mismatched_resource = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8002).unwrap();
-
-        assert_eq!(recording_transport.register_call_count(&exact, None), 1);
-        assert_eq!(
-            recording_transport.register_call_count(&mismatched_ue_id, None),
-            0
-        );
-        assert_eq!(
-            recording_transport.register_call_count(&mismatched_version, None),
-            0
-        );
+        let stats = wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 1).await;
+        assert_eq!(stats.rules[0].counts.filtered, 1);
+        let sent_messages = out_recording_transport.sent_messages();
+        assert_eq!(sent_messages.len(), 1);
         assert_eq!(
-            recording_transport.register_call_count(&mismatched_resource, None),
-            0
+            sent_messages[0].attributes.priority.enum_value_or_default(),
+            UPriority::UPRIORITY_CS5
         );
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn multi_map_allows_both_sources() {
-        let recording_transport = Arc::new(RecordingTransport::default());
-        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
-        let forwarding_listeners = ForwardingListeners::new();
-        let (out_sender, _) = tokio::sync::broadcast::channel(16);
-        let subscription_cache = make_subscription_cache(&[
-            ("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234"),
-            ("//authority-c/5BA0/1/8001", "//authority-b/5679/1/1234"),
-        ]);
+    async fn rule_rate_limit_drops_excess_messages() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
 
-        assert!(forwarding_listeners
-            .insert(
-                in_transport.clone(),
-                "authority-a",
-                "authority-b",
-                "test-forwarding-a",
-                out_sender.clone(),
-                subscription_cache.clone(),
-            )
-            .await
-            .is_ok());
-        assert!(forwarding_listeners
-            .insert(
-                in_transport,
-                "authority-c",
-                "authority-b",
-                "test-forwarding-c",
-                out_sender,
-                subscription_cache,
+        let mut streamer = make_test_streamer(&[]);
+        let dead_letter_sink = Arc::new(RingBufferDeadLetterSink::new(10));
+        streamer.set_dead_letter_sink(dead_letter_sink.clone());
+        assert!(streamer
+            .add_forwarding_rule_with_options(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
+                ForwardingRuleOptions::default().with_rate_limit(RateLimit {
+                    messages_per_second: Some(1),
+                    action: RateLimitAction::Drop,
+                    ..Default::default()
+                }),
             )
             .await
             .is_ok());
+        let listener = in_recording_transport.registered_listener();
 
-        let source_a = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
-        let source_c = UUri::try_from_parts("authority-c", 0x5BA0, 0x1, 0x8001).unwrap();
+        for _ in 0..3 {
+            listener.on_receive(UMessage::default()).await;
+        }
 
-        assert_eq!(recording_transport.register_call_count(&source_a, None), 1);
-        assert_eq!(recording_transport.register_call_count(&source_c, None), 1);
+        let stats = wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 1).await;
+        assert_eq!(stats.rules[0].counts.throttled, 2);
+        assert_eq!(stats.rules[0].counts.dropped, 2);
+        assert_eq!(stats.out_transports[0].counts.received, 1);
+        assert!(dead_letter_sink
+            .dead_letters()
+            .iter()
+            .all(|dead_letter| dead_letter.reason == DeadLetterReason::RateLimited));
+        assert_eq!(dead_letter_sink.dead_letters().len(), 2);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn publish_filter_dedupes_overlapping_rows() {
-        let recording_transport = Arc::new(RecordingTransport::default());
-        recording_transport.fail_duplicate_registers_with(UStatus::fail_with_code(
-            UCode::ALREADY_EXISTS,
-            "duplicate publish listener registration",
-        ));
-
-        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
-        let forwarding_listeners = ForwardingListeners::new();
-        let (out_sender, _) = tokio::sync::broadcast::channel(16);
-        let subscription_cache = make_subscription_cache(&[
-            ("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234"),
-            ("//authority-a/5BA0/1/8001", "//authority-b/5679/1/1234"),
-        ]);
+    async fn rules_report_the_options_in_effect_when_sharing_a_listener() {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport);
+        let rate_limit = RateLimit {
+            messages_per_second: Some(1),
+            action: RateLimitAction::Drop,
+            ..Default::default()
+        };
 
-        assert!(forwarding_listeners
-            .insert(
-                in_transport.clone(),
-                "authority-a",
-                "authority-b",
-                "test-forwarding",
-                out_sender,
-                subscription_cache.clone(),
+        let mut streamer = make_test_streamer(&[]);
+        streamer
+            .add_forwarding_rule(
+                in_endpoint.clone(),
+                Endpoint::new(
+                    "out-endpoint-a",
+                    "authority-b",
+                    Arc::new(RecordingTransport::default()),
+                ),
             )
             .await
-            .is_ok());
-
-        forwarding_listeners
-            .remove(
-                in_transport,
-                "authority-a",
-                "authority-b",
-                subscription_cache,
+            .unwrap();
+        streamer
+            .add_forwarding_rule_with_options(
+                in_endpoint,
+                Endpoint::new(
+                    "out-endpoint-b",
+                    "authority-b",
+                    Arc::new(RecordingTransport::default()),
+                ),
+                ForwardingRuleOptions::default().with_rate_limit(rate_limit),
             )
-            .await;
-
-        let request_source = uauthority_to_uuri("authority-a");
-        let request_sink = uauthority_to_uuri("authority-b");
-        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
+            .await
+            .unwrap();
+        let rules = streamer.rules().await;
+        assert_eq!(rules[0].rate_limit, None);
+        assert_eq!(rules[1].rate_limit, Some(rate_limit));
+        let listener = in_recording_transport.registered_listener();
+
+        for _ in 0..3 {
+            listener.on_receive(UMessage::default()).await;
+        }
 
-        assert_eq!(
-            recording_transport.register_call_count(&request_source, Some(&request_sink)),
-            1
-        );
-        assert_eq!(
-            recording_transport.unregister_call_count(&request_source, Some(&request_sink)),
-            1
-        );
-        assert_eq!(
-            recording_transport.register_call_count(&publish_source, None),
-            1
-        );
-        assert_eq!(
-            recording_transport.unregister_call_count(&publish_source, None),
-            1
-        );
+        let stats = wait_for_stats(&streamer, |stats| {
+            stats
+                .rules
+                .iter()
+                .map(|rule| rule.counts.forwarded)
+                .sum::<u64>()
+                == 4
+        })
+        .await;
+        let counts = |out_endpoint: &str| {
+            stats
+                .rules
+                .iter()
+                .find(|rule| rule.out_endpoint == out_endpoint)
+                .unwrap()
+                .counts
+        };
+        assert_eq!(counts("out-endpoint-a").forwarded, 3);
+        assert_eq!(counts("out-endpoint-a").throttled, 0);
+        assert_eq!(counts("out-endpoint-b").forwarded, 1);
+        assert_eq!(counts("out-endpoint-b").throttled, 2);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn add_forwarding_rule_rolls_back_on_listener_insert_failure() {
+    async fn out_endpoint_rate_limit_delays_sends() {
         let in_recording_transport = Arc::new(RecordingTransport::default());
         let out_recording_transport = Arc::new(RecordingTransport::default());
-
         let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
-        let out_transport: Arc<dyn UTransport> = out_recording_transport;
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
 
-        let in_endpoint = Endpoint::new("in-endpoint", "authority-a", in_transport.clone());
-        let out_endpoint = Endpoint::new("out-endpoint", "authority-b", out_transport.clone());
+        let mut streamer = make_test_streamer(&[]);
+        assert!(streamer
+            .add_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport).with_rate_limit(
+                    RateLimit {
+                        messages_per_second: Some(20),
+                        message_burst: Some(1),
+                        ..Default::default()
+                    }
+                ),
+            )
+            .await
+            .is_ok());
+        let listener = in_recording_transport.registered_listener();
 
-        let mut streamer =
-            make_test_streamer(&[("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            listener.on_receive(UMessage::default()).await;
+        }
 
-        let request_source = uauthority_to_uuri("authority-a");
-        let request_sink = uauthority_to_uuri("authority-b");
+        let stats = wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 3).await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(stats.out_transports[0].counts.throttled, 2);
+        assert_eq!(stats.rules[0].counts.throttled, 2);
+        assert_eq!(stats.rules[0].counts.dropped, 0);
+    }
 
-        in_recording_transport.set_register_failure(
-            &request_source,
-            Some(&request_sink),
-            UStatus::fail_with_code(UCode::INTERNAL, "forced listener insertion failure"),
-        );
+    #[tokio::test]
+    async fn new_async_works_on_current_thread_runtime() {
+        let usubscription = Arc::new(USubscriptionStaticFile::new(
+            "../utils/usubscription-static-file/static-configs/testdata.json".to_string(),
+        ));
 
-        assert!(streamer
-            .add_forwarding_rule(in_endpoint.clone(), out_endpoint.clone())
-            .await
-            .is_err());
-        assert_eq!(streamer.registered_forwarding_rules.lock().await.len(), 0);
-        assert_eq!(
-            streamer.transport_forwarders.forwarders.lock().await.len(),
-            0
-        );
+        let streamer = UStreamer::new_async("foo_bar_streamer", 100, usubscription).await;
 
-        in_recording_transport.clear_register_failure(&request_source, Some(&request_sink));
+        assert!(streamer.is_ok());
+    }
 
-        assert!(streamer
-            .add_forwarding_rule(in_endpoint.clone(), out_endpoint.clone())
-            .await
-            .is_ok());
-        assert_eq!(streamer.registered_forwarding_rules.lock().await.len(), 1);
-        assert_eq!(
-            streamer.transport_forwarders.forwarders.lock().await.len(),
-            1
-        );
+    #[test]
+    fn new_async_returns_error_outside_of_a_tokio_runtime() {
+        let usubscription = Arc::new(RecordingUSubscription::default());
 
-        assert!(streamer
-            .delete_forwarding_rule(in_endpoint, out_endpoint)
-            .await
-            .is_ok());
-        assert_eq!(streamer.registered_forwarding_rules.lock().await.len(), 0);
-        assert_eq!(
-            streamer.transport_forwarders.forwarders.lock().await.len(),
-            0
-        );
+        let Err(err) = futures::executor::block_on(UStreamer::new_async(
+            "foo_bar_streamer",
+            100,
+            usubscription.clone(),
+        )) else {
+            panic!("expected the streamer creation to fail");
+        };
+
+        assert_eq!(err.get_code(), UCode::FAILED_PRECONDITION);
+        assert!(usubscription.registered.lock().unwrap().is_empty());
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn unregistration_removes_publish_filters() {
-        let recording_transport = Arc::new(RecordingTransport::default());
-        let in_transport: Arc<dyn UTransport> = recording_transport.clone();
-        let forwarding_listeners = ForwardingListeners::new();
-        let (out_sender, _) = tokio::sync::broadcast::channel(16);
-        let subscription_cache =
-            make_subscription_cache(&[("//authority-a/5BA0/1/8001", "//authority-b/5678/1/1234")]);
+    #[tokio::test]
+    async fn new_async_returns_error_when_subscriptions_cannot_be_fetched() {
+        let usubscription = Arc::new(USubscriptionStaticFile::new(
+            "does/not/exist.json".to_string(),
+        ));
 
-        let insert_result = forwarding_listeners
-            .insert(
-                in_transport.clone(),
-                "authority-a",
-                "authority-b",
-                "test-forwarding",
-                out_sender,
-                subscription_cache.clone(),
-            )
-            .await;
+        let Err(err) = UStreamer::new_async("foo_bar_streamer", 100, usubscription).await else {
+            panic!("expected the streamer creation to fail");
+        };
 
-        assert!(insert_result.is_ok());
+        assert_eq!(err.get_code(), UCode::INVALID_ARGUMENT);
+        assert!(err.get_message().contains("Unable to fetch subscriptions"));
+    }
 
-        forwarding_listeners
-            .remove(
-                in_transport,
-                "authority-a",
-                "authority-b",
-                subscription_cache,
+    async fn forward_two_messages_and_shut_down(mut streamer: UStreamer) {
+        let in_recording_transport = Arc::new(RecordingTransport::default());
+        let out_recording_transport = Arc::new(RecordingTransport::default());
+        let in_transport: Arc<dyn UTransport> = in_recording_transport.clone();
+        let out_transport: Arc<dyn UTransport> = out_recording_transport.clone();
+
+        assert!(streamer
+            .add_forwarding_rule(
+                Endpoint::new("in-endpoint", "authority-a", in_transport),
+                Endpoint::new("out-endpoint", "authority-b", out_transport),
             )
-            .await;
+            .await
+            .is_ok());
+        let listener = in_recording_transport.registered_listener();
+        for _ in 0..2 {
+            listener.on_receive(UMessage::default()).await;
+        }
 
-        let unregister_calls = recording_transport.unregister_calls();
-        let request_source = uauthority_to_uuri("authority-a");
-        let request_sink = uauthority_to_uuri("authority-b");
-        let publish_source = UUri::try_from_parts("authority-a", 0x5BA0, 0x1, 0x8001).unwrap();
+        wait_for_stats(&streamer, |stats| stats.rules[0].counts.forwarded == 2).await;
+        streamer.shutdown(Duration::from_secs(5)).await;
+        assert_eq!(out_recording_transport.sent_messages().len(), 2);
+    }
 
-        assert!(has_listener_call(
-            &unregister_calls,
-            &request_source,
-            Some(&request_sink)
-        ));
-        assert!(has_listener_call(&unregister_calls, &publish_source, None));
+    #[tokio::test]
+    async fn forwarders_run_as_tasks_on_current_thread_runtime() {
+        forward_two_messages_and_shut_down(make_test_streamer(&[])).await;
+    }
+
+    #[tokio::test]
+    async fn forwarders_run_on_dedicated_threads_when_opted_in() {
+        let streamer =
+            make_test_streamer(&[]).with_forwarder_runtime(ForwarderRuntime::DedicatedThreads);
+        forward_two_messages_and_shut_down(streamer).await;
     }
 }
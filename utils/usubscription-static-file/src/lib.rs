@@ -167,7 +167,7 @@ impl USubscription for USubscriptionStaticFile {
         &self,
         _notifications_unregister_request: NotificationsRequest,
     ) -> Result<(), UStatus> {
-        todo!()
+        Ok(())
     }
 
     async fn fetch_subscribers(